@@ -1,10 +1,18 @@
 {
+  user_agent = "Mozilla/5.0 (X11; Linux x86_64; rv:140.0) Gecko/20100101 Firefox/140.0";
+  log_format = "pretty";
+
   dms = {
     discord = {
       user_token = { env = "DISCORD_AUTH"; };
       my_username = { env = "DEFAULT_USERNAME"; };
+      handshake_attempts = 3; # quick handshake retries before the reconnect backoff
+      compress = true; # zlib-stream gateway compression; off by default
+      intents = 37377; # GUILDS | GUILD_MESSAGES | DIRECT_MESSAGES | MESSAGE_CONTENT (default)
+      presence = "invisible"; # online, idle, dnd or invisible; left to Discord if unset
     };
     monitored_users = [ "play_me_once" "deevsdeevs" ];
+    notify_cooldown_minutes = 15; # per monitored chat; 0 notifies on every message
   };
 
   telegram = {
@@ -27,24 +35,68 @@
     info_channels = [
       "https://t.me/kopeechkav"
     ];
+    # temporarily stop watching these (any account's poll/info channels) without removing them from the lists above
+    excluded_channels = [ ];
+    # where telegram-channel-watch forwards poll_channels / info_channels messages; both default to channel_output
+    poll_output = "WatchingTT";
+    info_output = "WatchingTTInfo";
+    watch_active_hours = "08:00-23:00 Europe/Paris";
+    # only forward info_channels keyword matches in groups from admins or these senders (usernames or ids); anyone if unset
+    info_senders = { admins = true; allow = [ "@trusted_analyst" ]; };
+    # phrases (case-insensitive) and regexes that get an info_channels message forwarded; keywords default to a list of Russian market-sentiment phrases
+    info_keywords = [ "открытый интерес" "google trends" ];
+    info_regexes = [ "(?i)funding\\s+rates?" ];
+    # link previews per kind (dms, tweets, twitter_polls, youtube, email); all on except email by default
+    link_previews = { email = false; tweets = false; };
+    # outbound pacing; 0 turns a limit off
+    send_rate = { per_second = 25; per_chat_per_minute = 20; };
+    # drop notifications below this severity: low (YouTube uploads, closed polls), normal (DMs, tweets, polls, emails), high (pings, calls, keyword and budget alerts)
+    min_severity = "low";
+    # custom formats for email, youtube and twitter_poll notifications; ''${var?...} sections only show when var is non-empty
+    templates = { email = "From: \${from}\n\${reply_to?Reply-To: \${reply_to}\n}Subject: \${subject}\n\n\${body}"; };
+    # more accounts for telegram-channel-watch, each watching channels only it can see (must be able to post to channel_output)
+    watch_accounts = [
+      {
+        username = "@valera_alt";
+        phone = { env = "PHONE_NUMBER_ALT"; };
+        poll_channels = [ "https://t.me/private_signals" ];
+      }
+    ];
   };
 
   twitter = {
     # bearer_token from @valeratrades — does not have to match the posting account in oauth below
     bearer_token = { env = "TWITTER_MASTER_BEARER_TOKEN"; };
     sometimes_polls_list = "1507244316154023968";
-    everytime_polls_list = "1507245210547409040";
+    everytime_polls_list = "1507245210547409040"; # or by owner and name: "@valeratrades/Everytime polls"
+    # optional override, default: telegram.channel_output (also: everytime_polls_destination)
+    sometimes_polls_destination = "WatchingTTLow";
+    notify_all_tweets = [ "CryptoCred" ];
+    check_every = "1m"; # durations: `30s`, `5m`, `2h`, `1h30m`; also on youtube and email
     oauth = {
       acc_username = "valera_other";
       api_key = { env = "TWITTER_OTHER_API_PUBKEY"; };
       api_key_secret = { env = "TWITTER_OTHER_API_SECRET"; };
       access_token = { env = "TWITTER_OTHER_ACCESS_PUBKEY"; };
       access_token_secret = { env = "TWITTER_OTHER_ACCESS_SECRET"; };
+      # post with OAuth 2.0 instead (PKCE refresh token, needs offline.access):
+      # client_id = { env = "TWITTER_OTHER_CLIENT_ID"; };
+      # refresh_token = { env = "TWITTER_OTHER_REFRESH_TOKEN"; };
+    };
+    active_hours = {
+      start = "09:00";
+      end = "22:00";
+      tz = "Europe/Paris";
     };
     poll = {
       duration_hours = 24;
       schedule_every = "1w";
+      # schedule_cron = "0 9 * * MON"; # UTC, takes precedence over schedule_every
       num_of_retries = 5;
+      # replies posted under the poll as a thread, same ''${var} substitution as text
+      thread_followups = [ "Results in 24h, BTC at $\${btc_price} for reference" ];
+      allow_plain_tweets = false; # templates without options post as plain tweets
+      text_selection = "random";
       text = ''
 Sentiment check: $BTC, how are we feeling?
 
@@ -61,13 +113,29 @@ Sentiment check: $BTC, how are we feeling?
     channels = {
       hamaha = "UCI3uVtN-W5StRN1RsLNKV6g";
       test = "UCG5YX-fn1-EIv-sOSYMjSmg"; # Q: still relevant?
+      bankless = "@Bankless"; # `@handle`s and channel URLs are resolved to ids on startup
     };
+    skip_shorts = true;
+    title_keywords = [ "liquidation" "crash" ];
+    min_duration = "60s"; # skip shorter uploads (default 60s, "0s" to disable)
+    check_every = "5m";
+    # also post upload notifications to this Discord webhook
+    # discord_webhook = { env = "DISCORD_YOUTUBE_WEBHOOK"; };
   };
 
   email = {
     email = "valeratrades@gmail.com";
     ignore_patterns = [ "Alex Hormozi" "imperiumlabs" ];
+    always_forward_patterns = [ "^Mom <" ];
     claude_token = { env = "CLAUDE_TOKEN"; };
+    summarize_forwarded = true;
+    classify_concurrency = 5;
+    # alert when the day's (UTC) LLM spend (email and YouTube) crosses this; with pause_llm_over_budget, stop classifying
+    # until the next day, or with automated_over_budget, take emails needing the LLM as automated
+    llm_daily_budget_cents = 50;
+    pause_llm_over_budget = true;
+    # automated_over_budget = true;
+    retention_days = 90; # forget processed emails after this long (default 90; 0 keeps them)
     important_if_contains = {
       any = [];
       subject = [ "Appointment booked" ];
@@ -77,7 +145,14 @@ Sentiment check: $BTC, how are we feeling?
     auth = {
       imap = {
         pass = { env = "GOOGLE_MAIN_MAIL_PASS"; };
+        imap_host = "imap.gmail.com";
+        imap_port = 993;
+        starttls = false;
       };
     };
   };
+
+  clickhouse = {
+    url = "http://localhost:8123"; # HTTP interface; `health --deep` probes it with `SELECT 1`
+  };
 }
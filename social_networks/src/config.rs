@@ -1,4 +1,6 @@
+use color_eyre::eyre::Result;
 use social_networks_adapters::{email::EmailConfig, telegram_dms::TelegramConfig, twitter::TwitterConfig, youtube::YoutubeConfig};
+use social_networks_utils::logging::LogFormat;
 use v_utils::macros::{LiveSettings, MyConfigPrimitives, Settings};
 
 use crate::dms::DmsConfig;
@@ -20,4 +22,36 @@ pub struct AppConfig {
 	#[settings(skip)]
 	#[serde(default)]
 	pub email: Option<EmailConfig>,
+	/// Only probed by `health` for now
+	#[settings(skip)]
+	#[serde(default)]
+	pub clickhouse: Option<ClickhouseConfig>,
+	/// Sent with every outbound HTTP request; defaults to a browser-like string
+	#[primitives(skip)]
+	#[settings(skip)]
+	#[serde(default = "__default_user_agent")]
+	pub user_agent: String,
+	/// `json` writes the logs one event per line, for log shippers
+	#[primitives(skip)]
+	#[settings(skip)]
+	#[serde(default)]
+	pub log_format: LogFormat,
+}
+
+impl AppConfig {
+	/// What can be checked before any monitor starts: a broken notification template would otherwise only surface on
+	/// its first notification.
+	pub fn validate(&self) -> Result<()> {
+		self.telegram.templates.validate()
+	}
+}
+
+#[derive(Clone, Debug, Default, MyConfigPrimitives)]
+pub struct ClickhouseConfig {
+	/// HTTP interface, e.g. `http://localhost:8123`
+	pub url: String,
+}
+
+fn __default_user_agent() -> String {
+	social_networks_utils::http::DEFAULT_USER_AGENT.to_string()
 }
@@ -0,0 +1,24 @@
+//! `destinations`: where every configured Telegram destination points, as the params the Bot API gets. Nothing is
+//! sent.
+use colored::Colorize;
+use social_networks_adapters::telegram_notifier::describe_destination;
+
+use crate::config::AppConfig;
+
+pub fn main(config: &AppConfig) {
+	let telegram = &config.telegram;
+	let destinations = [
+		("telegram.channel_alerts", Some(&telegram.channel_alerts)),
+		("telegram.channel_output", Some(&telegram.channel_output)),
+		("telegram.poll_output", telegram.poll_output.as_ref()),
+		("telegram.info_output", telegram.info_output.as_ref()),
+		("twitter.everytime_polls_destination", config.twitter.everytime_polls_destination.as_ref()),
+		("twitter.sometimes_polls_destination", config.twitter.sometimes_polls_destination.as_ref()),
+	];
+	for (key, destination) in destinations {
+		match destination {
+			Some(destination) => println!("{key}: {}", describe_destination(destination)),
+			None => println!("{key}: {}", "unset, uses telegram.channel_output".dimmed()),
+		}
+	}
+}
@@ -1,13 +1,14 @@
-use std::collections::HashMap;
-
 use clap::Args;
-use jiff::Timestamp;
+use jiff::SignedDuration;
 use serde::Deserialize;
-use social_networks_adapters::{DmEvent, discord::DiscordConfig, telegram_notifier::TelegramNotifier};
-use tracing::{error, info};
+use social_networks_adapters::{AdapterError, Client, DiscordDms, DmEvent, TelegramDms, discord::DiscordConfig, telegram_dms::TelegramConfig, telegram_notifier::TelegramNotifier};
+use social_networks_utils::{
+	clock::{Cooldown, SystemClock},
+	shutdown, snooze,
+};
+use tracing::{debug, error, info};
 use v_utils::macros::MyConfigPrimitives;
 
-const MONITORED_USER_THROTTLE_SECS: i64 = 15 * 60;
 /// CLI args for the `dms` subcommand. Empty today, kept as a placeholder so the
 /// subcommand can grow flags without changing the command surface.
 #[derive(Args)]
@@ -28,9 +29,16 @@ pub struct DmsConfig {
 	#[serde(default)]
 	#[primitives(skip)]
 	pub discord: DiscordConfig,
+	/// Minimum gap between notifications about the same monitored user's chat; 0 notifies on every message
+	#[serde(default = "__default_notify_cooldown_minutes")]
+	pub notify_cooldown_minutes: u32,
 }
 
 impl DmsConfig {
+	fn notify_cooldown(&self) -> SignedDuration {
+		SignedDuration::from_mins(self.notify_cooldown_minutes.into())
+	}
+
 	fn monitored_matches(&self, platform: &str, username: &str) -> bool {
 		self.monitored_users.iter().any(|u| match u {
 			MonitoredUser::All(u) => u == username,
@@ -108,14 +116,33 @@ impl serde::Serialize for MonitoredUser {
 	}
 }
 
+/// Run the Discord and Telegram DM adapters and [`run`] over their events until either adapter gives up, or until a
+/// shutdown, which just drops them: none of them keeps state worth persisting.
+pub async fn listen(config: DmsConfig, telegram_config: TelegramConfig) -> Result<(), AdapterError> {
+	let (tx, rx) = tokio::sync::mpsc::unbounded_channel();
+	let notifier = TelegramNotifier::new(telegram_config.clone());
+	let mut discord = DiscordDms::new(config.discord.clone(), tx.clone());
+	let mut telegram = TelegramDms::new(telegram_config, tx);
+	tokio::select! {
+		e = discord.listen() => e,
+		e = telegram.listen() => e,
+		() = run(rx, config, notifier) => unreachable!("dms::run only returns when both adapters drop their senders"),
+		() = shutdown::requested() => Ok(()),
+	}
+}
+
 /// Consume DM events forever, applying notification rules. Returns when the event
 /// stream is closed (both adapters dropped their senders), which only happens on
 /// shutdown.
 pub async fn run(mut events: tokio::sync::mpsc::UnboundedReceiver<DmEvent>, config: DmsConfig, notifier: TelegramNotifier) {
-	// Throttle map for monitored-user notifications, keyed by (platform, chat_id).
-	let mut last_seen: HashMap<(&'static str, String), Timestamp> = HashMap::new();
+	// Throttle for monitored-user notifications, keyed by (platform, chat_id).
+	let mut cooldown: Cooldown<(&'static str, String)> = Cooldown::new(config.notify_cooldown(), SystemClock);
 
 	while let Some(event) = events.recv().await {
+		if snooze::is_snoozed("dms") {
+			debug!("DMs snoozed, dropping event");
+			continue;
+		}
 		match event {
 			DmEvent::IncomingCall { platform } => {
 				println!("Incoming call on {platform}");
@@ -133,6 +160,8 @@ pub async fn run(mut events: tokio::sync::mpsc::UnboundedReceiver<DmEvent>, conf
 				is_dm,
 				mentions_me,
 				is_reply_to_me,
+				attachments,
+				embeds,
 			} => {
 				let has_ping = text.contains("/ping");
 				let addressed_to_me = is_dm || mentions_me || is_reply_to_me;
@@ -147,24 +176,20 @@ pub async fn run(mut events: tokio::sync::mpsc::UnboundedReceiver<DmEvent>, conf
 					continue;
 				}
 
-				if is_dm && !has_ping && config.monitored_matches(platform, &sender) {
-					let now = Timestamp::now();
-					let key = (platform, chat_id);
-					let should_notify = match last_seen.get(&key) {
-						None => true,
-						Some(prev) => now.duration_since(*prev).as_secs() >= MONITORED_USER_THROTTLE_SECS,
-					};
-					if should_notify {
-						println!("{platform} message from monitored user {sender}: {text}");
-						if let Err(e) = notifier.send_monitored_user_message(&sender, platform).await {
-							error!("Error sending monitored user notification: {e}");
-						} else {
-							info!("Successfully sent monitored user notification for: {sender}");
-						}
+				// Every message restarts the chat's cooldown, even one that was not notified about
+				if is_dm && !has_ping && config.monitored_matches(platform, &sender) && cooldown.hit((platform, chat_id)) {
+					println!("{platform} message from monitored user {sender}: {text}");
+					match notifier.send_monitored_user_message(&sender, platform, &attachments, &embeds).await {
+						Ok(Some(_)) => info!("Successfully sent monitored user notification for: {sender}"),
+						Ok(None) => {}
+						Err(e) => error!("Error sending monitored user notification: {e}"),
 					}
-					last_seen.insert(key, now);
 				}
 			}
 		}
 	}
 }
+
+fn __default_notify_cooldown_minutes() -> u32 {
+	15
+}
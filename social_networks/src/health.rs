@@ -1,13 +1,23 @@
-use std::path::PathBuf;
+use std::{future::Future, path::PathBuf, time::Duration};
 
-use color_eyre::Result;
+use clap::Args;
+use color_eyre::{Result, eyre::bail};
 use colored::Colorize;
+use jiff::Timestamp;
+use social_networks_adapters::{email::llm_cost_day, telegram_notifier::bot_username, twitter::verify_bearer_token};
+use social_networks_utils::{
+	db::{Database, total_llm_spend},
+	heartbeat, http,
+	status::{self, MonitorState, MonitorStatus},
+};
 
 use crate::config::AppConfig;
 
 const SIZE_THRESHOLD_GB: f64 = 10.0;
-/// All services: (subcommand, display_name)
-const SERVICES: &[(&str, &str)] = &[
+/// How long each `--deep` probe may take before its service counts as unreachable.
+const PROBE_TIMEOUT: Duration = Duration::from_secs(5);
+/// All services: (subcommand, display_name). Also what `run-all` runs.
+pub(crate) const SERVICES: &[(&str, &str)] = &[
 	("dms", "DMs (Discord + Telegram)"),
 	("email", "Email"),
 	("telegram-channel-watch", "Telegram Channel Watch"),
@@ -15,23 +25,97 @@ const SERVICES: &[(&str, &str)] = &[
 	("twitter-schedule", "Twitter Schedule"),
 	("youtube", "YouTube Monitor"),
 ];
-pub fn main(config: AppConfig) -> Result<()> {
+/// A running service whose last heartbeat is older than this is taken as hung. Twitter's is logged once per check,
+/// so it gets twice its `check_every` if that is longer.
+const STALE_HEARTBEAT: Duration = Duration::from_secs(10 * 60);
+/// Exit code when a required setting is missing or a credential is rejected.
+const EXIT_CONFIG: i32 = 2;
+/// Exit code when a monitor failed or a server it needs is unreachable (and the config looks fine).
+const EXIT_SERVICE_DOWN: i32 = 3;
+/// Exit code with `--strict` when only warnings were found.
+const EXIT_WARNINGS: i32 = 1;
+
+/// Exits 2 on a config problem (missing bot token, credentials refused with 401/403), 3 on a service problem (a monitor
+/// in error or whose process died, a stale heartbeat, a server unreachable or timing out), otherwise 0.
+#[derive(Args)]
+pub struct HealthArgs {
+	/// Also check that the configured tokens are accepted and ClickHouse answers (needs network)
+	#[arg(long)]
+	pub deep: bool,
+	/// Also fail (exit 1) on warnings: oversized directories, monitors reconnecting or stopped cleanly
+	#[arg(long)]
+	pub strict: bool,
+}
+
+/// What the checks found wrong, by how bad it is.
+#[derive(Debug, Default)]
+struct Findings {
+	config: Vec<String>,
+	service_down: Vec<String>,
+	warnings: Vec<String>,
+}
+
+impl Findings {
+	fn exit_code(&self, strict: bool) -> Option<i32> {
+		if !self.config.is_empty() {
+			Some(EXIT_CONFIG)
+		} else if !self.service_down.is_empty() {
+			Some(EXIT_SERVICE_DOWN)
+		} else if strict && !self.warnings.is_empty() {
+			Some(EXIT_WARNINGS)
+		} else {
+			None
+		}
+	}
+
+	/// File a failed `--deep` probe under config or service down, by why it failed.
+	fn probe(&mut self, result: Result<(), ProbeFailure>, rejected: &str, unreachable: &str) {
+		match result {
+			Ok(()) => {}
+			Err(ProbeFailure::Rejected) => self.config.push(rejected.to_string()),
+			Err(ProbeFailure::Unreachable) => self.service_down.push(unreachable.to_string()),
+		}
+	}
+
+	fn summary(&self) -> String {
+		let mut parts = Vec::new();
+		for (label, found) in [("config", &self.config), ("service down", &self.service_down), ("warnings", &self.warnings)] {
+			if !found.is_empty() {
+				parts.push(format!("{label}: {}", found.join(", ")));
+			}
+		}
+		parts.join("; ")
+	}
+}
+
+pub async fn main(config: AppConfig, args: HealthArgs) -> Result<()> {
 	println!("{}", "=== Social Networks Health Check ===\n".bold().cyan());
 
-	check_services();
-	check_env_vars(&config);
-	check_directories();
+	let mut findings = Findings::default();
+	check_services(&config, &mut findings);
+	check_monitors(&mut findings);
+	check_env_vars(&config, &mut findings);
+	check_llm_spend(&config, &mut findings).await;
+	if args.deep {
+		check_connectivity(&config, &mut findings).await;
+	}
+	check_directories(&mut findings);
 
 	println!();
+	if let Some(code) = findings.exit_code(args.strict) {
+		println!("{} {}", "Unhealthy:".bold().red(), findings.summary());
+		std::process::exit(code);
+	}
+	if !findings.warnings.is_empty() {
+		println!("{} {}", "Healthy, but:".bold().yellow(), findings.summary());
+	}
 	Ok(())
 }
 
-/// Checks if a process with binary ending in `social_networks` and the given subcommand is running.
+/// Pid of a running process with binary ending in `social_networks` and the given subcommand.
 /// Scans /proc to work regardless of how the process was launched (cargo run, installed binary, systemd).
-fn is_service_running(subcommand: &str) -> bool {
-	let Ok(entries) = std::fs::read_dir("/proc") else {
-		return false;
-	};
+fn find_process(subcommand: &str) -> Option<u32> {
+	let entries = std::fs::read_dir("/proc").ok()?;
 	let my_pid = std::process::id().to_string();
 	for entry in entries.flatten() {
 		let pid = entry.file_name();
@@ -44,16 +128,44 @@ fn is_service_running(subcommand: &str) -> bool {
 			continue;
 		};
 		let args: Vec<&[u8]> = cmdline.split(|&b| b == 0).filter(|s| !s.is_empty()).collect();
-		if args.len() < 2 {
+		let Some((binary, args)) = args.split_first() else {
 			continue;
+		};
+		// Global flags can come before the subcommand
+		if String::from_utf8_lossy(binary).ends_with("social_networks") && args.contains(&subcommand.as_bytes()) {
+			return pid_str.parse().ok();
 		}
-		let binary = String::from_utf8_lossy(args[0]);
-		let arg1 = String::from_utf8_lossy(args[1]);
-		if binary.ends_with("social_networks") && arg1 == subcommand {
-			return true;
+	}
+	None
+}
+
+/// Whether the process that reported `s` is gone without having said it stopped or failed: killed, OOM-killed, or
+/// crashed past its error handling.
+fn died(s: &MonitorStatus) -> bool {
+	matches!(s.state, MonitorState::Connected | MonitorState::Reconnecting) && !pid_alive(s.pid)
+}
+
+fn pid_alive(pid: u32) -> bool {
+	std::path::Path::new(&format!("/proc/{pid}")).exists()
+}
+
+/// Whether `status_name` is what a monitor of `service` reports its status under.
+fn reports_as(service: &str, status_name: &str) -> bool {
+	match service {
+		"dms" => matches!(status_name, "discord_dms" | "telegram_dms"),
+		_ => {
+			let surface = service.replace('-', "_");
+			status_name == surface || status_name.strip_prefix(&surface).is_some_and(|account| account.starts_with(':'))
 		}
 	}
-	false
+}
+
+/// Module `service` logs its heartbeats from.
+fn heartbeat_module(service: &str) -> String {
+	match service {
+		"dms" => "social_networks_adapters::discord".to_string(),
+		_ => format!("social_networks_adapters::{}", service.replace('-', "_")),
+	}
 }
 
 /// Gets the directory size in bytes
@@ -96,21 +208,122 @@ fn status_icon(ok: bool) -> colored::ColoredString {
 	if ok { "✓".green() } else { "✗".red() }
 }
 
-fn check_services() {
+/// Whether each service is up, and how long ago it last logged a heartbeat (only DMs and the Twitter monitor do). A
+/// running service with a stale heartbeat is alive but wedged, and one that isn't running though one of its monitors
+/// [`died`] should be; both are service problems. Services that never ran, or stopped cleanly, may be down. Under
+/// `run-all`, a service counts as up while its monitors report to their status files from that process and aren't in
+/// error (so not if skipped as unconfigured or stopped on an auth error), and its heartbeats are read from
+/// `run_all.log`.
+fn check_services(config: &AppConfig, findings: &mut Findings) {
 	println!("{}", "Services:".bold());
+	let now = Timestamp::now();
+	let run_all = find_process("run-all");
+	let statuses = status::read_all().unwrap_or_default();
 	for (service_name, display_name) in SERVICES {
-		let running = is_service_running(service_name);
-		println!("  {} {}", status_icon(running), display_name);
+		let (running, log_name, module) = match (find_process(service_name), run_all) {
+			(None, Some(pid)) => {
+				let running = statuses.iter().any(|s| s.pid == pid && reports_as(service_name, &s.name) && s.state != MonitorState::Error);
+				(running, "run_all".to_string(), Some(heartbeat_module(service_name)))
+			}
+			(own, _) => (own.is_some(), service_name.replace('-', "_"), None),
+		};
+		if !running && statuses.iter().any(|s| reports_as(service_name, &s.name) && died(s)) {
+			findings.service_down.push(format!("{service_name} not running"));
+		}
+		let heartbeat = match heartbeat::log_path(&log_name).and_then(|path| heartbeat::last_heartbeat(&path, module.as_deref())) {
+			Ok(heartbeat) => heartbeat,
+			Err(e) => {
+				println!("  {} {display_name} (unable to read log: {e:#})", status_icon(running));
+				continue;
+			}
+		};
+		let Some(at) = heartbeat else {
+			println!("  {} {display_name}", status_icon(running));
+			continue;
+		};
+		let age = now.duration_since(at);
+		let stale_after = match *service_name {
+			"twitter" => STALE_HEARTBEAT.max(config.twitter.check_every * 2),
+			_ => STALE_HEARTBEAT,
+		};
+		let stale = running && age.unsigned_abs() > stale_after;
+		if stale {
+			findings.service_down.push(format!("{service_name} heartbeat stale"));
+		}
+		let last = format!("last heartbeat {:#} ago", age.round(jiff::Unit::Second).unwrap_or_default());
+		println!("  {} {display_name} ({})", status_icon(running && !stale), if stale { last.red() } else { last.normal() });
+	}
+}
+
+/// Last status each monitor reported. One whose process is gone is reported as not running, whatever it last said.
+/// A monitor in error, or one that [`died`], is a service problem; one reconnecting, or stopped cleanly, only a warning.
+fn check_monitors(findings: &mut Findings) {
+	println!("\n{}", "Monitors:".bold());
+	let statuses = match status::read_all() {
+		Ok(statuses) => statuses,
+		Err(e) => {
+			println!("  {} unable to read monitor statuses: {e:#}", status_icon(false));
+			return;
+		}
+	};
+	if statuses.is_empty() {
+		println!("  no monitor has reported yet");
+	}
+	let now = Timestamp::now();
+	for s in statuses {
+		let running = pid_alive(s.pid);
+		let state = match (running, s.state) {
+			(_, MonitorState::Error) => {
+				findings.service_down.push(format!("{} failed", s.name));
+				if running { "error" } else { "not running (failed)" }
+			}
+			(false, MonitorState::Stopped) => {
+				findings.warnings.push(format!("{} stopped", s.name));
+				"not running (stopped)"
+			}
+			(false, _) => {
+				findings.service_down.push(format!("{} died", s.name));
+				"not running (died)"
+			}
+			(true, MonitorState::Connected) => "connected",
+			(true, MonitorState::Reconnecting) => {
+				findings.warnings.push(format!("{} reconnecting", s.name));
+				"reconnecting"
+			}
+			(true, MonitorState::Stopped) => "stopping",
+		};
+		let last_event = s.last_event_at.map_or_else(
+			|| "never".to_string(),
+			|at| format!("{:#} ago", now.duration_since(at).round(jiff::Unit::Second).unwrap_or_default()),
+		);
+		println!(
+			"  {} {}: {state}, {} events, {} errors, last event {last_event}",
+			status_icon(running && s.state == MonitorState::Connected),
+			s.name,
+			s.events,
+			s.errors
+		);
 	}
 }
 
 /// Required environment variables for various features
-fn check_env_vars(config: &AppConfig) {
+fn check_env_vars(config: &AppConfig, findings: &mut Findings) {
 	println!("\n{}", "Environment & Config:".bold());
 
 	// Check core telegram config (required for notifications)
 	let telegram_ok = !config.telegram.bot_token.is_empty();
 	println!("  {} Telegram bot token", status_icon(telegram_ok));
+	if !telegram_ok {
+		findings.config.push("Telegram bot token missing".to_string());
+	}
+
+	match config.telegram.templates.validate() {
+		Ok(()) => println!("  {} Notification templates", status_icon(true)),
+		Err(e) => {
+			println!("  {} Notification templates: {e:#}", status_icon(false));
+			findings.config.push("Invalid notification template".to_string());
+		}
+	}
 
 	// Check Discord config if dms is configured
 	let discord_ok = !config.dms.discord.user_token.is_empty();
@@ -136,7 +349,114 @@ fn check_env_vars(config: &AppConfig) {
 	println!("  {} Claude token (for email classification)", status_icon(claude_token_ok));
 }
 
-fn check_directories() {
+/// Today's (UTC) LLM spend by component, against `email.llm_daily_budget_cents`. Over budget is only a warning: the
+/// budget alert already went out, and the email monitor holds back as configured.
+async fn check_llm_spend(config: &AppConfig, findings: &mut Findings) {
+	println!("\n{}", "LLM Spend:".bold());
+	if xdg::BaseDirectories::with_prefix(env!("CARGO_PKG_NAME"))
+		.get_state_file("db.sqlite3")
+		.is_none_or(|path| !path.exists())
+	{
+		println!("  nothing recorded yet");
+		return;
+	}
+	let spend = match Database::try_new().await {
+		Ok(db) => db.llm_spend(&llm_cost_day(Timestamp::now())).await,
+		Err(e) => Err(e),
+	};
+	let spend = match spend {
+		Ok(spend) => spend,
+		Err(e) => {
+			println!("  {} unable to read LLM spend: {e:#}", status_icon(false));
+			return;
+		}
+	};
+	let total = total_llm_spend(&spend);
+	let by_component: Vec<String> = spend.iter().map(|(component, cents)| format!("{component} {cents:.2}¢")).collect();
+	let breakdown = if by_component.is_empty() { String::new() } else { format!(" ({})", by_component.join(", ")) };
+	match config.email.as_ref().and_then(|e| e.llm_daily_budget_cents) {
+		Some(budget) => {
+			let over = total >= budget;
+			if over {
+				findings.warnings.push("LLM over daily budget".to_string());
+			}
+			println!("  {} Today: {total:.2}¢ of the {budget:.2}¢ daily budget{breakdown}", status_icon(!over));
+		}
+		None => println!("  Today: {total:.2}¢{breakdown}, no daily budget set"),
+	}
+}
+
+/// Whether the configured credentials and servers actually work, beyond being set: the bot token via `getMe`, the
+/// Twitter bearer token via an authenticated read, ClickHouse via `SELECT 1`. Unset ones are skipped. A token refused
+/// with 401/403 is a config problem; anything else failing (no answer, connection refused, a 5xx) a service one.
+async fn check_connectivity(config: &AppConfig, findings: &mut Findings) {
+	println!("\n{}", "Connectivity:".bold());
+
+	if !config.telegram.bot_token.is_empty() {
+		let probe = async { bot_username(&config.telegram.bot_token).await.map(|username| format!("valid, bot {username}")) };
+		findings.probe(report_probe("Telegram bot token", probe).await, "Telegram bot token rejected", "Telegram Bot API unreachable");
+	}
+	if !config.twitter.bearer_token.is_empty() {
+		let probe = async { verify_bearer_token(&config.twitter.bearer_token).await.map(|()| "valid".to_string()) };
+		findings.probe(report_probe("Twitter bearer token", probe).await, "Twitter bearer token rejected", "Twitter API unreachable");
+	}
+	if let Some(url) = config.clickhouse.as_ref().map(|c| c.url.trim_end_matches('/')).filter(|url| !url.is_empty()) {
+		let probe = async { probe_clickhouse(url).await.map(|()| format!("reachable at {url}")) };
+		findings.probe(report_probe("ClickHouse", probe).await, "ClickHouse rejected the request", "ClickHouse unreachable");
+	}
+}
+
+/// Why a `--deep` probe failed.
+#[derive(Debug, PartialEq, Eq)]
+enum ProbeFailure {
+	/// Refused with HTTP 401/403: the credential is wrong
+	Rejected,
+	/// No answer in time, no connection, or any other error
+	Unreachable,
+}
+
+impl ProbeFailure {
+	fn of(e: &color_eyre::eyre::Report) -> Self {
+		let status = e.chain().find_map(|e| e.downcast_ref::<reqwest::Error>()).and_then(reqwest::Error::status);
+		match status {
+			Some(reqwest::StatusCode::UNAUTHORIZED | reqwest::StatusCode::FORBIDDEN) => Self::Rejected,
+			_ => Self::Unreachable,
+		}
+	}
+}
+
+/// Print `label` with what `probe` found, giving up after [`PROBE_TIMEOUT`].
+async fn report_probe(label: &str, probe: impl Future<Output = Result<String>>) -> Result<(), ProbeFailure> {
+	match tokio::time::timeout(PROBE_TIMEOUT, probe).await {
+		Ok(Ok(found)) => {
+			println!("  {} {label}: {found}", status_icon(true));
+			Ok(())
+		}
+		Ok(Err(e)) => {
+			println!("  {} {label}: {e:#}", status_icon(false));
+			Err(ProbeFailure::of(&e))
+		}
+		Err(_) => {
+			println!("  {} {label}: no answer within {}s", status_icon(false), PROBE_TIMEOUT.as_secs());
+			Err(ProbeFailure::Unreachable)
+		}
+	}
+}
+
+async fn probe_clickhouse(url: &str) -> Result<()> {
+	let response = http::client().get(url).query(&[("query", "SELECT 1")]).send().await?;
+	let status = response.status();
+	let body = response.text().await?;
+	if !status.is_success() {
+		bail!("HTTP {status}: {}", body.trim());
+	}
+	if body.trim() != "1" {
+		bail!("unexpected reply to `SELECT 1`: {:?}", body.trim());
+	}
+	Ok(())
+}
+
+fn check_directories(findings: &mut Findings) {
 	println!("\n{}", "Directory Sizes:".bold());
 
 	let app_name = env!("CARGO_PKG_NAME");
@@ -144,12 +464,12 @@ fn check_directories() {
 
 	// State directory
 	if let Some(state_dir) = xdg_dirs.get_state_home() {
-		check_directory_size(&state_dir, "State directory");
+		check_directory_size(&state_dir, "State directory", findings);
 	}
 
 	// Config directory
 	if let Some(config_dir) = xdg_dirs.get_config_home() {
-		check_directory_size(&config_dir, "Config directory");
+		check_directory_size(&config_dir, "Config directory", findings);
 	}
 
 	// Common log locations
@@ -162,7 +482,7 @@ fn check_directories() {
 
 	for path in &log_paths {
 		if path.exists() {
-			check_directory_size(path, &format!("{}", path.display()));
+			check_directory_size(path, &format!("{}", path.display()), findings);
 		}
 	}
 
@@ -170,7 +490,8 @@ fn check_directories() {
 	check_journald_size();
 }
 
-fn check_directory_size(path: &PathBuf, name: &str) {
+/// Past [`SIZE_THRESHOLD_GB`] is a warning.
+fn check_directory_size(path: &PathBuf, name: &str, findings: &mut Findings) {
 	match get_dir_size(path) {
 		Ok(size) => {
 			let size_gb = size as f64 / (1024.0 * 1024.0 * 1024.0);
@@ -178,6 +499,7 @@ fn check_directory_size(path: &PathBuf, name: &str) {
 			let size_str = bytes_to_human(size);
 			if alarming {
 				println!("  {} {} ({})", status_icon(false), name, size_str.red());
+				findings.warnings.push(format!("{name} is {size_str}"));
 			} else {
 				println!("  {} {} ({})", status_icon(true), name, size_str);
 			}
@@ -204,3 +526,74 @@ fn check_journald_size() {
 		}
 	}
 }
+
+#[cfg(test)]
+mod tests {
+	use color_eyre::eyre::{Report, eyre};
+	use tokio::{io::AsyncWriteExt, net::TcpListener};
+
+	use super::*;
+
+	#[test]
+	fn test_exit_code() {
+		let findings = |config: &[&str], service_down: &[&str], warnings: &[&str]| Findings {
+			config: config.iter().map(|s| s.to_string()).collect(),
+			service_down: service_down.iter().map(|s| s.to_string()).collect(),
+			warnings: warnings.iter().map(|s| s.to_string()).collect(),
+		};
+		assert_eq!(findings(&[], &[], &[]).exit_code(true), None);
+		assert_eq!(
+			findings(&["Telegram bot token missing"], &["email failed"], &["youtube stopped"]).exit_code(false),
+			Some(EXIT_CONFIG)
+		);
+		assert_eq!(findings(&[], &["email failed"], &["youtube stopped"]).exit_code(false), Some(EXIT_SERVICE_DOWN));
+		assert_eq!(findings(&[], &[], &["youtube stopped"]).exit_code(false), None);
+		assert_eq!(findings(&[], &[], &["youtube stopped"]).exit_code(true), Some(EXIT_WARNINGS));
+	}
+
+	#[test]
+	fn test_died() {
+		let status = |state, pid| MonitorStatus {
+			name: "twitter".to_string(),
+			state,
+			last_event_at: None,
+			events: 0,
+			errors: 0,
+			updated_at: Timestamp::now(),
+			pid,
+		};
+		// Past the kernel's pid_max, so never a live process
+		let gone = u32::MAX;
+		assert!(died(&status(MonitorState::Connected, gone)));
+		assert!(died(&status(MonitorState::Reconnecting, gone)));
+		assert!(!died(&status(MonitorState::Stopped, gone)));
+		assert!(!died(&status(MonitorState::Error, gone)));
+		assert!(!died(&status(MonitorState::Connected, std::process::id())));
+	}
+
+	/// What a probe of a server answering with `status` fails with, the way `bot_username` reports it
+	async fn status_error(status: u16) -> Report {
+		let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+		let addr = listener.local_addr().unwrap();
+		tokio::spawn(async move {
+			let (mut socket, _) = listener.accept().await.unwrap();
+			let response = format!("HTTP/1.1 {status} Whatever\r\ncontent-length: 0\r\nconnection: close\r\n\r\n");
+			socket.write_all(response.as_bytes()).await.unwrap();
+		});
+		let response = http::client().get(format!("http://{addr}/")).send().await.unwrap();
+		Report::new(response.error_for_status().unwrap_err()).wrap_err("getMe failed")
+	}
+
+	#[tokio::test]
+	async fn test_probe_failure_of() {
+		assert_eq!(ProbeFailure::of(&status_error(401).await), ProbeFailure::Rejected);
+		assert_eq!(ProbeFailure::of(&status_error(403).await), ProbeFailure::Rejected);
+		assert_eq!(ProbeFailure::of(&status_error(404).await), ProbeFailure::Unreachable);
+		assert_eq!(ProbeFailure::of(&status_error(503).await), ProbeFailure::Unreachable);
+		assert_eq!(ProbeFailure::of(&eyre!("unexpected reply to `SELECT 1`")), ProbeFailure::Unreachable);
+
+		// Nothing listening
+		let refused = http::client().get("http://127.0.0.1:1/").send().await.unwrap_err();
+		assert_eq!(ProbeFailure::of(&Report::new(refused)), ProbeFailure::Unreachable);
+	}
+}
@@ -1,18 +1,33 @@
 #![allow(unused_features)]
 #![feature(default_field_values)]
 mod config;
+mod destinations;
 mod dms;
 mod health;
+mod run_all;
 
 use clap::{Parser, Subcommand};
 use color_eyre::eyre::Result;
 use config::{AppConfig, LiveSettings, SettingsFlags};
 use dms::DmsArgs;
+use health::HealthArgs;
 use social_networks_adapters::{
-	AdapterError, Client, DiscordDms, EmailMonitor, TelegramChannelWatch, TelegramDms, TwitterMonitor, TwitterSchedule, YoutubeMonitor, alert, email::EmailArgs, install_panic_alert,
-	telegram_channel_watch::TelegramArgs, telegram_notifier::TelegramNotifier, twitter::TwitterArgs, twitter_schedule::TwitterScheduleArgs, youtube::YoutubeArgs,
+	AdapterError, Client, EmailMonitor, TelegramChannelWatch, TwitterMonitor, TwitterSchedule, YoutubeMonitor, alert,
+	email::{EmailArgs, export_processed},
+	install_panic_alert,
+	telegram_channel_watch::TelegramArgs,
+	twitter::{self, TwitterArgs},
+	twitter_schedule::{TwitterScheduleArgs, list_slots, simulate},
+	youtube::{self, YoutubeArgs},
+};
+use social_networks_utils::{
+	db::Database,
+	logging::{self, LogFormat},
+	shutdown,
+	snooze::{self, SnoozeArgs},
+	status,
+	utils::parse_duration,
 };
-use social_networks_utils::db::Database;
 use v_utils::utils::exit_on_error;
 
 #[derive(Parser)]
@@ -22,18 +37,31 @@ struct Cli {
 	command: Commands,
 	#[command(flatten)]
 	settings: SettingsFlags,
+	/// Log how long each monitor cycle and each channel/user in it takes (otherwise only at debug level)
+	#[arg(long, global = true)]
+	profile: bool,
+	/// Stop monitors cleanly after this long (e.g. `30m`, `6h`), as Ctrl-C would
+	#[arg(long, global = true, value_name = "DURATION", value_parser = parse_duration)]
+	max_runtime: Option<std::time::Duration>,
 }
 
 #[derive(Subcommand)]
 enum Commands {
+	/// Print the chat params each configured Telegram destination resolves to, without sending anything
+	Destinations,
 	/// DM monitoring (ping, monitored users) for Discord and Telegram simultaneously
 	Dms(DmsArgs),
 	/// Email operations
 	Email(EmailArgs),
 	/// Show health status of all services, config, and directories
-	Health,
+	Health(HealthArgs),
 	/// Run database migrations
 	MigrateDb,
+	/// Run every configured monitor in this one process, restarting any that fail. Log in to Telegram with `dms` and
+	/// `telegram-channel-watch` once beforehand: login prompts can't share the terminal with the other monitors.
+	RunAll,
+	/// Temporarily mute notifications from a source, or list active snoozes
+	Snooze(SnoozeArgs),
 	/// Telegram channel watching (poll/info forwarding)
 	TelegramChannelWatch(TelegramArgs),
 	/// Twitter operations
@@ -48,70 +76,99 @@ fn main() {
 	let cli = Cli::parse();
 	let settings = exit_on_error(LiveSettings::new(cli.settings, std::time::Duration::from_secs(60)));
 	let config: AppConfig = exit_on_error(settings.config());
+	// `health` reports a broken config itself, among everything else
+	if !matches!(cli.command, Commands::Health(_)) {
+		exit_on_error(config.validate());
+	}
+	social_networks_utils::http::set_user_agent(config.user_agent.clone());
+	social_networks_utils::profile::set_enabled(cli.profile);
+	if let Some(max_runtime) = cli.max_runtime {
+		shutdown::set_max_runtime(max_runtime);
+	}
 
 	let result: Result<()> = match cli.command {
-		Commands::Health => health::main(config),
+		Commands::Health(args) => {
+			let runtime = tokio::runtime::Runtime::new().unwrap();
+			runtime.block_on(health::main(config, args))
+		}
+		Commands::Destinations => {
+			destinations::main(&config);
+			Ok(())
+		}
 		Commands::MigrateDb => {
 			let runtime = tokio::runtime::Runtime::new().unwrap();
 			runtime.block_on(async { Database::try_new().await.map(|_| ()) })
 		}
 		Commands::Dms(_) => run_async("dms", || async {
-			v_utils::clientside!(Some("dms"));
-			let (tx, rx) = tokio::sync::mpsc::unbounded_channel();
-			let notifier = TelegramNotifier::new(config.telegram.clone());
-			let mut discord = DiscordDms::new(config.dms.discord.clone(), tx.clone());
-			let mut telegram = TelegramDms::new(config.telegram, tx);
-			let err = tokio::select! {
-				e = discord.listen() => e.unwrap_err(),
-				e = telegram.listen() => e.unwrap_err(),
-				() = dms::run(rx, config.dms, notifier) => unreachable!("dms::run only returns when both adapters drop their senders"),
-			};
-			alert(&err).await;
-			Err::<(), AdapterError>(err)
+			init_logging("dms", config.log_format);
+			run_monitor(dms::listen(config.dms, config.telegram)).await
 		}),
 		Commands::Email(args) => run_async("email", || async {
-			v_utils::clientside!(Some("email"));
+			init_logging("email", config.log_format);
+			if args.export_processed {
+				let db = Database::try_new().await.map_err(adapter_from_eyre)?;
+				let output = args.output.expect("clap enforces --output with --export-processed");
+				return export_processed(&db, args.format, &output).await.map_err(adapter_from_eyre);
+			}
 			let email_config = config
 				.email
 				.clone()
 				.ok_or_else(|| color_eyre::eyre::eyre!("Email config not found in config file"))
 				.map_err(adapter_from_eyre)?;
-			let mut monitor = EmailMonitor::try_from_configs(email_config, config.telegram).await.map_err(adapter_from_eyre)?;
+			let mut monitor = EmailMonitor::try_from_configs(email_config, config.telegram)
+				.await
+				.map_err(adapter_from_eyre)?
+				.headless(args.headless);
+			if args.authorize {
+				return monitor.authorize().await.map_err(adapter_from_eyre);
+			}
 			if args.mark_all_read {
 				return monitor.mark_all_as_read().await.map_err(adapter_from_eyre);
 			}
-			let err = monitor.listen().await.unwrap_err();
-			alert(&err).await;
-			Err::<(), AdapterError>(err)
+			if args.purge_old {
+				let purged = monitor.purge_old_emails().await.map_err(adapter_from_eyre)?;
+				println!("Purged {purged} processed emails");
+				return Ok(());
+			}
+			run_monitor(monitor.listen()).await
 		}),
-		Commands::TelegramChannelWatch(_) => run_async("telegram_channel_watch", || async {
-			v_utils::clientside!(Some("telegram_channel_watch"));
-			let mut adapter = TelegramChannelWatch::new(config.telegram);
-			let err = adapter.listen().await.unwrap_err();
-			alert(&err).await;
-			Err::<(), AdapterError>(err)
+		Commands::RunAll => run_async("run_all", || async {
+			init_logging("run_all", config.log_format);
+			run_all::main(config).await
 		}),
-		Commands::Twitter(_) => run_async("twitter", || async {
-			v_utils::clientside!(Some("twitter"));
+		Commands::Snooze(args) => snooze::run(args),
+		Commands::TelegramChannelWatch(args) => run_async("telegram_channel_watch", || async {
+			init_logging("telegram_channel_watch", config.log_format);
+			let mut adapter = TelegramChannelWatch::new(config.telegram).output_channel(args.output_channel);
+			run_monitor(adapter.listen()).await
+		}),
+		Commands::Twitter(args) => run_async("twitter", || async {
+			init_logging("twitter", config.log_format);
+			if args.count_only {
+				return twitter::count_members(&config.twitter).await;
+			}
 			let mut adapter = TwitterMonitor::new(config.twitter, config.telegram);
-			let err = adapter.listen().await.unwrap_err();
-			alert(&err).await;
-			Err::<(), AdapterError>(err)
+			run_monitor(adapter.listen()).await
 		}),
 		Commands::TwitterSchedule(args) => run_async("twitter_schedule", || async {
-			v_utils::clientside!(Some("twitter_schedule"));
-			let mut adapter = TwitterSchedule::new(config.twitter, args.skip_first);
-			let err = adapter.listen().await.unwrap_err();
-			alert(&err).await;
-			Err::<(), AdapterError>(err)
-		}),
-		Commands::Youtube(_) => run_async("youtube", || async {
-			v_utils::clientside!(Some("youtube"));
-			let mut adapter = YoutubeMonitor::new(config.youtube, config.telegram);
-			let err = adapter.listen().await.unwrap_err();
-			alert(&err).await;
-			Err::<(), AdapterError>(err)
+			init_logging("twitter_schedule", config.log_format);
+			if let Some(n) = args.list_slots {
+				return list_slots(&config.twitter, args.skip_first, n).map_err(adapter_from_eyre);
+			}
+			if let Some(n) = args.simulate {
+				return simulate(&config.twitter, args.skip_first, n).await.map_err(adapter_from_eyre);
+			}
+			let mut adapter = TwitterSchedule::new(config.twitter, args.skip_first).dry_run(args.dry_run);
+			run_monitor(adapter.listen()).await
 		}),
+		Commands::Youtube(args) => match args.parse_samples {
+			Some(dir) => youtube::parse_samples(&dir),
+			None => run_async("youtube", || async {
+				init_logging("youtube", config.log_format);
+				let mut adapter = YoutubeMonitor::new(config.youtube, config.telegram);
+				run_monitor(adapter.listen()).await
+			}),
+		},
 	};
 
 	exit_on_error(result);
@@ -133,6 +190,38 @@ where
 	runtime.block_on(async { f().await.map_err(|e| e.into()) })
 }
 
+/// Log the monitor run as `name` to `<state>/social_networks/<name>.log`: `clientside!`'s pretty-printed JSON, or one
+/// JSON object per line with `log_format = "json"` (or `SOCIAL_NETWORKS_LOG_FORMAT=json`).
+fn init_logging(name: &'static str, configured: LogFormat) {
+	match logging::format(configured) {
+		LogFormat::Pretty => {
+			v_utils::clientside!(Some(name));
+		}
+		LogFormat::Json => {
+			color_eyre::install().unwrap();
+			miette::set_hook(Box::new(|_| Box::new(miette::MietteHandlerOpts::new().terminal_links(true).build()))).expect("miette hook already set");
+			exit_on_error(logging::init_json_lines(name, option_env!("LOG_DIRECTIVES")));
+		}
+	}
+}
+
+/// Run a monitor until it stops cleanly on Ctrl-C, SIGTERM or `--max-runtime`, persisting its status, or until its
+/// terminal error, which is alerted on.
+async fn run_monitor(listen: impl Future<Output = Result<(), AdapterError>>) -> Result<(), AdapterError> {
+	shutdown::install();
+	match listen.await {
+		Ok(()) => {
+			status::stopped();
+			println!("Stopped");
+			Ok(())
+		}
+		Err(err) => {
+			alert(&err).await;
+			Err(err)
+		}
+	}
+}
+
 fn adapter_from_eyre(e: color_eyre::eyre::Report) -> AdapterError {
 	AdapterError::Unhandled {
 		surface: "email",
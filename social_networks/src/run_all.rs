@@ -0,0 +1,108 @@
+//! `run-all`: every monitor in [`SERVICES`] as a task of one process. A monitor that gives up is alerted on and
+//! restarted with the reconnect backoff, except after an auth error, which retrying cannot fix. A shutdown (Ctrl-C,
+//! `--max-runtime`) stops them all.
+use std::time::{Duration, Instant};
+
+use color_eyre::eyre::{Result, bail};
+use social_networks_adapters::{AdapterError, Client, EmailMonitor, TelegramChannelWatch, TwitterMonitor, TwitterSchedule, YoutubeMonitor, alert};
+use social_networks_utils::{shutdown, status, utils::reconnect_delay};
+use tokio::task::JoinSet;
+use tracing::{error, info};
+
+use crate::{adapter_from_eyre, config::AppConfig, dms, health::SERVICES};
+
+/// A monitor that stayed up this long before exiting is restarted without the backoff it had built up.
+const BACKOFF_RESET_AFTER: Duration = Duration::from_secs(10 * 60);
+
+/// Returns on shutdown, or (with an error) once every monitor has stopped on an auth error.
+pub async fn main(config: AppConfig) -> Result<()> {
+	shutdown::install();
+	let mut tasks = JoinSet::new();
+	for &(service, display_name) in SERVICES {
+		if let Some(reason) = unconfigured(service, &config) {
+			println!("Skipping {display_name}: {reason}");
+			continue;
+		}
+		println!("Starting {display_name}");
+		tasks.spawn(supervise(service, config.clone()));
+	}
+	if tasks.is_empty() {
+		bail!("Nothing to run, no service is configured");
+	}
+
+	while let Some(stopped) = tasks.join_next().await {
+		match stopped {
+			Ok(Ok(())) => {}
+			Ok(Err(err)) => error!("{err}; not restarting it, the other services keep running"),
+			Err(e) => error!("Supervisor task failed: {e}"),
+		}
+	}
+	if shutdown::is_requested() {
+		status::stopped();
+		println!("Stopped");
+		return Ok(());
+	}
+	bail!("Every service stopped");
+}
+
+/// Why `service` can't run with `config`, for the ones that need a section of their own.
+fn unconfigured(service: &str, config: &AppConfig) -> Option<&'static str> {
+	match service {
+		"email" if config.email.is_none() => Some("no email config"),
+		"twitter-schedule" if config.twitter.poll.is_none() => Some("no twitter.poll config"),
+		_ => None,
+	}
+}
+
+/// Keep `service` running until shutdown; returns its error once it stops on an auth error. A panic counts as an
+/// unhandled error (the panic hook has already alerted on it).
+async fn supervise(service: &'static str, config: AppConfig) -> Result<(), AdapterError> {
+	let mut attempt: u32 = 0;
+	loop {
+		let started = Instant::now();
+		let err = match tokio::spawn(listen(service, config.clone())).await {
+			Ok(Ok(())) => return Ok(()),
+			Ok(Err(err)) => {
+				alert(&err).await;
+				err
+			}
+			Err(e) => AdapterError::Unhandled {
+				surface: service,
+				detail: format!("panicked: {e}"),
+			},
+		};
+		if let AdapterError::Auth { .. } = err {
+			return Err(err);
+		}
+		if started.elapsed() >= BACKOFF_RESET_AFTER {
+			attempt = 0;
+		}
+		let delay = reconnect_delay(attempt);
+		error!("{err}\nRestarting {service} in {:.1}s...", delay.as_secs_f64());
+		if shutdown::requested_during(delay).await {
+			return Ok(());
+		}
+		attempt = attempt.saturating_add(1);
+	}
+}
+
+/// Build `service`'s monitor from `config` and run it until shutdown or until it gives up, as its own subcommand would
+/// without flags.
+async fn listen(service: &'static str, config: AppConfig) -> Result<(), AdapterError> {
+	info!("starting {service}");
+	match service {
+		"dms" => dms::listen(config.dms, config.telegram).await,
+		"email" => {
+			let email_config = config.email.expect("checked by `unconfigured`");
+			match EmailMonitor::try_from_configs(email_config, config.telegram).await {
+				Ok(mut monitor) => monitor.listen().await,
+				Err(e) => Err(adapter_from_eyre(e)),
+			}
+		}
+		"telegram-channel-watch" => TelegramChannelWatch::new(config.telegram).listen().await,
+		"twitter" => TwitterMonitor::new(config.twitter, config.telegram).listen().await,
+		"twitter-schedule" => TwitterSchedule::new(config.twitter, false).listen().await,
+		"youtube" => YoutubeMonitor::new(config.youtube, config.telegram).listen().await,
+		_ => unreachable!("{service} is not in SERVICES"),
+	}
+}
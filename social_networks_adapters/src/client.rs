@@ -1,21 +1,23 @@
-use std::convert::Infallible;
-
+use social_networks_utils::status;
 use thiserror::Error;
 use tracing::error;
 
 /// A long-running social-network surface.
 ///
-/// `listen` runs forever in the happy path and returns only when the surface has hit an
-/// error class it does not know how to recover from in-process. The caller (the binary)
-/// fires a `v_notify` alert and exits — we do not silently retry past unknown failures.
+/// `listen` runs until a [`shutdown`](social_networks_utils::shutdown) is requested in the happy path, and
+/// otherwise returns only when the surface has hit an error class it does not know how to recover from
+/// in-process. The caller (the binary) fires a `v_notify` alert and exits — we do not silently retry past
+/// unknown failures.
 #[trait_variant::make(Send)]
 pub trait Client {
 	/// Surface name used in alerts, e.g. `"discord_dms"`, `"twitter_schedule"`.
 	fn surface(&self) -> &'static str;
 
-	/// Run forever. Recoverable errors are handled inside this call (sleep + reconnect
-	/// with backoff). Anything that escapes is treated as terminal by the orchestrator.
-	async fn listen(&mut self) -> Result<Infallible, AdapterError>;
+	/// Run until a shutdown is requested, then return `Ok` with the surface's state persisted. Surfaces with no
+	/// state of their own may leave that to the caller, which then drops them mid-wait. Recoverable errors are
+	/// handled inside this call (sleep + reconnect with backoff). Anything that escapes is treated as terminal by
+	/// the orchestrator.
+	async fn listen(&mut self) -> Result<(), AdapterError>;
 }
 
 #[derive(Debug, Error)]
@@ -33,10 +35,19 @@ pub enum AdapterError {
 	Unhandled { surface: &'static str, detail: String },
 }
 
+impl AdapterError {
+	pub fn surface(&self) -> &'static str {
+		match self {
+			Self::Auth { surface, .. } | Self::Unhandled { surface, .. } => surface,
+		}
+	}
+}
+
 /// Send a max-importance (`error`) notification via the `v_notify` CLI.
 /// Failures to spawn `v_notify` are logged but never escalated: a broken alerting path
 /// must not also kill the surfaces that are still working.
 pub async fn alert(err: &AdapterError) {
+	status::failed(err.surface());
 	let text = format!("[social_networks] {err}");
 	error!("{text}");
 	match tokio::process::Command::new("v_notify").args(["-l", "error"]).arg(&text).status().await {
@@ -1,11 +1,16 @@
-use std::{convert::Infallible, sync::Arc};
+use std::sync::Arc;
 
-use color_eyre::eyre::Result;
+use color_eyre::eyre::{Context, Result, bail};
+use flate2::{Decompress, FlushDecompress};
 use futures::future::{Either, select};
 use futures_util::{SinkExt, StreamExt, stream::SplitStream};
 use jiff::{Timestamp, fmt::strtime};
 use serde::{Deserialize, Serialize};
 use serde_json::json;
+use social_networks_utils::{
+	status,
+	utils::{reconnect_delay, with_jitter},
+};
 use tokio::{
 	sync::{Mutex, mpsc::UnboundedSender},
 	time::{self, Duration},
@@ -23,11 +28,45 @@ use crate::{
 };
 
 const SURFACE: &str = "discord_dms";
+const GATEWAY_URL: &str = "wss://gateway.discord.gg";
+const GATEWAY_QUERY: &str = "?v=10&encoding=json";
+/// Ends every complete payload of a `zlib-stream` gateway connection (a zlib `Z_SYNC_FLUSH`)
+const ZLIB_SUFFIX: [u8; 4] = [0x00, 0x00, 0xff, 0xff];
+/// Pause between quick handshake attempts, before `listen`'s reconnect backoff takes over
+const HANDSHAKE_RETRY_DELAY: Duration = Duration::from_secs(2);
+/// GUILDS | GUILD_MESSAGES | DIRECT_MESSAGES | MESSAGE_CONTENT. Without MESSAGE_CONTENT the gateway sends
+/// MESSAGE_CREATE with an empty `content` for anything that doesn't mention us, so monitored users' messages come
+/// through blank.
+const DEFAULT_INTENTS: u64 = (1 << 0) | (1 << 9) | (1 << 12) | (1 << 15);
+/// Upper bound of the jittered wait Discord asks for after an op 9 before identifying again (it suggests 1-5s)
+const INVALID_SESSION_DELAY: Duration = Duration::from_secs(5);
 
 #[derive(Clone, Debug, Default, MyConfigPrimitives)]
 pub struct DiscordConfig {
 	pub user_token: String,
 	pub my_username: String,
+	/// Quick tries at the connect/HELLO/IDENTIFY handshake before falling back to the reconnect backoff
+	#[serde(default = "__default_handshake_attempts")]
+	pub handshake_attempts: u8,
+	/// Ask the gateway for `compress=zlib-stream`, trading a little CPU for much less bandwidth on busy accounts
+	#[serde(default)]
+	pub compress: bool,
+	/// Gateway intents bitmask sent with IDENTIFY
+	#[serde(default = "__default_intents")]
+	pub intents: u64,
+	/// Status to show while connected; unset leaves it to Discord (whatever the account's clients last chose)
+	#[serde(default)]
+	#[primitives(skip)]
+	pub presence: Option<PresenceStatus>,
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum PresenceStatus {
+	Online,
+	Idle,
+	Dnd,
+	Invisible,
 }
 
 pub struct DiscordDms {
@@ -35,6 +74,86 @@ pub struct DiscordDms {
 	tx: UnboundedSender<DmEvent>,
 	message_counter: u64,
 	my_user_id: Option<String>,
+	gateway: GatewayState,
+}
+
+/// What's needed to RESUME (op 6) a dropped gateway session instead of IDENTIFYing afresh, which replays the events
+/// missed while disconnected.
+#[derive(Debug, Default)]
+struct GatewayState {
+	/// Last dispatch sequence number `s`; also sent with heartbeats
+	seq: Option<u64>,
+	/// From READY; `None` until the first one, or after the session was invalidated
+	session: Option<ResumableSession>,
+}
+
+#[derive(Debug, PartialEq)]
+struct ResumableSession {
+	session_id: String,
+	resume_gateway_url: String,
+}
+
+impl GatewayState {
+	/// URL to connect to and the payload to send after HELLO: RESUME when there's a session to pick up, else IDENTIFY.
+	fn opening(&self, config: &DiscordConfig) -> (String, DiscordMessage) {
+		let token = &config.user_token;
+		match (&self.session, self.seq) {
+			(Some(session), Some(seq)) => (
+				format!("{}/{GATEWAY_QUERY}", session.resume_gateway_url.trim_end_matches('/')),
+				DiscordMessage {
+					op: 6,
+					d: Some(json!({ "token": token, "session_id": session.session_id, "seq": seq })),
+					s: None,
+					t: None,
+				},
+			),
+			_ => {
+				let mut identify = json!({
+					"token": token,
+					"intents": config.intents,
+					"properties": {
+						"os": "linux",
+						"browser": "rust",
+						"device": "pc"
+					}
+				});
+				if let Some(status) = config.presence {
+					identify["presence"] = json!({
+						"status": status,
+						"since": null,
+						"activities": [],
+						"afk": false
+					});
+				}
+				(
+					format!("{GATEWAY_URL}/{GATEWAY_QUERY}"),
+					DiscordMessage {
+						op: 2,
+						d: Some(identify),
+						s: None,
+						t: None,
+					},
+				)
+			}
+		}
+	}
+
+	fn ready(&mut self, data: &serde_json::Value) {
+		let field = |name| data.get(name).and_then(|v| v.as_str()).map(str::to_string);
+		self.session = match (field("session_id"), field("resume_gateway_url")) {
+			(Some(session_id), Some(resume_gateway_url)) => Some(ResumableSession { session_id, resume_gateway_url }),
+			_ => {
+				warn!("Discord READY without session_id/resume_gateway_url; reconnects will re-identify");
+				None
+			}
+		};
+	}
+
+	/// Next connection identifies afresh.
+	fn invalidate(&mut self) {
+		self.seq = None;
+		self.session = None;
+	}
 }
 
 impl DiscordDms {
@@ -44,6 +163,7 @@ impl DiscordDms {
 			tx,
 			message_counter: 0,
 			my_user_id: None,
+			gateway: GatewayState::default(),
 		}
 	}
 
@@ -51,7 +171,7 @@ impl DiscordDms {
 	/// Returns `Ok(())` if the caller should reconnect, `Err(AdapterError::Auth)` if
 	/// retrying cannot help (datacenter banned, token revoked, etc.).
 	async fn run_session(&mut self) -> Result<(), AdapterError> {
-		let (mut read, write, heartbeat_secs) = match self.connect().await {
+		let (mut read, write, mut frames, heartbeat_every) = match retry_handshake(self.discord_config.handshake_attempts, HANDSHAKE_RETRY_DELAY, || self.connect()).await {
 			Ok(c) => c,
 			Err(e) => {
 				error!("Discord connection error: {e:#}");
@@ -60,8 +180,9 @@ impl DiscordDms {
 		};
 		info!("--Discord DM Commands-- connected to WebSocket");
 		println!("Discord DM Commands: Connected");
+		status::connected(SURFACE);
 
-		let mut heartbeat_interval = time::interval(Duration::from_secs(heartbeat_secs));
+		let mut heartbeat_interval = time::interval(heartbeat_every);
 
 		loop {
 			enum Event {
@@ -80,42 +201,42 @@ impl DiscordDms {
 			};
 
 			match event {
-				Event::Heartbeat => {
-					let heartbeat = DiscordMessage {
-						op: 1,
-						d: Some(json!(null)),
-						s: None,
-						t: None,
-					};
-					let msg = match serde_json::to_string(&heartbeat) {
-						Ok(m) => m,
-						Err(e) =>
-							return Err(AdapterError::Unhandled {
-								surface: SURFACE,
-								detail: format!("heartbeat serialization: {e}"),
-							}),
-					};
-					if write.lock().await.send(Message::Text(msg.into())).await.is_err() {
-						error!("Failed to send Discord heartbeat, reconnecting...");
+				Event::Heartbeat =>
+					if !self.send_heartbeat(&write).await? {
 						return Ok(());
-					}
-				}
-				Event::Message(Some(Ok(Message::Text(text)))) =>
+					},
+				Event::Message(Some(Ok(message @ (Message::Text(_) | Message::Binary(_))))) => {
+					let text = match frames.decode(message) {
+						Ok(Some(text)) => text,
+						Ok(None) => continue,
+						Err(e) => {
+							error!("Failed to decode Discord gateway frame: {e:#}, reconnecting...");
+							return Ok(());
+						}
+					};
 					if let Ok(event) = serde_json::from_str::<DiscordMessage>(&text) {
 						self.message_counter += 1;
+						if event.s.is_some() {
+							self.gateway.seq = event.s;
+						}
 
-						match event.op {
-							11 => {
+						match GatewayOp::of(&event) {
+							GatewayOp::HeartbeatAck => {
 								let now_zoned = Timestamp::now().to_zoned(jiff::tz::TimeZone::UTC);
 								let now = strtime::format("%m/%d/%y-%H", &now_zoned).unwrap();
 								info!("Heartbeat received. Time: {now}. Since last heartbeat processed: {} messages", self.message_counter);
 								self.message_counter = 0;
 							}
-							0 =>
+							GatewayOp::Dispatch =>
 								if let Some(d) = &event.d {
+									status::event(SURFACE);
 									let event_type = event.t.as_deref();
 									let result = match event_type {
 										Some("READY") => self.handle_ready(d),
+										Some("RESUMED") => {
+											info!("Discord session resumed");
+											Ok(())
+										}
 										Some("CALL_CREATE") => self.handle_call_create(d),
 										// Only MESSAGE_CREATE: Discord also fires MESSAGE_UPDATE with identical content
 										// when it unfurls links/embeds, which would double-notify.
@@ -126,14 +247,38 @@ impl DiscordDms {
 										error!("Error handling {}: {e}", event_type.unwrap_or("unknown"));
 									}
 								},
-							_ => {}
+							GatewayOp::HeartbeatRequest => {
+								if !self.send_heartbeat(&write).await? {
+									return Ok(());
+								}
+								// The one we just sent counts; the next is a full interval away
+								heartbeat_interval.reset();
+							}
+							GatewayOp::Reconnect => {
+								info!("Discord requested a reconnect, resuming...");
+								return Ok(());
+							}
+							GatewayOp::InvalidSession { resumable } => {
+								if !resumable {
+									self.gateway.invalidate();
+								}
+								let delay = with_jitter(INVALID_SESSION_DELAY);
+								warn!("Discord invalidated the session (resumable: {resumable}), reconnecting in {:.1}s...", delay.as_secs_f64());
+								time::sleep(delay).await;
+								return Ok(());
+							}
+							GatewayOp::Other(_) => {}
 						}
-					},
+					}
+				}
 				Event::Message(Some(Ok(Message::Close(frame)))) => {
+					if frame.as_ref().is_some_and(|f| !close_allows_resume(close_code(f))) {
+						self.gateway.invalidate();
+					}
 					return classify_close(frame);
 				}
 				Event::Message(Some(Ok(_))) => {
-					// Non-text non-close message (Ping/Pong/Binary), ignore
+					// Ping/Pong, ignore
 				}
 				Event::Message(Some(Err(e))) => {
 					error!("Discord WebSocket error: {e}, reconnecting...");
@@ -152,44 +297,54 @@ impl DiscordDms {
 	) -> Result<(
 		SplitStream<WebSocketStream<MaybeTlsStream<tokio::net::TcpStream>>>,
 		Arc<Mutex<futures_util::stream::SplitSink<WebSocketStream<MaybeTlsStream<tokio::net::TcpStream>>, Message>>>,
-		u64,
+		GatewayFrames,
+		Duration,
 	)> {
-		let url = "wss://gateway.discord.gg/?v=10&encoding=json";
-		let (ws_stream, _) = connect_async(url).await?;
+		let (mut url, opening) = self.gateway.opening(&self.discord_config);
+		if self.discord_config.compress {
+			url.push_str("&compress=zlib-stream");
+		}
+		let (ws_stream, _) = connect_async(url.as_str()).await?;
 
 		let (write, mut read) = ws_stream.split();
 		let write = Arc::new(Mutex::new(write));
+		let mut frames = GatewayFrames::new(self.discord_config.compress);
 
-		let hello_msg = read.next().await.ok_or_else(|| color_eyre::eyre::eyre!("No hello message"))??;
-		let hello: DiscordMessage = serde_json::from_str(&hello_msg.to_string())?;
-
-		let heartbeat_interval = hello
-			.d
-			.as_ref()
-			.and_then(|d| d.get("heartbeat_interval"))
-			.and_then(|v| v.as_u64())
-			.ok_or_else(|| color_eyre::eyre::eyre!("No heartbeat interval"))?;
-
-		let heartbeat_secs = heartbeat_interval / 1000;
-
-		let identify = DiscordMessage {
-			op: 2,
-			d: Some(json!({
-				"token": self.discord_config.user_token,
-				"properties": {
-					"$os": "linux",
-					"$browser": "rust",
-					"$device": "pc"
-				}
-			})),
-			s: None,
-			t: None,
+		let hello_text = loop {
+			let msg = read.next().await.ok_or_else(|| color_eyre::eyre::eyre!("No hello message"))??;
+			if let Some(text) = frames.decode(msg)? {
+				break text;
+			}
 		};
+		let hello: DiscordMessage = serde_json::from_str(&hello_text)?;
+		let heartbeat_every = hello_heartbeat_interval(&hello)?;
 
-		let msg = serde_json::to_string(&identify)?;
+		if opening.op == 6 {
+			info!("Resuming Discord session at seq {}", self.gateway.seq.unwrap_or_default());
+		}
+		let msg = serde_json::to_string(&opening)?;
 		write.lock().await.send(Message::Text(msg.into())).await?;
 
-		Ok((read, write, heartbeat_secs))
+		Ok((read, write, frames, heartbeat_every))
+	}
+
+	/// Send op 1 with the last seen sequence. `Ok(false)` if the socket is gone and the session should reconnect.
+	async fn send_heartbeat(&self, write: &Mutex<futures_util::stream::SplitSink<WebSocketStream<MaybeTlsStream<tokio::net::TcpStream>>, Message>>) -> Result<bool, AdapterError> {
+		let heartbeat = DiscordMessage {
+			op: 1,
+			d: Some(json!(self.gateway.seq)),
+			s: None,
+			t: None,
+		};
+		let msg = serde_json::to_string(&heartbeat).map_err(|e| AdapterError::Unhandled {
+			surface: SURFACE,
+			detail: format!("heartbeat serialization: {e}"),
+		})?;
+		if write.lock().await.send(Message::Text(msg.into())).await.is_err() {
+			error!("Failed to send Discord heartbeat, reconnecting...");
+			return Ok(false);
+		}
+		Ok(true)
 	}
 
 	fn handle_ready(&mut self, data: &serde_json::Value) -> Result<()> {
@@ -200,6 +355,7 @@ impl DiscordDms {
 			.ok_or_else(|| color_eyre::eyre::eyre!("READY event missing user.id"))?;
 		info!("Captured my user id: {user_id}");
 		self.my_user_id = Some(user_id.to_string());
+		self.gateway.ready(data);
 		Ok(())
 	}
 
@@ -216,12 +372,17 @@ impl DiscordDms {
 
 	fn handle_message(&self, data: &serde_json::Value) -> Result<()> {
 		let author = data.get("author").and_then(|a| a.get("username")).and_then(|u| u.as_str());
-		let content = data.get("content").and_then(|c| c.as_str());
+		let content = data.get("content").and_then(|c| c.as_str()).unwrap_or_default();
 		let channel_id = data.get("channel_id").and_then(|c| c.as_str());
 
-		let (Some(author), Some(content), Some(channel_id)) = (author, content, channel_id) else {
+		let (Some(author), Some(channel_id)) = (author, channel_id) else {
 			return Ok(());
 		};
+		// An image or a bare link embed comes with empty `content`
+		let (attachments, embeds) = message_media(data);
+		if content.is_empty() && attachments.is_empty() && embeds.is_empty() {
+			return Ok(());
+		}
 
 		// Discord WS echoes our own outgoing messages back to us; drop those at the transport boundary,
 		// mirroring Telegram's `!message.outgoing()` filter.
@@ -257,22 +418,33 @@ impl DiscordDms {
 			is_dm,
 			mentions_me,
 			is_reply_to_me,
+			attachments,
+			embeds,
 		});
 
 		Ok(())
 	}
 }
 
+/// Attachment URLs and embed titles (URL for untitled embeds) of a MESSAGE_CREATE payload.
+fn message_media(data: &serde_json::Value) -> (Vec<String>, Vec<String>) {
+	let items = |key: &str| data.get(key).and_then(|v| v.as_array()).into_iter().flatten();
+	let attachments = items("attachments").filter_map(|a| a.get("url")?.as_str()).map(str::to_string).collect();
+	let embeds = items("embeds").filter_map(|e| e.get("title").or_else(|| e.get("url"))?.as_str()).map(str::to_string).collect();
+	(attachments, embeds)
+}
+
 impl Client for DiscordDms {
 	fn surface(&self) -> &'static str {
 		SURFACE
 	}
 
-	async fn listen(&mut self) -> Result<Infallible, AdapterError> {
+	async fn listen(&mut self) -> Result<(), AdapterError> {
 		let mut attempt: u32 = 0;
 		loop {
 			match self.run_session().await {
 				Ok(()) => {
+					status::reconnecting(SURFACE);
 					let delay = reconnect_delay(attempt);
 					warn!("Discord reconnecting in {:.1}s (attempt {attempt})", delay.as_secs_f64());
 					time::sleep(delay).await;
@@ -284,11 +456,6 @@ impl Client for DiscordDms {
 	}
 }
 
-fn reconnect_delay(attempt: u32) -> Duration {
-	let delay_secs = std::f64::consts::E.powi(attempt as i32).min(600.0);
-	Duration::from_secs_f64(delay_secs)
-}
-
 /// Map a Discord WS close frame to either a recoverable reconnect (`Ok(())`) or a fatal
 /// auth-class error. Codes 4004/4010-4014 are documented as fatal in the Discord
 /// gateway docs (invalid token, invalid intent, datacenter blocked, etc.).
@@ -297,10 +464,7 @@ fn classify_close(frame: Option<tokio_tungstenite::tungstenite::protocol::frame:
 		error!("Discord WS closed with no frame, reconnecting...");
 		return Ok(());
 	};
-	let code: u16 = match frame.code {
-		CloseCode::Library(n) => n,
-		other => u16::from(other),
-	};
+	let code = close_code(&frame);
 	match code {
 		4004 | 4010 | 4011 | 4012 | 4013 | 4014 => Err(AdapterError::Auth {
 			surface: SURFACE,
@@ -313,6 +477,115 @@ fn classify_close(frame: Option<tokio_tungstenite::tungstenite::protocol::frame:
 	}
 }
 
+/// Heartbeat interval from the gateway's HELLO (op 10). Kept in milliseconds: Discord sends e.g. 41250, and
+/// truncating to whole seconds drifts from it (and would give tokio a zero period, which panics, below one). A zero
+/// interval is rejected for the same reason.
+fn hello_heartbeat_interval(hello: &DiscordMessage) -> Result<Duration> {
+	if hello.op != 10 {
+		bail!("Expected Discord HELLO (op 10), got op {}", hello.op);
+	}
+	let ms = hello
+		.d
+		.as_ref()
+		.and_then(|d| d.get("heartbeat_interval"))
+		.and_then(|v| v.as_u64())
+		.ok_or_else(|| color_eyre::eyre::eyre!("No heartbeat interval"))?;
+	if ms == 0 {
+		bail!("Discord HELLO has a zero heartbeat interval");
+	}
+	Ok(Duration::from_millis(ms))
+}
+
+fn close_code(frame: &tokio_tungstenite::tungstenite::protocol::frame::CloseFrame) -> u16 {
+	match frame.code {
+		CloseCode::Library(n) => n,
+		other => u16::from(other),
+	}
+}
+
+/// 4007 (invalid seq) and 4009 (session timed out) end the session, so the next connection must identify afresh.
+fn close_allows_resume(code: u16) -> bool {
+	!matches!(code, 4007 | 4009)
+}
+
+/// Turns gateway messages into JSON text. With `zlib-stream`, a connection is a single zlib stream, split across binary
+/// messages: a payload is complete once a message ends in [`ZLIB_SUFFIX`], and must be inflated with the context of
+/// everything before it.
+enum GatewayFrames {
+	Plain,
+	ZlibStream { buffer: Vec<u8>, inflate: Decompress },
+}
+
+impl GatewayFrames {
+	fn new(compress: bool) -> Self {
+		if compress {
+			Self::ZlibStream {
+				buffer: Vec::new(),
+				inflate: Decompress::new(true),
+			}
+		} else {
+			Self::Plain
+		}
+	}
+
+	/// The payload `message` completes, if any. Non-data messages (ping, pong, close) yield `None`.
+	fn decode(&mut self, message: Message) -> Result<Option<String>> {
+		match (self, message) {
+			(_, Message::Text(text)) => Ok(Some(text.to_string())),
+			(Self::Plain, Message::Binary(bytes)) => Ok(Some(String::from_utf8(bytes.to_vec())?)),
+			(Self::ZlibStream { buffer, inflate }, Message::Binary(bytes)) => {
+				buffer.extend_from_slice(&bytes);
+				if !buffer.ends_with(&ZLIB_SUFFIX) {
+					return Ok(None);
+				}
+				let start = inflate.total_in();
+				let mut out = Vec::with_capacity(buffer.len() * 4);
+				loop {
+					let consumed = (inflate.total_in() - start) as usize;
+					let produced = out.len();
+					out.reserve(4096);
+					inflate
+						.decompress_vec(&buffer[consumed..], &mut out, FlushDecompress::Sync)
+						.context("Corrupt zlib-stream payload")?;
+					let now_consumed = (inflate.total_in() - start) as usize;
+					// Done once all input is in and inflate has no more to give: it stopped short of filling the output,
+					// or (when the last call happened to fill it exactly) this call produced nothing
+					if now_consumed == buffer.len() && (out.len() < out.capacity() || out.len() == produced) {
+						break;
+					}
+					if now_consumed == consumed && out.len() == produced {
+						bail!("zlib-stream made no progress on a {}-byte payload", buffer.len());
+					}
+				}
+				buffer.clear();
+				Ok(Some(String::from_utf8(out)?))
+			}
+			_ => Ok(None),
+		}
+	}
+}
+
+/// Run `handshake` up to `attempts` times (always at least once), sleeping `delay` between failures. Returns the first
+/// success or the last error.
+async fn retry_handshake<T, F, Fut>(attempts: u8, delay: Duration, mut handshake: F) -> Result<T>
+where
+	F: FnMut() -> Fut,
+	Fut: Future<Output = Result<T>>, {
+	let attempts = attempts.max(1);
+	let mut attempt = 1;
+	loop {
+		match handshake().await {
+			Ok(connected) => return Ok(connected),
+			Err(e) if attempt < attempts => {
+				warn!("Discord handshake failed (attempt {attempt}/{attempts}): {e:#}, retrying in {:.1}s", delay.as_secs_f64());
+				time::sleep(delay).await;
+				attempt += 1;
+			}
+			Err(e) => return Err(e),
+		}
+	}
+}
+
 #[derive(Debug, Deserialize, Serialize)]
 struct DiscordMessage {
 	op: u8,
@@ -322,3 +595,200 @@ struct DiscordMessage {
 	#[serde(skip_serializing_if = "Option::is_none")]
 	t: Option<String>,
 }
+
+/// What a gateway payload asks of the session, by opcode.
+#[derive(Debug, PartialEq)]
+enum GatewayOp {
+	/// 0: an event (READY, MESSAGE_CREATE, ...)
+	Dispatch,
+	/// 1: the server wants a heartbeat now, not at the next interval tick
+	HeartbeatRequest,
+	/// 7: reconnect and resume
+	Reconnect,
+	/// 9: the session is gone; `d` says whether RESUME can still pick it up
+	InvalidSession {
+		resumable: bool,
+	},
+	/// 11
+	HeartbeatAck,
+	Other(u8),
+}
+
+impl GatewayOp {
+	fn of(message: &DiscordMessage) -> Self {
+		match message.op {
+			0 => Self::Dispatch,
+			1 => Self::HeartbeatRequest,
+			7 => Self::Reconnect,
+			9 => Self::InvalidSession {
+				resumable: message.d.as_ref().and_then(|d| d.as_bool()).unwrap_or(false),
+			},
+			11 => Self::HeartbeatAck,
+			op => Self::Other(op),
+		}
+	}
+}
+
+fn __default_handshake_attempts() -> u8 {
+	3
+}
+
+fn __default_intents() -> u64 {
+	DEFAULT_INTENTS
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_zlib_stream_frames() {
+		use std::io::Write;
+
+		// Discord flushes the one stream after every payload; later payloads reuse earlier ones' dictionary
+		let mut encoder = flate2::write::ZlibEncoder::new(Vec::new(), flate2::Compression::default());
+		let mut payload = |json: &str| {
+			encoder.write_all(json.as_bytes()).unwrap();
+			encoder.flush().unwrap();
+			std::mem::take(encoder.get_mut())
+		};
+		let hello = payload(r#"{"t":null,"s":null,"op":10,"d":{"heartbeat_interval":41250}}"#);
+		let dispatch = payload(r#"{"t":"MESSAGE_CREATE","s":1,"op":0,"d":{"content":"hi"}}"#);
+		assert!(hello.ends_with(&ZLIB_SUFFIX) && dispatch.ends_with(&ZLIB_SUFFIX));
+
+		let mut frames = GatewayFrames::new(true);
+		// A payload can arrive split over several messages
+		let (first, rest) = hello.split_at(hello.len() / 2);
+		assert_eq!(frames.decode(Message::Binary(first.to_vec().into())).unwrap(), None);
+		let hello = frames.decode(Message::Binary(rest.to_vec().into())).unwrap().unwrap();
+		assert_eq!(hello_heartbeat_interval(&serde_json::from_str(&hello).unwrap()).unwrap(), Duration::from_millis(41250));
+		let dispatch = frames.decode(Message::Binary(dispatch.into())).unwrap().unwrap();
+		assert_eq!(serde_json::from_str::<DiscordMessage>(&dispatch).unwrap().d.unwrap()["content"], "hi");
+		assert_eq!(frames.decode(Message::Ping(Vec::new().into())).unwrap(), None);
+
+		let mut plain = GatewayFrames::new(false);
+		assert_eq!(plain.decode(Message::Text("{}".into())).unwrap().as_deref(), Some("{}"));
+	}
+
+	#[tokio::test(start_paused = true)]
+	async fn test_retry_handshake() {
+		let calls = std::cell::Cell::new(0);
+		let flaky = |fail_first: u32| {
+			calls.set(0);
+			let calls = &calls;
+			move || {
+				calls.set(calls.get() + 1);
+				let n = calls.get();
+				async move { if n <= fail_first { bail!("hiccup {n}") } else { Ok(n) } }
+			}
+		};
+
+		assert_eq!(retry_handshake(3, HANDSHAKE_RETRY_DELAY, flaky(2)).await.unwrap(), 3);
+		let err = retry_handshake(3, HANDSHAKE_RETRY_DELAY, flaky(5)).await.unwrap_err();
+		assert_eq!((err.to_string(), calls.get()), ("hiccup 3".to_string(), 3));
+		// 0 still makes one attempt
+		assert!(retry_handshake(0, HANDSHAKE_RETRY_DELAY, flaky(5)).await.is_err());
+		assert_eq!(calls.get(), 1);
+	}
+
+	#[test]
+	fn test_message_media() {
+		let data = json!({
+			"content": "",
+			"attachments": [{ "id": "1", "filename": "a.png", "url": "https://cdn.discordapp.com/attachments/1/2/a.png" }],
+			"embeds": [{ "type": "article", "title": "Some article", "url": "https://example.com/a" }, { "type": "image", "url": "https://example.com/b.png" }, { "type": "rich" }],
+		});
+		let (attachments, embeds) = message_media(&data);
+		assert_eq!(attachments, ["https://cdn.discordapp.com/attachments/1/2/a.png"]);
+		assert_eq!(embeds, ["Some article", "https://example.com/b.png"]);
+		assert_eq!(message_media(&json!({ "content": "hi" })), (vec![], vec![]));
+	}
+
+	fn config() -> DiscordConfig {
+		DiscordConfig {
+			user_token: "tok".to_string(),
+			intents: DEFAULT_INTENTS,
+			..Default::default()
+		}
+	}
+
+	#[test]
+	fn test_identify_payload() {
+		let (_, identify) = GatewayState::default().opening(&config());
+		let d = identify.d.unwrap();
+		assert_eq!(d["intents"], 37377);
+		// Unset, the presence is left out rather than forced to online
+		assert_eq!(d.get("presence"), None);
+
+		let configured: DiscordConfig = serde_json::from_str(r#"{"user_token":"tok","my_username":"me","intents":4096,"presence":"invisible"}"#).unwrap();
+		let d = GatewayState::default().opening(&configured).1.d.unwrap();
+		assert_eq!((&d["intents"], &d["presence"]["status"]), (&json!(4096), &json!("invisible")));
+	}
+
+	#[test]
+	fn test_gateway_resume() {
+		let mut gateway = GatewayState::default();
+		let (url, opening) = gateway.opening(&config());
+		assert_eq!((url.as_str(), opening.op), ("wss://gateway.discord.gg/?v=10&encoding=json", 2));
+		assert_eq!(opening.d.unwrap()["properties"], json!({ "os": "linux", "browser": "rust", "device": "pc" }));
+
+		gateway.ready(&json!({ "user": { "id": "1" }, "session_id": "abc", "resume_gateway_url": "wss://gateway-us-east1-b.discord.gg" }));
+		// No dispatch sequence seen yet, nothing to resume from
+		assert_eq!(gateway.opening(&config()).1.op, 2);
+
+		gateway.seq = Some(42);
+		let (url, opening) = gateway.opening(&config());
+		assert_eq!(url, "wss://gateway-us-east1-b.discord.gg/?v=10&encoding=json");
+		assert_eq!(
+			serde_json::to_value(&opening).unwrap(),
+			json!({ "op": 6, "d": { "token": "tok", "session_id": "abc", "seq": 42 } })
+		);
+
+		gateway.invalidate();
+		assert_eq!(gateway.opening(&config()).1.op, 2);
+		assert_eq!(gateway.seq, None);
+	}
+
+	#[test]
+	fn test_hello_heartbeat_interval() {
+		let hello: DiscordMessage = serde_json::from_str(r#"{"t":null,"s":null,"op":10,"d":{"heartbeat_interval":41250}}"#).unwrap();
+		assert_eq!(hello_heartbeat_interval(&hello).unwrap(), Duration::from_millis(41250));
+		let dispatch: DiscordMessage = serde_json::from_str(r#"{"t":"READY","s":1,"op":0,"d":{}}"#).unwrap();
+		assert!(hello_heartbeat_interval(&dispatch).is_err());
+		let zero: DiscordMessage = serde_json::from_str(r#"{"t":null,"s":null,"op":10,"d":{"heartbeat_interval":0}}"#).unwrap();
+		assert!(hello_heartbeat_interval(&zero).is_err());
+	}
+
+	#[test]
+	fn test_gateway_op_routing() {
+		let ops: Vec<_> = [
+			r#"{"op":0,"s":3,"t":"MESSAGE_CREATE","d":{}}"#,
+			r#"{"op":1,"d":null}"#,
+			r#"{"op":7,"d":null}"#,
+			r#"{"op":9,"d":true}"#,
+			r#"{"op":9,"d":false}"#,
+			r#"{"op":11,"d":null}"#,
+			r#"{"op":10,"d":{"heartbeat_interval":41250}}"#,
+		]
+		.iter()
+		.map(|json| format!("{:?}", GatewayOp::of(&serde_json::from_str(json).unwrap())))
+		.collect();
+		insta::assert_snapshot!(ops.join("\n"), @"
+		Dispatch
+		HeartbeatRequest
+		Reconnect
+		InvalidSession { resumable: true }
+		InvalidSession { resumable: false }
+		HeartbeatAck
+		Other(10)
+		");
+	}
+
+	#[test]
+	fn test_close_allows_resume() {
+		assert!(close_allows_resume(1001));
+		assert!(close_allows_resume(4000));
+		assert!(!close_allows_resume(4007));
+		assert!(!close_allows_resume(4009));
+	}
+}
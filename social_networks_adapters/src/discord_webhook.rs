@@ -0,0 +1,119 @@
+//! Notifications posted to a Discord channel through an incoming webhook, alongside the Telegram ones. Only YouTube
+//! uploads go here for now (`youtube.discord_webhook`).
+use color_eyre::eyre::{Result, bail};
+use reqwest::Client;
+use serde::Serialize;
+use social_networks_utils::{dedup::RecentlyNotified, http};
+use tracing::info;
+
+#[derive(Clone, Debug)]
+pub struct DiscordWebhook {
+	client: Client,
+	url: String,
+	recent: RecentlyNotified,
+}
+
+impl DiscordWebhook {
+	pub fn new(url: String) -> Self {
+		Self {
+			client: http::client(),
+			url,
+			recent: RecentlyNotified::default(),
+		}
+	}
+
+	/// Same notification as [`TelegramNotifier::send_youtube_notification`](crate::telegram_notifier::TelegramNotifier::send_youtube_notification),
+	/// as an embed with the thumbnail. Deduped like it; returns whether it was sent.
+	pub async fn send_youtube_notification(&self, channel_name: &str, headline: &str, title: &str, sentiment: &str, video_id: &str) -> Result<bool> {
+		let item = format!("youtube:{video_id}|discord_webhook");
+		if !self.recent.claim(&item).await {
+			info!("Already posted {video_id} to the Discord webhook recently, not sending again");
+			return Ok(false);
+		}
+		if let Err(e) = self.post(&youtube_payload(channel_name, headline, title, sentiment, video_id)).await {
+			self.recent.release(&item).await;
+			return Err(e);
+		}
+		Ok(true)
+	}
+
+	async fn post(&self, payload: &WebhookPayload) -> Result<()> {
+		// The webhook's token is part of its URL, so keep it out of the error
+		let response = self.client.post(&self.url).json(payload).send().await.map_err(reqwest::Error::without_url)?;
+		let status = response.status();
+		if !status.is_success() {
+			bail!("Discord webhook answered {status}: {}", response.text().await.unwrap_or_default());
+		}
+		Ok(())
+	}
+}
+
+fn youtube_payload(channel_name: &str, headline: &str, title: &str, sentiment: &str, video_id: &str) -> WebhookPayload {
+	WebhookPayload {
+		content: format!("[{channel_name}] {headline}"),
+		embeds: vec![Embed {
+			title: title.to_string(),
+			url: format!("https://youtube.com/watch?v={video_id}"),
+			description: format!("Perception: {sentiment}"),
+			thumbnail: EmbedImage {
+				url: format!("https://img.youtube.com/vi/{video_id}/hqdefault.jpg"),
+			},
+		}],
+		// Titles are arbitrary text; an `@everyone` in one must not ping the server
+		allowed_mentions: AllowedMentions { parse: Vec::new() },
+	}
+}
+
+/// Body of an [execute webhook](https://discord.com/developers/docs/resources/webhook#execute-webhook) request.
+#[derive(Debug, Serialize)]
+struct WebhookPayload {
+	content: String,
+	embeds: Vec<Embed>,
+	allowed_mentions: AllowedMentions,
+}
+
+#[derive(Debug, Serialize)]
+struct Embed {
+	title: String,
+	url: String,
+	description: String,
+	thumbnail: EmbedImage,
+}
+
+#[derive(Debug, Serialize)]
+struct EmbedImage {
+	url: String,
+}
+
+#[derive(Debug, Serialize)]
+struct AllowedMentions {
+	parse: Vec<String>,
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_youtube_payload() {
+		let payload = youtube_payload("Ben", "uploaded a new video", "BTC @everyone", "bullish", "dQw4w9WgXcQ");
+		insta::assert_snapshot!(serde_json::to_string_pretty(&payload).unwrap(), @r#"
+		{
+		  "content": "[Ben] uploaded a new video",
+		  "embeds": [
+		    {
+		      "title": "BTC @everyone",
+		      "url": "https://youtube.com/watch?v=dQw4w9WgXcQ",
+		      "description": "Perception: bullish",
+		      "thumbnail": {
+		        "url": "https://img.youtube.com/vi/dQw4w9WgXcQ/hqdefault.jpg"
+		      }
+		    }
+		  ],
+		  "allowed_mentions": {
+		    "parse": []
+		  }
+		}
+		"#);
+	}
+}
@@ -18,6 +18,10 @@ pub enum DmEvent {
 		is_dm: bool,
 		mentions_me: bool,
 		is_reply_to_me: bool,
+		/// URLs of attached files. Empty for platforms that don't report them.
+		attachments: Vec<String>,
+		/// Titles of link embeds, or their URL when untitled.
+		embeds: Vec<String>,
 	},
 	IncomingCall {
 		platform: &'static str,
@@ -2,8 +2,10 @@
 #![feature(default_field_values)]
 pub mod client;
 pub mod discord;
+pub mod discord_webhook;
 pub mod dm_event;
 pub mod email;
+mod pop3;
 pub mod telegram_channel_watch;
 pub mod telegram_dms;
 pub mod telegram_notifier;
@@ -0,0 +1,172 @@
+//! Minimal blocking POP3 client (RFC 1939): just the commands the email monitor needs.
+use std::{
+	io::{BufRead, BufReader, Read, Write},
+	net::TcpStream,
+	time::Duration,
+};
+
+use color_eyre::eyre::{Context, ContextCompat, Result, bail};
+
+const TIMEOUT: Duration = Duration::from_secs(60);
+
+pub(crate) struct Pop3Session<S: Read + Write> {
+	stream: BufReader<S>,
+}
+
+impl Pop3Session<native_tls::TlsStream<TcpStream>> {
+	/// Connect with implicit TLS (POP3S, usually port 995) and read the greeting.
+	pub fn connect(host: &str, port: u16) -> Result<Self> {
+		let tcp = TcpStream::connect((host, port)).with_context(|| format!("Failed to connect to POP3 server {host}:{port}"))?;
+		tcp.set_read_timeout(Some(TIMEOUT))?;
+		tcp.set_write_timeout(Some(TIMEOUT))?;
+		let tls = native_tls::TlsConnector::new()?.connect(host, tcp).context("POP3 TLS handshake failed")?;
+		Self::from_stream(tls)
+	}
+}
+
+impl<S: Read + Write> Pop3Session<S> {
+	fn from_stream(stream: S) -> Result<Self> {
+		let mut session = Self { stream: BufReader::new(stream) };
+		session.read_status().context("Bad POP3 greeting")?;
+		Ok(session)
+	}
+
+	pub fn login(&mut self, user: &str, pass: &str) -> Result<()> {
+		self.command(&format!("USER {user}"))?;
+		self.command(&format!("PASS {pass}")).context("POP3 login failed")?;
+		Ok(())
+	}
+
+	/// `(message number, unique id)` for every message in the maildrop.
+	pub fn uidl(&mut self) -> Result<Vec<(u32, String)>> {
+		self.command("UIDL")?;
+		let listing = String::from_utf8_lossy(&self.read_multiline()?).into_owned();
+		listing
+			.lines()
+			.map(|line| {
+				let (n, uid) = line.split_once(' ').with_context(|| format!("Malformed UIDL line: {line}"))?;
+				Ok((n.parse().with_context(|| format!("Malformed UIDL line: {line}"))?, uid.trim().to_string()))
+			})
+			.collect()
+	}
+
+	/// Full RFC822 message, dot-unstuffed.
+	pub fn retr(&mut self, n: u32) -> Result<Vec<u8>> {
+		self.command(&format!("RETR {n}"))?;
+		self.read_multiline()
+	}
+
+	/// Mark for deletion; the server only deletes on a clean `QUIT`.
+	pub fn dele(&mut self, n: u32) -> Result<()> {
+		self.command(&format!("DELE {n}"))?;
+		Ok(())
+	}
+
+	pub fn quit(mut self) -> Result<()> {
+		self.command("QUIT")?;
+		Ok(())
+	}
+
+	fn command(&mut self, cmd: &str) -> Result<String> {
+		let stream = self.stream.get_mut();
+		stream.write_all(cmd.as_bytes())?;
+		stream.write_all(b"\r\n")?;
+		stream.flush()?;
+		// Don't echo credentials into errors
+		let verb = cmd.split(' ').next().unwrap_or(cmd);
+		self.read_status().with_context(|| format!("POP3 {verb} failed"))
+	}
+
+	fn read_status(&mut self) -> Result<String> {
+		let mut line = String::new();
+		if self.stream.read_line(&mut line)? == 0 {
+			bail!("POP3 connection closed");
+		}
+		let line = line.trim_end();
+		match line.strip_prefix("+OK") {
+			Some(rest) => Ok(rest.trim_start().to_string()),
+			None => bail!("{line}"),
+		}
+	}
+
+	fn read_multiline(&mut self) -> Result<Vec<u8>> {
+		let mut out = Vec::new();
+		let mut line = Vec::new();
+		loop {
+			line.clear();
+			if self.stream.read_until(b'\n', &mut line)? == 0 {
+				bail!("POP3 connection closed mid-response");
+			}
+			let content = line.strip_suffix(b"\r\n").or_else(|| line.strip_suffix(b"\n")).unwrap_or(&line);
+			if content == b"." {
+				return Ok(out);
+			}
+			out.extend_from_slice(content.strip_prefix(b".").unwrap_or(content));
+			out.extend_from_slice(b"\r\n");
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::io::Cursor;
+
+	use super::*;
+
+	/// Replays canned server output and records what the client sent.
+	struct Scripted {
+		input: Cursor<Vec<u8>>,
+		sent: Vec<u8>,
+	}
+	impl Read for Scripted {
+		fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+			self.input.read(buf)
+		}
+	}
+	impl Write for Scripted {
+		fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+			self.sent.write(buf)
+		}
+
+		fn flush(&mut self) -> std::io::Result<()> {
+			Ok(())
+		}
+	}
+
+	fn session(server: &str) -> Pop3Session<Scripted> {
+		Pop3Session::from_stream(Scripted {
+			input: Cursor::new(server.as_bytes().to_vec()),
+			sent: Vec::new(),
+		})
+		.unwrap()
+	}
+
+	#[test]
+	fn test_session() {
+		let mut s = session(concat!(
+			"+OK POP3 ready\r\n",
+			"+OK\r\n",
+			"+OK logged in\r\n",
+			"+OK 2 messages\r\n1 abc123\r\n2 def456\r\n.\r\n",
+			"+OK message follows\r\nSubject: hi\r\n\r\n..leading dot\r\nbody\r\n.\r\n",
+			"+OK deleted\r\n",
+			"+OK bye\r\n",
+		));
+		s.login("me", "hunter2").unwrap();
+		assert_eq!(s.uidl().unwrap(), vec![(1, "abc123".to_string()), (2, "def456".to_string())]);
+		assert_eq!(String::from_utf8(s.retr(1).unwrap()).unwrap(), "Subject: hi\r\n\r\n.leading dot\r\nbody\r\n");
+		s.dele(1).unwrap();
+		let sent = String::from_utf8(s.stream.get_ref().sent.clone()).unwrap();
+		s.quit().unwrap();
+		assert_eq!(sent, "USER me\r\nPASS hunter2\r\nUIDL\r\nRETR 1\r\nDELE 1\r\n");
+	}
+
+	#[test]
+	fn test_err_response_hides_credentials() {
+		let mut s = session("+OK ready\r\n+OK\r\n-ERR invalid password\r\n");
+		let err = s.login("me", "hunter2").unwrap_err();
+		let msg = format!("{err:#}");
+		assert!(msg.contains("-ERR invalid password"));
+		assert!(!msg.contains("hunter2"));
+	}
+}
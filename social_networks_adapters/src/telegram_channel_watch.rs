@@ -1,15 +1,21 @@
-use std::convert::Infallible;
+use std::collections::HashSet;
 
 use clap::Args;
-use color_eyre::eyre::Result;
-use futures::future::{Either, select};
-use grammers_client::{Client, update::Update};
-use grammers_session::types::PeerRef;
+use color_eyre::eyre::{Context, Result, bail};
+use futures::future::{Either, join_all, select, select_all};
+use grammers_client::{Client, peer::Peer, update::Update};
+use grammers_session::types::{PeerId, PeerKind, PeerRef};
 use jiff::{SignedDuration, Timestamp};
+use regex::Regex;
 use serde::{Deserialize, Serialize};
-use social_networks_utils::telegram_utils::{self, ConnectionConfig, TelegramConnection};
-use tokio::time::{self, Duration};
-use tracing::{debug, error, info};
+use social_networks_utils::{
+	clock::{Clock, SystemClock},
+	dedup::RecentlyNotified,
+	shutdown, snooze, status,
+	telegram_utils::{self, ConnectionConfig, TelegramConnection},
+	utils::reconnect_delay,
+};
+use tracing::{debug, error, info, instrument};
 
 use crate::{
 	client::{AdapterError, Client as AdapterClient},
@@ -18,7 +24,15 @@ use crate::{
 
 const SURFACE: &str = "telegram_channel_watch";
 #[derive(Args)]
-pub struct TelegramArgs {}
+pub struct TelegramArgs {
+	/// Forward everything to this channel (username, or id) for this run instead of the configured outputs
+	#[arg(long, value_name = "USERNAME_OR_ID", value_parser = parse_destination)]
+	pub output_channel: Option<TelegramDestination>,
+}
+
+fn parse_destination(s: &str) -> Result<TelegramDestination, String> {
+	s.parse().map_err(|e| format!("{e:#}"))
+}
 
 pub struct TelegramChannelWatch {
 	telegram_config: TelegramConfig,
@@ -28,6 +42,16 @@ impl TelegramChannelWatch {
 	pub fn new(telegram_config: TelegramConfig) -> Self {
 		Self { telegram_config }
 	}
+
+	/// Override `channel_output` with `destination`, and drop `poll_output`/`info_output` so nothing bypasses it.
+	pub fn output_channel(mut self, destination: Option<TelegramDestination>) -> Self {
+		if let Some(destination) = destination {
+			self.telegram_config.channel_output = destination;
+			self.telegram_config.poll_output = None;
+			self.telegram_config.info_output = None;
+		}
+		self
+	}
 }
 
 impl AdapterClient for TelegramChannelWatch {
@@ -35,18 +59,129 @@ impl AdapterClient for TelegramChannelWatch {
 		SURFACE
 	}
 
-	async fn listen(&mut self) -> Result<Infallible, AdapterError> {
+	async fn listen(&mut self) -> Result<(), AdapterError> {
 		println!("Starting Telegram Channel Watch...");
-		let mut attempt: u32 = 0;
-		loop {
-			match run_telegram_monitor(&self.telegram_config).await {
-				Err(ChannelWatchError::Auth(detail)) => return Err(AdapterError::Auth { surface: SURFACE, detail }),
-				Err(ChannelWatchError::Recoverable(e)) => {
-					let delay = reconnect_delay(attempt);
-					error!("Telegram monitor error: {e:#}\nReconnecting in {:.1}s...", delay.as_secs_f64());
-					time::sleep(delay).await;
-					attempt = attempt.saturating_add(1);
+		let sessions = watch_sessions(&self.telegram_config).map_err(|e| AdapterError::Unhandled {
+			surface: SURFACE,
+			detail: format!("{e:#}"),
+		})?;
+		let info_filter = InfoFilter::new(&self.telegram_config).map_err(|e| AdapterError::Unhandled {
+			surface: SURFACE,
+			detail: format!("{e:#}"),
+		})?;
+		// Accounts reconnect independently; the first terminal error stops them all, like `dms::run`'s adapters
+		let monitors = sessions.iter().map(|session| Box::pin(watch_account(&self.telegram_config, session, &info_filter)));
+		let (result, _, rest) = select_all(monitors).await;
+		if result.is_ok() {
+			// Shutdown: the other accounts are stopping too, let them save their positions
+			join_all(rest).await;
+		}
+		result
+	}
+}
+
+/// One logged-in account and the channels it watches.
+#[derive(Debug)]
+struct WatchSession<'a> {
+	username: &'a str,
+	phone: &'a str,
+	session_suffix: &'static str,
+	poll_channels: &'a [String],
+	info_channels: &'a [String],
+	/// Shared by every session: skipped when resolving `poll_channels`/`info_channels`
+	excluded_channels: &'a [String],
+	/// Only the main account's profile gets the status from `telegram_status.json`
+	updates_status: bool,
+}
+
+impl WatchSession<'_> {
+	/// Name this account reports its [`status`] under; the main account uses the surface name `alert` marks on exit.
+	fn status_name(&self) -> String {
+		if self.session_suffix.is_empty() {
+			SURFACE.to_string()
+		} else {
+			format!("{SURFACE}:{}", self.username.trim_start_matches('@'))
+		}
+	}
+
+	/// `channels` without the excluded ones.
+	fn watched<'s>(&'s self, channels: &'s [String]) -> impl Iterator<Item = &'s String> {
+		channels.iter().filter(|channel| {
+			let excluded = self.excluded_channels.iter().any(|e| channel_username(e).eq_ignore_ascii_case(channel_username(channel)));
+			if excluded {
+				info!("Skipping excluded channel {channel}");
+			}
+			!excluded
+		})
+	}
+}
+
+/// Username in a channel link (`https://t.me/name`), or a bare `name`/`@name`.
+fn channel_username(channel: &str) -> &str {
+	channel.trim_start_matches("https://t.me/").trim_start_matches('@')
+}
+
+/// The main account followed by each of `watch_accounts`. A channel assigned to two accounts would be forwarded twice,
+/// and an account listed twice would fight over its session, so both are config errors.
+fn watch_sessions(telegram_config: &TelegramConfig) -> Result<Vec<WatchSession<'_>>> {
+	let main = WatchSession {
+		username: &telegram_config.username,
+		phone: &telegram_config.phone,
+		session_suffix: "",
+		poll_channels: &telegram_config.poll_channels,
+		info_channels: &telegram_config.info_channels,
+		excluded_channels: &telegram_config.excluded_channels,
+		updates_status: true,
+	};
+	let extra = telegram_config.watch_accounts.iter().map(|account| WatchSession {
+		username: &account.username,
+		phone: &account.phone,
+		session_suffix: "_watch",
+		poll_channels: &account.poll_channels,
+		info_channels: &account.info_channels,
+		excluded_channels: &telegram_config.excluded_channels,
+		updates_status: false,
+	});
+	let sessions: Vec<_> = std::iter::once(main).chain(extra).collect();
+
+	let mut usernames = HashSet::new();
+	let mut channels = HashSet::new();
+	for session in &sessions {
+		if !usernames.insert(session.username.trim_start_matches('@')) {
+			bail!("Telegram account {} is listed more than once for channel watching", session.username);
+		}
+		for channel in session.poll_channels.iter().chain(session.info_channels) {
+			if !channels.insert(channel.trim_start_matches("https://t.me/")) {
+				bail!("Telegram channel {channel} is assigned to more than one watch account");
+			}
+		}
+	}
+	Ok(sessions)
+}
+
+/// Keep one account's monitor running, reconnecting with backoff; returns only on shutdown or an auth error.
+async fn watch_account(telegram_config: &TelegramConfig, session: &WatchSession<'_>, info_filter: &InfoFilter) -> Result<(), AdapterError> {
+	let mut attempt: u32 = 0;
+	loop {
+		match run_telegram_monitor(telegram_config, session, info_filter, &SystemClock).await {
+			Ok(()) => return Ok(()),
+			Err(ChannelWatchError::Auth(detail)) => {
+				if !session.session_suffix.is_empty() {
+					status::failed(&session.status_name());
+				}
+				return Err(AdapterError::Auth {
+					surface: SURFACE,
+					detail: format!("{}: {detail}", session.username),
+				});
+			}
+			Err(ChannelWatchError::Recoverable(e)) => {
+				status::reconnecting(&session.status_name());
+				let delay = reconnect_delay(attempt);
+				error!("Telegram monitor error ({}): {e:#}\nReconnecting in {:.1}s...", session.username, delay.as_secs_f64());
+				if shutdown::requested_during(delay).await {
+					return Ok(());
 				}
+				attempt = attempt.saturating_add(1);
 			}
 		}
 	}
@@ -63,21 +198,114 @@ impl<E: Into<color_eyre::eyre::Report>> From<E> for ChannelWatchError {
 	}
 }
 
+/// Who may trigger an info-channel keyword forward in a group.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct TrustedSenders {
+	/// Let the group's admins through, including anonymous ones (who post as the group itself)
+	#[serde(default)]
+	pub admins: bool,
+	/// Usernames (`@` optional, case-insensitive) or numeric ids, of users or of channels posting in the group
+	#[serde(default)]
+	pub allow: Vec<String>,
+}
+
+/// A message's sender, as far as [`check_sender`] cares.
+#[derive(Debug)]
+enum Sender<'a> {
+	/// A post in a broadcast channel
+	Broadcast,
+	/// Sent as the group itself, which only its anonymous admins can do
+	AsGroup,
+	/// A member, or a channel a member posts as (`is_user = false`), which can't be an admin
+	Member {
+		id: i64,
+		username: Option<&'a str>,
+		is_user: bool,
+	},
+	Unknown,
+}
+
+#[derive(Debug, PartialEq)]
+enum SenderCheck {
+	Allowed,
+	Denied,
+	/// Allowed only if the sender turns out to be an admin of the group
+	IfAdmin,
+}
+
+impl<'a> Sender<'a> {
+	fn of(message: &'a grammers_client::update::Message) -> Self {
+		if matches!(message.peer(), Some(Peer::Channel(_))) {
+			return Sender::Broadcast;
+		}
+		match message.sender_id() {
+			None => Sender::Unknown,
+			Some(id) if id == message.peer_id() => Sender::AsGroup,
+			Some(id) => Sender::Member {
+				id: id.bare_id_unchecked(),
+				username: message.sender().and_then(|p| p.username()),
+				is_user: id.kind() == PeerKind::User,
+			},
+		}
+	}
+}
+
+/// Whether `sender` may trigger a forward under `trusted` (everyone may if `None`). The admin lookup costs a request,
+/// so it's left to the caller ([`SenderCheck::IfAdmin`]) and only made for members not on the allow-list.
+fn check_sender(trusted: Option<&TrustedSenders>, sender: &Sender) -> SenderCheck {
+	let Some(trusted) = trusted else {
+		return SenderCheck::Allowed;
+	};
+	match *sender {
+		Sender::Broadcast => SenderCheck::Allowed,
+		Sender::AsGroup if trusted.admins => SenderCheck::Allowed,
+		Sender::AsGroup | Sender::Unknown => SenderCheck::Denied,
+		Sender::Member { id, username, is_user } => {
+			let listed = trusted
+				.allow
+				.iter()
+				.map(|a| a.trim_start_matches('@'))
+				.any(|allowed| allowed.parse::<i64>().is_ok_and(|allowed_id| allowed_id == id) || username.is_some_and(|u| u.eq_ignore_ascii_case(allowed)));
+			match (listed, trusted.admins && is_user) {
+				(true, _) => SenderCheck::Allowed,
+				(false, true) => SenderCheck::IfAdmin,
+				(false, false) => SenderCheck::Denied,
+			}
+		}
+	}
+}
+
+/// [`check_sender`], asking Telegram about admin status when it comes to that.
+async fn sender_authorized(client: &Client, message: &grammers_client::update::Message, trusted: Option<&TrustedSenders>) -> Result<bool> {
+	let sender = Sender::of(message);
+	match check_sender(trusted, &sender) {
+		SenderCheck::Allowed => Ok(true),
+		SenderCheck::Denied => Ok(false),
+		SenderCheck::IfAdmin => {
+			let chat = message.peer_ref().await.map_err(|e| color_eyre::eyre::eyre!(e))?;
+			let user = message.sender_ref().await.map_err(|e| color_eyre::eyre::eyre!(e))?;
+			let (Some(chat), Some(user)) = (chat, user) else {
+				debug!("Can't look up admin status of {sender:?}, not trusting it");
+				return Ok(false);
+			};
+			Ok(client.get_permissions(chat, user).await?.is_admin())
+		}
+	}
+}
+
 #[derive(Debug, Default, Deserialize, Serialize)]
 struct StatusDrop {
 	status: String,
 }
 
-fn reconnect_delay(attempt: u32) -> Duration {
-	let delay_secs = std::f64::consts::E.powi(attempt as i32).min(600.0);
-	Duration::from_secs_f64(delay_secs)
-}
-
-async fn run_telegram_monitor(telegram_config: &TelegramConfig) -> Result<Infallible, ChannelWatchError> {
+#[instrument(skip_all, fields(account = session.username))]
+async fn run_telegram_monitor(telegram_config: &TelegramConfig, session: &WatchSession<'_>, info_filter: &InfoFilter, clock: &impl Clock) -> Result<(), ChannelWatchError> {
 	let status_file = xdg::BaseDirectories::with_prefix("social_networks")
 		.place_state_file("telegram_status.json")
 		.map_err(color_eyre::eyre::Report::from)?;
-	let status_drop: StatusDrop = if status_file.exists() {
+	let status_drop: StatusDrop = if !session.updates_status {
+		StatusDrop::default()
+	} else if status_file.exists() {
 		let content = std::fs::read_to_string(&status_file).map_err(color_eyre::eyre::Report::from)?;
 		let status: StatusDrop = serde_json::from_str(&content).map_err(color_eyre::eyre::Report::from)?;
 		info!("Loaded status from file: {}", status.status);
@@ -88,11 +316,12 @@ async fn run_telegram_monitor(telegram_config: &TelegramConfig) -> Result<Infall
 	};
 
 	let TelegramConnection { client, mut updates, mut runner } = telegram_utils::connect(ConnectionConfig {
-		username: &telegram_config.username,
-		phone: &telegram_config.phone,
+		username: session.username,
+		phone: session.phone,
 		api_id: telegram_config.api_id,
 		api_hash: &telegram_config.api_hash,
-		session_suffix: "",
+		session_suffix: session.session_suffix,
+		catch_up: true,
 	})
 	.await
 	.map_err(|e| {
@@ -103,13 +332,15 @@ async fn run_telegram_monitor(telegram_config: &TelegramConfig) -> Result<Infall
 		}
 	})?;
 
-	println!("Telegram started");
+	println!("Telegram started ({})", session.username);
 	info!("--Telegram-- connected and authorized");
+	let status_name = session.status_name();
+	status::connected(&status_name);
 
-	info!("Resolving {} poll channels", telegram_config.poll_channels.len());
+	info!("Resolving {} poll channels", session.poll_channels.len());
 	let mut poll_peer_ids = Vec::new();
-	for channel in &telegram_config.poll_channels {
-		match client.resolve_username(channel.trim_start_matches("https://t.me/")).await? {
+	for channel in session.watched(session.poll_channels) {
+		match client.resolve_username(channel_username(channel)).await? {
 			Some(peer) => {
 				poll_peer_ids.push(peer.id());
 				info!(
@@ -123,10 +354,10 @@ async fn run_telegram_monitor(telegram_config: &TelegramConfig) -> Result<Infall
 		}
 	}
 
-	info!("Resolving {} info channels", telegram_config.info_channels.len());
+	info!("Resolving {} info channels", session.info_channels.len());
 	let mut info_peer_ids = Vec::new();
-	for channel in &telegram_config.info_channels {
-		match client.resolve_username(channel.trim_start_matches("https://t.me/")).await? {
+	for channel in session.watched(session.info_channels) {
+		match client.resolve_username(channel_username(channel)).await? {
 			Some(peer) => {
 				info_peer_ids.push(peer.id());
 				info!(
@@ -140,43 +371,26 @@ async fn run_telegram_monitor(telegram_config: &TelegramConfig) -> Result<Infall
 		}
 	}
 
-	let output_username = match &telegram_config.channel_output {
-		TelegramDestination::Channel(tg::TopLevelId::AtName(name)) | TelegramDestination::Group(tg::TopLevelId::AtName(name)) => name.trim_start_matches('@'),
-		_ => {
-			return Err(ChannelWatchError::Recoverable(color_eyre::eyre::eyre!(
-				"channel_output must be a username for grammers client forwarding"
-			)));
-		}
-	};
-
-	info!("Resolving output channel: {output_username}");
-	let watch_chat = match client.resolve_username(output_username).await? {
-		Some(peer) => {
-			info!("Output channel resolved: {}", peer.id().bot_api_dialog_id().expect("resolve_username never returns self"));
-			match peer.to_ref().await.map_err(|e| color_eyre::eyre::eyre!(e))? {
-				Some(r) => r,
-				None =>
-					return Err(ChannelWatchError::Recoverable(color_eyre::eyre::eyre!(
-						"Output channel peer has no access hash: {output_username}"
-					))),
-			}
-		}
-		None => {
-			error!("Could not resolve output channel: {output_username}");
-			return Err(ChannelWatchError::Recoverable(color_eyre::eyre::eyre!("Could not resolve output channel: {output_username}")));
-		}
+	let [(poll_key, poll_output), (info_key, info_output)] = output_destinations(telegram_config);
+	let poll_chat = resolve_output(&client, poll_key, poll_output).await?;
+	let info_chat = if info_output == poll_output {
+		poll_chat
+	} else {
+		resolve_output(&client, info_key, info_output).await?
 	};
 
 	eprintln!("Listening for channel messages...");
 	info!("Starting main event loop");
 
 	let mut message_counter = 0u64;
+	let recent = RecentlyNotified::default();
 	let mut last_status_update = Timestamp::default();
+	let mut last_position_saved = clock.now();
 
-	//LOOP: daemon - runs until process termination
-	loop {
+	//LOOP: daemon - runs until the connection fails or shutdown
+	let result = loop {
 		if telegram_utils::should_reconnect_for_stack() {
-			return Err(ChannelWatchError::Recoverable(color_eyre::eyre::eyre!("Stack usage critical, forcing reconnect")));
+			break Err(ChannelWatchError::Recoverable(color_eyre::eyre::eyre!("Stack usage critical, forcing reconnect")));
 		}
 
 		telegram_utils::log_stack("telegram_channel_watch loop start");
@@ -184,34 +398,39 @@ async fn run_telegram_monitor(telegram_config: &TelegramConfig) -> Result<Infall
 		enum Event {
 			Update(Box<Result<Update, grammers_client::InvocationError>>),
 			RunnerExited,
+			Shutdown,
 		}
 
 		let event = {
 			let update_fut = std::pin::pin!(updates.next());
 			let runner_fut = runner.as_mut();
-			match select(update_fut, runner_fut).await {
-				Either::Left((result, _)) => Event::Update(Box::new(result)),
-				Either::Right(((), _)) => Event::RunnerExited,
+			let shutdown_fut = std::pin::pin!(shutdown::requested());
+			match select(select(update_fut, runner_fut), shutdown_fut).await {
+				Either::Left((Either::Left((result, _)), _)) => Event::Update(Box::new(result)),
+				Either::Left((Either::Right(((), _)), _)) => Event::RunnerExited,
+				Either::Right(((), _)) => Event::Shutdown,
 			}
 		};
 
 		telegram_utils::log_stack("telegram_channel_watch after select");
 
 		match event {
+			Event::Shutdown => break Ok(()),
 			Event::RunnerExited => {
-				return Err(ChannelWatchError::Recoverable(color_eyre::eyre::eyre!("MTProto runner exited unexpectedly")));
+				break Err(ChannelWatchError::Recoverable(color_eyre::eyre::eyre!("MTProto runner exited unexpectedly")));
 			}
 			Event::Update(result) => match *result {
 				Err(e) => {
 					let s = format!("{e:#}");
 					if classify_invocation_auth(&s) {
-						return Err(ChannelWatchError::Auth(s));
+						break Err(ChannelWatchError::Auth(s));
 					}
 					error!("Error getting next update: {s}");
 					continue;
 				}
 				Ok(update) => {
 					message_counter += 1;
+					status::event(&status_name);
 
 					match update {
 						Update::NewMessage(message) if !message.outgoing() => {
@@ -224,12 +443,16 @@ async fn run_telegram_monitor(telegram_config: &TelegramConfig) -> Result<Infall
 							};
 							let peer_id = peer.id();
 
-							if poll_peer_ids.contains(&peer_id) {
-								if let Err(e) = handle_poll_message(&client, &message, watch_chat).await {
+							if telegram_config.watch_active_hours.as_ref().is_some_and(|h| !h.is_active(clock.now())) {
+								debug!("Outside active hours, not forwarding message from {}", peer.name().unwrap_or("unknown"));
+							} else if snooze::is_snoozed("telegram_channel_watch") {
+								debug!("Channel watch snoozed, not forwarding message from {}", peer.name().unwrap_or("unknown"));
+							} else if poll_peer_ids.contains(&peer_id) {
+								if let Err(e) = handle_poll_message(&client, &message, poll_chat, &recent).await {
 									error!("Error handling poll message: {e}");
 								}
 							} else if info_peer_ids.contains(&peer_id)
-								&& let Err(e) = handle_info_message(&client, &message, watch_chat).await
+								&& let Err(e) = handle_info_message(&client, &message, info_chat, &recent, info_filter, telegram_config.info_senders.as_ref()).await
 							{
 								error!("Error handling info message: {e}");
 							}
@@ -237,7 +460,11 @@ async fn run_telegram_monitor(telegram_config: &TelegramConfig) -> Result<Infall
 						_ => {}
 					}
 
-					let now = Timestamp::now();
+					let now = clock.now();
+					if now.duration_since(last_position_saved) >= POSITION_SAVE_EVERY {
+						save_position(&updates).await;
+						last_position_saved = now;
+					}
 					if now.duration_since(last_status_update) > SignedDuration::from_secs(4 * 60) {
 						if !status_drop.status.is_empty() {
 							if let Err(e) = update_profile(&client, &status_drop.status).await {
@@ -251,10 +478,59 @@ async fn run_telegram_monitor(telegram_config: &TelegramConfig) -> Result<Infall
 				}
 			},
 		}
+	};
+	// So the reconnect (or next start) catches up from here rather than from the last periodic save
+	save_position(&updates).await;
+	result
+}
+
+/// How often the update position is saved while running; at most this much is replayed after a crash.
+const POSITION_SAVE_EVERY: SignedDuration = SignedDuration::from_secs(10);
+
+/// Save how far the update stream got to the session, for [`telegram_utils::connect`]'s catch-up on reconnect.
+async fn save_position(updates: &grammers_client::client::UpdateStream) {
+	if let Err(e) = updates.sync_update_state().await {
+		error!("Failed to save Telegram update position: {e}");
+	}
+}
+
+/// `(config key, destination)` for poll forwards, then for info forwards; unset ones fall back to `channel_output`.
+fn output_destinations(telegram_config: &TelegramConfig) -> [(&'static str, &TelegramDestination); 2] {
+	let fallback = ("channel_output", &telegram_config.channel_output);
+	[
+		telegram_config.poll_output.as_ref().map_or(fallback, |d| ("poll_output", d)),
+		telegram_config.info_output.as_ref().map_or(fallback, |d| ("info_output", d)),
+	]
+}
+
+/// Resolve the output destination configured under `telegram.<key>`. grammers forwards by peer, so it must be a username.
+async fn resolve_output(client: &Client, key: &str, destination: &TelegramDestination) -> Result<PeerRef, ChannelWatchError> {
+	let username = match destination {
+		TelegramDestination::Channel(tg::TopLevelId::AtName(name)) | TelegramDestination::Group(tg::TopLevelId::AtName(name)) => name.trim_start_matches('@'),
+		_ => {
+			return Err(ChannelWatchError::Recoverable(color_eyre::eyre::eyre!(
+				"telegram.{key} must be a username for grammers client forwarding"
+			)));
+		}
+	};
+
+	info!("Resolving {key}: {username}");
+	match client.resolve_username(username).await? {
+		Some(peer) => {
+			info!("{key} resolved: {}", peer.id().bot_api_dialog_id().expect("resolve_username never returns self"));
+			match peer.to_ref().await.map_err(|e| color_eyre::eyre::eyre!(e))? {
+				Some(r) => Ok(r),
+				None => Err(ChannelWatchError::Recoverable(color_eyre::eyre::eyre!("telegram.{key} peer has no access hash: {username}"))),
+			}
+		}
+		None => {
+			error!("Could not resolve telegram.{key}: {username}");
+			Err(ChannelWatchError::Recoverable(color_eyre::eyre::eyre!("Could not resolve telegram.{key}: {username}")))
+		}
 	}
 }
 
-async fn handle_poll_message(client: &Client, message: &grammers_client::update::Message, watch_chat: PeerRef) -> Result<()> {
+async fn handle_poll_message(client: &Client, message: &grammers_client::update::Message, watch_chat: PeerRef, recent: &RecentlyNotified) -> Result<()> {
 	if message.media().is_some() {
 		let source_ref = match message.peer_ref().await.map_err(|e| color_eyre::eyre::eyre!(e))? {
 			Some(r) => r,
@@ -264,31 +540,55 @@ async fn handle_poll_message(client: &Client, message: &grammers_client::update:
 			}
 		};
 		let source_name = message.peer().and_then(|p| p.name()).unwrap_or("unknown").to_string();
-		client.forward_messages(watch_chat, &[message.id()], source_ref).await?;
-		info!("Forwarded poll/media message from {source_name}");
+		if forward_once(client, message, watch_chat, source_ref, recent).await? {
+			info!("Forwarded poll/media message from {source_name}");
+		}
 	}
 	Ok(())
 }
 
-async fn handle_info_message(client: &Client, message: &grammers_client::update::Message, watch_chat: PeerRef) -> Result<()> {
-	let key_words = [
-		"самые торгуемые акции",
-		"отслеживание настроений",
-		"гугл тренд",
-		"google trends",
-		"поисковых запросов",
-		"популярные запросы",
-		"популярных запросов",
-		"кредитное плечо",
-		"закредитованность",
-		"количество уникальных слов",
-		"открытому интересу",
-		"открытый интерес",
-	];
-
-	let text = message.text();
-	let text_lower = text.to_lowercase();
-	if key_words.iter().any(|word| text_lower.contains(word)) {
+/// What gets an `info_channels` message forwarded: any of `info_keywords` (case-insensitive) or `info_regexes`.
+#[derive(Debug)]
+struct InfoFilter {
+	keywords: Vec<String>,
+	regexes: Vec<Regex>,
+}
+
+impl InfoFilter {
+	fn new(telegram_config: &TelegramConfig) -> Result<Self> {
+		let regexes = telegram_config
+			.info_regexes
+			.iter()
+			.map(|pattern| Regex::new(pattern).with_context(|| format!("Invalid telegram.info_regexes pattern: {pattern}")))
+			.collect::<Result<_>>()?;
+		Ok(Self {
+			keywords: telegram_config.info_keywords.iter().map(|k| k.to_lowercase()).collect(),
+			regexes,
+		})
+	}
+
+	fn matches(&self, text: &str) -> bool {
+		let text_lower = text.to_lowercase();
+		self.keywords.iter().any(|word| text_lower.contains(word)) || self.regexes.iter().any(|regex| regex.is_match(text))
+	}
+}
+
+async fn handle_info_message(
+	client: &Client,
+	message: &grammers_client::update::Message,
+	watch_chat: PeerRef,
+	recent: &RecentlyNotified,
+	filter: &InfoFilter,
+	trusted: Option<&TrustedSenders>,
+) -> Result<()> {
+	if filter.matches(message.text()) {
+		if !sender_authorized(client, message, trusted).await? {
+			debug!(
+				"Keyword match from untrusted sender in {}, not forwarding",
+				message.peer().and_then(|p| p.name()).unwrap_or("unknown")
+			);
+			return Ok(());
+		}
 		let source_ref = match message.peer_ref().await.map_err(|e| color_eyre::eyre::eyre!(e))? {
 			Some(r) => r,
 			None => {
@@ -297,12 +597,37 @@ async fn handle_info_message(client: &Client, message: &grammers_client::update:
 			}
 		};
 		let source_name = message.peer().and_then(|p| p.name()).unwrap_or("unknown").to_string();
-		client.forward_messages(watch_chat, &[message.id()], source_ref).await?;
-		info!("Forwarded info message from {source_name}");
+		if forward_once(client, message, watch_chat, source_ref, recent).await? {
+			info!("Forwarded info message from {source_name}");
+		}
 	}
 	Ok(())
 }
 
+/// Dedup key of a forwarded channel message, by the peer's Bot API id (kind embedded, so a channel and a chat with
+/// the same bare id differ). Persisted in `recent_notifications`, so it must stay stable across releases or restarts
+/// would forward a day's worth of messages again; hence not `PeerId`'s `Debug`.
+fn forward_item(peer_id: PeerId, message_id: i32) -> String {
+	// Only the logged-in user has no id, and it posts in no watched channel
+	let peer = peer_id.bot_api_dialog_id().map_or_else(|| "self".to_string(), |id| id.to_string());
+	format!("telegram_forward:{peer}:{message_id}")
+}
+
+/// Forward `message` to `watch_chat` unless it already was within the dedup window (e.g. redelivered after a
+/// restart). Returns whether it was forwarded.
+async fn forward_once(client: &Client, message: &grammers_client::update::Message, watch_chat: PeerRef, source_ref: PeerRef, recent: &RecentlyNotified) -> Result<bool> {
+	let item = forward_item(message.peer_id(), message.id());
+	if !recent.claim(&item).await {
+		debug!("Already forwarded {item} recently");
+		return Ok(false);
+	}
+	if let Err(e) = client.forward_messages(watch_chat, &[message.id()], source_ref).await {
+		recent.release(&item).await;
+		return Err(e.into());
+	}
+	Ok(true)
+}
+
 async fn update_profile(client: &Client, status: &str) -> Result<()> {
 	use grammers_tl_types::functions;
 
@@ -316,3 +641,145 @@ async fn update_profile(client: &Client, status: &str) -> Result<()> {
 
 	Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::telegram_dms::WatchAccount;
+
+	fn channels(names: &[&str]) -> Vec<String> {
+		names.iter().map(|n| format!("https://t.me/{n}")).collect()
+	}
+
+	fn account(username: &str, poll: &[&str]) -> WatchAccount {
+		WatchAccount {
+			username: username.to_string(),
+			phone: "+100".to_string(),
+			poll_channels: channels(poll),
+			info_channels: Vec::new(),
+		}
+	}
+
+	#[test]
+	fn test_forward_item() {
+		let channel = PeerId::channel(1_234_567).unwrap();
+		insta::assert_snapshot!(forward_item(channel, 42), @"telegram_forward:-1000001234567:42");
+		assert_ne!(forward_item(channel, 42), forward_item(PeerId::chat(1_234_567).unwrap(), 42));
+	}
+
+	#[test]
+	fn test_output_destinations() {
+		let destination = |name: &str| serde_json::from_str::<TelegramDestination>(&format!("\"{name}\"")).unwrap();
+		let mut config = TelegramConfig {
+			channel_output: destination("WatchingTT"),
+			..Default::default()
+		};
+		let [poll, info] = output_destinations(&config);
+		assert_eq!((poll.0, info.0), ("channel_output", "channel_output"));
+		assert_eq!(poll.1, &destination("WatchingTT"));
+
+		config.info_output = Some(destination("WatchingTTInfo"));
+		let [poll, info] = output_destinations(&config);
+		assert_eq!((poll, info), (("channel_output", &destination("WatchingTT")), ("info_output", &destination("WatchingTTInfo"))));
+
+		// `--output-channel` redirects both, whatever is configured
+		let watch = TelegramChannelWatch::new(config).output_channel(Some(destination("@test")));
+		let [poll, info] = output_destinations(&watch.telegram_config);
+		assert_eq!((poll, info), (("channel_output", &destination("@test")), ("channel_output", &destination("@test"))));
+	}
+
+	#[test]
+	fn test_info_filter() {
+		let mut config = TelegramConfig {
+			info_keywords: vec!["Открытый интерес".to_string(), "google trends".to_string()],
+			info_regexes: vec![r"(?i)funding\s+rates?".to_string(), r"\bBTC\b".to_string()],
+			..Default::default()
+		};
+		let filter = InfoFilter::new(&config).unwrap();
+		assert!(filter.matches("ОТКРЫТЫЙ ИНТЕРЕС вырос"));
+		assert!(filter.matches("Google Trends: bitcoin"));
+		assert!(filter.matches("Funding rate flipped"));
+		assert!(filter.matches("BTC dominance"));
+		// Regexes stay case-sensitive without `(?i)`
+		assert!(!filter.matches("btc dominance"));
+		assert!(!filter.matches("погода"));
+
+		config.info_regexes = vec!["(unclosed".to_string()];
+		assert!(InfoFilter::new(&config).unwrap_err().to_string().contains("(unclosed"));
+	}
+
+	#[test]
+	fn test_check_sender() {
+		let trusted = TrustedSenders {
+			admins: false,
+			allow: vec!["@Analyst".to_string(), "777".to_string()],
+		};
+		let member = |id, username, is_user| Sender::Member { id, username, is_user };
+
+		assert_eq!(check_sender(None, &member(1, Some("rando"), true)), SenderCheck::Allowed);
+		assert_eq!(check_sender(Some(&trusted), &Sender::Broadcast), SenderCheck::Allowed);
+		assert_eq!(check_sender(Some(&trusted), &member(1, Some("analyst"), true)), SenderCheck::Allowed);
+		assert_eq!(check_sender(Some(&trusted), &member(777, None, true)), SenderCheck::Allowed);
+		assert_eq!(check_sender(Some(&trusted), &member(1, Some("rando"), true)), SenderCheck::Denied);
+		assert_eq!(check_sender(Some(&trusted), &Sender::AsGroup), SenderCheck::Denied);
+		assert_eq!(check_sender(Some(&trusted), &Sender::Unknown), SenderCheck::Denied);
+
+		let with_admins = TrustedSenders { admins: true, ..trusted };
+		assert_eq!(check_sender(Some(&with_admins), &member(1, Some("rando"), true)), SenderCheck::IfAdmin);
+		assert_eq!(check_sender(Some(&with_admins), &member(1, Some("analyst"), true)), SenderCheck::Allowed);
+		assert_eq!(check_sender(Some(&with_admins), &Sender::AsGroup), SenderCheck::Allowed);
+		// A channel posting in the group can't be an admin; only the allow-list lets it through
+		assert_eq!(check_sender(Some(&with_admins), &member(5, Some("somechannel"), false)), SenderCheck::Denied);
+		assert_eq!(check_sender(Some(&with_admins), &Sender::Unknown), SenderCheck::Denied);
+	}
+
+	#[test]
+	fn test_watch_sessions() {
+		let mut config = TelegramConfig {
+			username: "@main".to_string(),
+			phone: "+1".to_string(),
+			poll_channels: channels(&["public_a", "public_b"]),
+			info_channels: channels(&["news"]),
+			..Default::default()
+		};
+		let sessions = watch_sessions(&config).unwrap();
+		assert_eq!(sessions.len(), 1);
+		assert_eq!((sessions[0].username, sessions[0].session_suffix, sessions[0].updates_status), ("@main", "", true));
+
+		config.watch_accounts = vec![account("@alt", &["private_c"])];
+		let sessions = watch_sessions(&config).unwrap();
+		let summary: Vec<_> = sessions
+			.iter()
+			.map(|s| (s.username, s.phone, s.session_suffix, s.poll_channels.len(), s.info_channels.len(), s.updates_status))
+			.collect();
+		assert_eq!(summary, [("@main", "+1", "", 2, 1, true), ("@alt", "+100", "_watch", 1, 0, false)]);
+		assert_eq!(
+			sessions.iter().map(WatchSession::status_name).collect::<Vec<_>>(),
+			["telegram_channel_watch", "telegram_channel_watch:alt"]
+		);
+
+		config.watch_accounts = vec![account("@alt", &["private_c"]), account("alt", &[])];
+		assert!(watch_sessions(&config).unwrap_err().to_string().contains("listed more than once"));
+
+		config.watch_accounts = vec![account("@alt", &["public_b"])];
+		assert!(watch_sessions(&config).unwrap_err().to_string().contains("https://t.me/public_b"));
+	}
+
+	#[test]
+	fn test_excluded_channels_not_watched() {
+		let config = TelegramConfig {
+			username: "@main".to_string(),
+			poll_channels: channels(&["public_a", "Public_B", "public_c"]),
+			info_channels: channels(&["news"]),
+			excluded_channels: vec!["@public_b".to_string(), "https://t.me/news".to_string()],
+			watch_accounts: vec![account("@alt", &["private_c", "public_b_fork"])],
+			..Default::default()
+		};
+		let sessions = watch_sessions(&config).unwrap();
+		let watched = |s: &WatchSession, channels| s.watched(channels).map(|c| channel_username(c).to_string()).collect::<Vec<_>>();
+		assert_eq!(watched(&sessions[0], sessions[0].poll_channels), ["public_a", "public_c"]);
+		assert!(watched(&sessions[0], sessions[0].info_channels).is_empty());
+		// Exclusion matches whole usernames only
+		assert_eq!(watched(&sessions[1], sessions[1].poll_channels), ["private_c", "public_b_fork"]);
+	}
+}
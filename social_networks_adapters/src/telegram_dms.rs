@@ -1,10 +1,12 @@
-use std::convert::Infallible;
-
 use color_eyre::eyre::Result;
 use futures::future::{Either, select};
 use grammers_client::update::Update;
 use grammers_tl_types as tl;
-use social_networks_utils::telegram_utils::{self, ConnectionConfig, TelegramConnection};
+use social_networks_utils::{
+	status,
+	telegram_utils::{self, ConnectionConfig, TelegramConnection},
+	utils::ActiveHours,
+};
 pub use tg::TelegramDestination;
 use tokio::{
 	sync::mpsc::UnboundedSender,
@@ -16,6 +18,8 @@ use v_utils::macros::MyConfigPrimitives;
 use crate::{
 	client::{AdapterError, Client as AdapterClient},
 	dm_event::DmEvent,
+	telegram_channel_watch::TrustedSenders,
+	telegram_notifier::{LinkPreviews, NotificationTemplates, SendRate, Severity},
 };
 
 const SURFACE: &str = "telegram_dms";
@@ -35,6 +39,70 @@ pub struct TelegramConfig {
 	pub poll_channels: Vec<String>,
 	#[primitives(skip)]
 	pub info_channels: Vec<String>,
+	/// Channels of any watch account's `poll_channels`/`info_channels` to skip for now, without losing their place
+	#[serde(default)]
+	#[primitives(skip)]
+	pub excluded_channels: Vec<String>,
+	/// Only forward poll/info channel messages inside this window (`telegram-channel-watch`).
+	#[primitives(skip)]
+	pub watch_active_hours: Option<ActiveHours>,
+	/// Where `telegram-channel-watch` forwards `poll_channels` messages (default: `channel_output`). Must be a username.
+	#[serde(default)]
+	#[primitives(skip)]
+	pub poll_output: Option<TelegramDestination>,
+	/// Where `telegram-channel-watch` forwards `info_channels` matches (default: `channel_output`). Must be a username.
+	#[serde(default)]
+	#[primitives(skip)]
+	pub info_output: Option<TelegramDestination>,
+	/// Only forward `info_channels` keyword matches in groups from these senders; anyone can trigger one if unset.
+	/// Broadcast channels are unaffected, since only their admins post there.
+	#[serde(default)]
+	#[primitives(skip)]
+	pub info_senders: Option<TrustedSenders>,
+	/// Case-insensitive phrases that get an `info_channels` message forwarded. Defaults to the Russian market-sentiment
+	/// ones (most traded stocks, Google Trends, leverage, open interest, ...).
+	#[serde(default = "__default_info_keywords")]
+	#[primitives(skip)]
+	pub info_keywords: Vec<String>,
+	/// Regexes that get an `info_channels` message forwarded, alongside `info_keywords`. Case-sensitive unless `(?i)`.
+	#[serde(default)]
+	#[primitives(skip)]
+	pub info_regexes: Vec<String>,
+	/// Per notification kind link previews; all on except `email` by default.
+	#[serde(default)]
+	#[primitives(skip)]
+	pub link_previews: LinkPreviews,
+	/// Custom formats for email, YouTube and Twitter poll notifications; unset ones keep the built-in format
+	#[serde(default)]
+	#[primitives(skip)]
+	pub templates: NotificationTemplates,
+	/// How fast notifications go out, overall and per chat.
+	#[serde(default)]
+	#[primitives(skip)]
+	pub send_rate: SendRate,
+	/// Drop notifications less important than this (`low`, `normal` or `high`); `low` sends everything
+	#[serde(default)]
+	#[primitives(skip)]
+	pub min_severity: Severity,
+	/// More accounts for `telegram-channel-watch`, for channels the main one can't see. Each watches only its own
+	/// channels, alongside the main account's `poll_channels`/`info_channels`.
+	#[serde(default)]
+	#[primitives(skip)]
+	pub watch_accounts: Vec<WatchAccount>,
+}
+
+/// An extra `telegram-channel-watch` login. Shares `api_id`/`api_hash` and `channel_output` with the main account,
+/// so it must be able to post to the output channel.
+#[derive(Clone, Debug, MyConfigPrimitives)]
+pub struct WatchAccount {
+	pub username: String,
+	pub phone: String,
+	#[serde(default)]
+	#[primitives(skip)]
+	pub poll_channels: Vec<String>,
+	#[serde(default)]
+	#[primitives(skip)]
+	pub info_channels: Vec<String>,
 }
 
 pub struct TelegramDms {
@@ -54,6 +122,7 @@ impl TelegramDms {
 			api_id: self.telegram_config.api_id,
 			api_hash: &self.telegram_config.api_hash,
 			session_suffix: "_dm",
+			catch_up: false,
 		})
 		.await
 	}
@@ -74,6 +143,7 @@ impl TelegramDms {
 
 		info!("--Telegram DM Commands-- connected and authorized");
 		println!("Telegram DM Commands: Connected");
+		status::connected(SURFACE);
 
 		let _ = client; // hold for the lifetime of the session
 		let mut updates = Box::new(updates);
@@ -114,7 +184,10 @@ impl TelegramDms {
 						error!("Error getting next update: {s}, reconnecting...");
 						return Ok(());
 					}
-					Ok(update) => self.handle_update(update),
+					Ok(update) => {
+						status::event(SURFACE);
+						self.handle_update(update);
+					}
 				},
 			}
 		}
@@ -143,6 +216,8 @@ impl TelegramDms {
 					is_dm: true,
 					mentions_me: false,
 					is_reply_to_me: false,
+					attachments: Vec::new(),
+					embeds: Vec::new(),
 				});
 			}
 			// Incoming voice/video call: server sends `phoneCallRequested` to the callee.
@@ -164,9 +239,10 @@ impl AdapterClient for TelegramDms {
 		SURFACE
 	}
 
-	async fn listen(&mut self) -> Result<Infallible, AdapterError> {
+	async fn listen(&mut self) -> Result<(), AdapterError> {
 		loop {
 			self.run_session().await?;
+			status::reconnecting(SURFACE);
 			error!("Telegram DMs reconnecting in 30s...");
 			time::sleep(Duration::from_secs(30)).await;
 		}
@@ -198,3 +274,22 @@ pub(crate) fn classify_invocation_auth(s: &str) -> bool {
 		|| lc.contains("api_id_invalid")
 		|| lc.contains("phone_number_banned")
 }
+
+fn __default_info_keywords() -> Vec<String> {
+	[
+		"самые торгуемые акции",
+		"отслеживание настроений",
+		"гугл тренд",
+		"google trends",
+		"поисковых запросов",
+		"популярные запросы",
+		"популярных запросов",
+		"кредитное плечо",
+		"закредитованность",
+		"количество уникальных слов",
+		"открытому интересу",
+		"открытый интерес",
+	]
+	.map(String::from)
+	.to_vec()
+}
@@ -1,71 +1,1333 @@
-use color_eyre::eyre::{Result, bail};
+use std::{
+	collections::{HashMap, VecDeque},
+	sync::Mutex,
+	time::Duration,
+};
+
+use color_eyre::eyre::{Context, Result, bail, eyre};
 use reqwest::Client;
-use tracing::instrument;
+use serde::{Deserialize, Serialize};
+use social_networks_utils::{db::total_llm_spend, dedup::RecentlyNotified, http, template};
+use tokio::{
+	sync::{mpsc, oneshot},
+	time::{self, Instant},
+};
+use tracing::{info, instrument, warn};
 
 use crate::telegram_dms::{TelegramConfig, TelegramDestination};
 
+/// Longest `text` the Bot API accepts in one `sendMessage`, in UTF-16 code units (see [`utf16_len`]).
+const MESSAGE_LIMIT: usize = 4096;
+/// Longest photo caption the Bot API accepts, in UTF-16 code units like [`MESSAGE_LIMIT`].
+const CAPTION_LIMIT: usize = 1024;
+/// Retries of a `sendMessage` rate-limited with a 429, each after the `retry_after` Telegram asks for.
+const RATE_LIMIT_RETRIES: u32 = 3;
+/// A 429 asking to wait longer than this fails right away rather than holding the message back.
+const MAX_RETRY_AFTER: Duration = Duration::from_secs(60);
+
 #[derive(Clone, Debug)]
 pub struct TelegramNotifier {
 	config: TelegramConfig,
 	client: Client,
+	recent: RecentlyNotified,
+}
+
+/// The send worker of the process: every notifier queues its messages to it, so they are all paced together by the
+/// [`SendRate`] of the first one to send. Replaced if its runtime went away.
+static OUTBOX: Mutex<Option<mpsc::UnboundedSender<Outgoing>>> = Mutex::new(None);
+
+fn outbox(rate: &SendRate) -> mpsc::UnboundedSender<Outgoing> {
+	let mut outbox = OUTBOX.lock().unwrap();
+	match &*outbox {
+		Some(sender) if !sender.is_closed() => sender.clone(),
+		_ => {
+			let (sender, queued) = mpsc::unbounded_channel();
+			let client = http::client();
+			tokio::spawn(drain_outbox(rate.clone(), queued, async move |outgoing: &Outgoing| {
+				post_message(&client, &outgoing.bot_token, outgoing.method, &outgoing.params).await
+			}));
+			*outbox = Some(sender.clone());
+			sender
+		}
+	}
+}
+
+#[derive(Debug)]
+struct Outgoing {
+	bot_token: String,
+	method: &'static str,
+	params: Vec<(String, String)>,
+	sent: oneshot::Sender<Result<SentMessage>>,
+	/// 429s answered so far, up to [`RATE_LIMIT_RETRIES`]
+	rate_limited: u32,
+}
+
+/// Where a sent message landed, as reported back by the Bot API. Lets callers map later
+/// updates (reactions, replies) back to what they sent.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct SentMessage {
+	pub chat_id: i64,
+	pub message_id: i64,
+}
+
+/// What a message notifies about; picks per-kind send options such as [`LinkPreviews`].
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum NotificationKind {
+	/// Pings, calls and monitored-user messages
+	Dm,
+	Tweet,
+	/// New and closed Twitter polls
+	TwitterPoll,
+	/// Uploads and title keyword alerts
+	Youtube,
+	Email,
+}
+
+/// How much a notification matters. Those below `telegram.min_severity` are dropped, e.g. to keep only the important
+/// ones through a busy stretch.
+#[derive(Clone, Copy, Debug, Default, Deserialize, Eq, Hash, Ord, PartialEq, PartialOrd, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Severity {
+	/// YouTube uploads, closed Twitter polls. The default `min_severity`, so nothing is dropped unless configured.
+	#[default]
+	Low,
+	/// Monitored-user messages, tweets, new Twitter polls, emails
+	Normal,
+	/// Pings, calls, YouTube title keyword matches, the LLM budget alert
+	High,
+}
+
+/// Whether Telegram shows a link preview under each kind of notification (`telegram.link_previews`).
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(default)]
+pub struct LinkPreviews {
+	pub dms: bool,
+	pub tweets: bool,
+	pub twitter_polls: bool,
+	pub youtube: bool,
+	pub email: bool,
+}
+impl Default for LinkPreviews {
+	/// Everything previews except forwarded emails, whose links are arbitrary (trackers, unsubscribe pages).
+	fn default() -> Self {
+		Self {
+			dms: true,
+			tweets: true,
+			twitter_polls: true,
+			youtube: true,
+			email: false,
+		}
+	}
+}
+impl LinkPreviews {
+	pub fn enabled(&self, kind: NotificationKind) -> bool {
+		match kind {
+			NotificationKind::Dm => self.dms,
+			NotificationKind::Tweet => self.tweets,
+			NotificationKind::TwitterPoll => self.twitter_polls,
+			NotificationKind::Youtube => self.youtube,
+			NotificationKind::Email => self.email,
+		}
+	}
+}
+
+/// How Telegram renders a message's text (`parse_mode`). Anything user-supplied going into a formatted message must
+/// pass through [`ParseMode::escape`], or a stray `<` or `_` gets the whole message rejected.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum ParseMode {
+	#[default]
+	Plain,
+	Html,
+	MarkdownV2,
+}
+impl ParseMode {
+	fn field(self) -> Option<&'static str> {
+		match self {
+			ParseMode::Plain => None,
+			ParseMode::Html => Some("HTML"),
+			ParseMode::MarkdownV2 => Some("MarkdownV2"),
+		}
+	}
+
+	/// `text` made to show literally in this mode.
+	pub fn escape(self, text: &str) -> String {
+		match self {
+			ParseMode::Plain => text.to_string(),
+			ParseMode::Html => text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;"),
+			ParseMode::MarkdownV2 => {
+				let mut escaped = String::with_capacity(text.len());
+				for c in text.chars() {
+					if "_*[]()~`>#+-=|{}.!\\".contains(c) {
+						escaped.push('\\');
+					}
+					escaped.push(c);
+				}
+				escaped
+			}
+		}
+	}
+
+	/// `label` linking to `url`, both escaped.
+	pub fn link(self, label: &str, url: &str) -> String {
+		match self {
+			ParseMode::Plain => format!("{label} ({url})"),
+			ParseMode::Html => format!("<a href=\"{}\">{}</a>", ParseMode::Html.escape(url), ParseMode::Html.escape(label)),
+			// Inside the URL part only `)` and `\` need escaping
+			ParseMode::MarkdownV2 => format!("[{}]({})", self.escape(label), url.replace('\\', "\\\\").replace(')', "\\)")),
+		}
+	}
+}
+
+/// Notification formats (`telegram.templates`), replacing the built-in ones. `${var}` placeholders as in tweet
+/// templates, plus `${var?...}` sections shown only when `var` is non-empty, e.g. `${reply_to?Reply-To: ${reply_to}\n}`.
+/// Values are escaped for the message's parse mode (HTML for `youtube` and `twitter_poll`); the rest is sent as written.
+#[derive(Clone, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(default)]
+pub struct NotificationTemplates {
+	/// Forwarded emails. Variables: `from`, `history`, `subject`, `summary`, `body`, `reply_to`
+	pub email: Option<String>,
+	/// New videos and streams. Variables: `channel`, `headline`, `title`, `sentiment`, `url`
+	pub youtube: Option<String>,
+	/// New polls from monitored lists. Variables: `author`, `text`, `url`
+	pub twitter_poll: Option<String>,
+}
+impl NotificationTemplates {
+	/// Reject malformed templates and unknown variables, before anything gets sent with them.
+	pub fn validate(&self) -> Result<()> {
+		TemplateKind::ALL.into_iter().try_for_each(|kind| self.checked(kind).map(|_| ()))
+	}
+
+	fn checked(&self, kind: TemplateKind) -> Result<&str> {
+		let text = match kind {
+			TemplateKind::Email => self.email.as_deref(),
+			TemplateKind::Youtube => self.youtube.as_deref(),
+			TemplateKind::TwitterPoll => self.twitter_poll.as_deref(),
+		}
+		.unwrap_or(kind.builtin());
+		let key = kind.key();
+		for name in template::variable_names(text).wrap_err_with(|| format!("telegram.templates.{key}"))? {
+			if !kind.variables().contains(&name.as_str()) {
+				bail!("telegram.templates.{key} uses unknown variable `${{{name}}}`, expected one of: {}", kind.variables().join(", "));
+			}
+		}
+		Ok(text)
+	}
+
+	/// `kind`'s template with `values` escaped and filled in; an empty value leaves out the sections on it.
+	fn render(&self, kind: TemplateKind, values: &[(&str, &str)]) -> Result<String> {
+		let mode = kind.mode();
+		let variables = values.iter().map(|(name, value)| (name.to_string(), mode.escape(value))).collect();
+		template::render(self.checked(kind)?, &variables)
+	}
+}
+
+#[derive(Clone, Copy, Debug)]
+enum TemplateKind {
+	Email,
+	Youtube,
+	TwitterPoll,
+}
+impl TemplateKind {
+	const ALL: [Self; 3] = [Self::Email, Self::Youtube, Self::TwitterPoll];
+
+	fn key(self) -> &'static str {
+		match self {
+			Self::Email => "email",
+			Self::Youtube => "youtube",
+			Self::TwitterPoll => "twitter_poll",
+		}
+	}
+
+	fn builtin(self) -> &'static str {
+		match self {
+			Self::Email => "📧 New Email\n\nFrom: ${from}${history? (${history})}\nSubject: ${subject}\n\n${summary?💡 ${summary}\n\n}${body}",
+			Self::Youtube => "[${channel}] ${headline}: <a href=\"${url}\">${title}</a>\nPerception: ${sentiment}",
+			Self::TwitterPoll => "<a href=\"${url}\">Twitter poll</a> from ${author}:\n${text}",
+		}
+	}
+
+	fn variables(self) -> &'static [&'static str] {
+		match self {
+			Self::Email => &["from", "history", "subject", "summary", "body", "reply_to"],
+			Self::Youtube => &["channel", "headline", "title", "sentiment", "url"],
+			Self::TwitterPoll => &["author", "text", "url"],
+		}
+	}
+
+	fn mode(self) -> ParseMode {
+		match self {
+			Self::Email => ParseMode::Plain,
+			Self::Youtube | Self::TwitterPoll => ParseMode::Html,
+		}
+	}
+}
+
+/// A forwarded email's fields, as `telegram.templates.email` sees them; empty ones are left out of its sections.
+#[derive(Debug, Default)]
+pub struct EmailNotification<'a> {
+	pub from: &'a str,
+	/// How often the sender wrote before, e.g. "seen once before"
+	pub history: &'a str,
+	pub subject: &'a str,
+	/// LLM summary, when `email.summarize_forwarded` is on
+	pub summary: &'a str,
+	pub body: &'a str,
+	pub reply_to: &'a str,
+}
+
+/// How fast the notifier sends (`telegram.send_rate`). Telegram starts answering 429 past about 30 messages a second per
+/// bot, and 20 a minute into one group or channel. 0 turns a limit off.
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(default)]
+pub struct SendRate {
+	/// Across all chats
+	pub per_second: u32,
+	/// Into any single chat
+	pub per_chat_per_minute: u32,
+}
+impl Default for SendRate {
+	fn default() -> Self {
+		Self {
+			per_second: 25,
+			per_chat_per_minute: 20,
+		}
+	}
+}
+
+/// Messages waiting to go out, queued per chat and spaced by [`SendRate`]. A chat waiting out its own gap doesn't hold
+/// up the others; each send only pushes back the next one overall by the overall gap.
+#[derive(Debug)]
+struct SendQueue<T> {
+	rate: SendRate,
+	/// Oldest first, each with its place in the overall order
+	queued: HashMap<String, VecDeque<(u64, T)>>,
+	pushed: u64,
+	next_send: Option<Instant>,
+	next_per_chat: HashMap<String, Instant>,
+}
+
+impl<T> SendQueue<T> {
+	fn new(rate: SendRate) -> Self {
+		Self {
+			rate,
+			queued: HashMap::new(),
+			pushed: 0,
+			next_send: None,
+			next_per_chat: HashMap::new(),
+		}
+	}
+
+	fn push(&mut self, chat: &str, item: T) {
+		self.queued.entry(chat.to_string()).or_default().push_back((self.pushed, item));
+		self.pushed += 1;
+	}
+
+	/// When the next send may go out at `now`, and into which chat: the one that can go soonest, the one queued longest
+	/// among those.
+	fn next(&self, now: Instant) -> Option<(Instant, &str)> {
+		self.queued
+			.iter()
+			.filter_map(|(chat, queue)| {
+				let (order, _) = queue.front()?;
+				let at = [Some(now), self.next_send, self.next_per_chat.get(chat).copied()].into_iter().flatten().max()?;
+				Some((at, *order, chat.as_str()))
+			})
+			.min()
+			.map(|(at, _, chat)| (at, chat))
+	}
+
+	/// Take the oldest message queued for `chat`, booking its send for `at`. Returns it with its place in the order, for
+	/// [`Self::retry_at`].
+	fn take(&mut self, chat: &str, at: Instant) -> Option<(u64, T)> {
+		let queue = self.queued.get_mut(chat)?;
+		let (order, item) = queue.pop_front()?;
+		if queue.is_empty() {
+			self.queued.remove(chat);
+		}
+		let gap = |count: u32, period: Duration| if count == 0 { Duration::ZERO } else { period / count };
+		self.next_send = Some(at + gap(self.rate.per_second, Duration::from_secs(1)));
+		self.next_per_chat.retain(|_, next| *next > at);
+		self.next_per_chat.insert(chat.to_string(), at + gap(self.rate.per_chat_per_minute, Duration::from_secs(60)));
+		Some((order, item))
+	}
+
+	/// Put a message [`Self::take`]n from `chat` back at the front of its queue, with nothing more going into `chat`
+	/// before `at`. The other chats carry on meanwhile.
+	fn retry_at(&mut self, chat: &str, order: u64, item: T, at: Instant) {
+		self.queued.entry(chat.to_string()).or_default().push_front((order, item));
+		let next = self.next_per_chat.entry(chat.to_string()).or_insert(at);
+		*next = (*next).max(at);
+	}
+}
+
+/// Send queued messages with `post` as the [`SendQueue`] allows, each as soon as both its chat's and the overall limit
+/// let it. A rate-limited message waits out its `retry_after` back in its chat's queue, not in the worker.
+async fn drain_outbox(rate: SendRate, mut incoming: mpsc::UnboundedReceiver<Outgoing>, post: impl AsyncFn(&Outgoing) -> Result<Result<SentMessage, RateLimited>>) {
+	let mut queue = SendQueue::new(rate);
+	loop {
+		let due = queue.next(Instant::now()).map(|(at, _)| at);
+		let wait = async {
+			match due {
+				Some(at) => time::sleep_until(at).await,
+				None => std::future::pending().await,
+			}
+		};
+		tokio::select! {
+			outgoing = incoming.recv() => {
+				let Some(outgoing) = outgoing else { return };
+				let chat = outgoing.params.iter().find(|(key, _)| key == "chat_id").map_or(String::new(), |(_, chat)| chat.clone());
+				queue.push(&chat, outgoing);
+			}
+			() = wait => {
+				let now = Instant::now();
+				let Some((_, chat)) = queue.next(now) else { continue };
+				let chat = chat.to_string();
+				let (order, mut outgoing) = queue.take(&chat, now).expect("`next` picks a chat with a message queued");
+				let result = match post(&outgoing).await {
+					Ok(Ok(sent)) => Ok(sent),
+					Ok(Err(limited)) if outgoing.rate_limited < RATE_LIMIT_RETRIES && limited.retry_after <= MAX_RETRY_AFTER => {
+						outgoing.rate_limited += 1;
+						warn!(
+							"Telegram rate-limited {} to {chat}, retrying in {}s ({}/{RATE_LIMIT_RETRIES})",
+							outgoing.method,
+							limited.retry_after.as_secs(),
+							outgoing.rate_limited
+						);
+						queue.retry_at(&chat, order, outgoing, Instant::now() + limited.retry_after);
+						continue;
+					}
+					Ok(Err(limited)) => Err(eyre!("Failed to send Telegram message: {}", limited.description)),
+					Err(e) => Err(e),
+				};
+				let _ = outgoing.sent.send(result);
+			}
+		}
+	}
+}
+
+/// A 429 from the Bot API.
+#[derive(Debug)]
+struct RateLimited {
+	/// How long Telegram asked to wait
+	retry_after: Duration,
+	description: String,
+}
+
+/// Call a Bot API `method` that sends a message, once. A 429 is `Ok(Err(_))`, left for the caller to retry.
+async fn post_message(client: &Client, bot_token: &str, method: &str, params: &[(String, String)]) -> Result<Result<SentMessage, RateLimited>> {
+	let url = format!("https://api.telegram.org/bot{bot_token}/{method}");
+	tracing::debug!(?params);
+
+	let response = client.post(&url).form(params).send().await?;
+	tracing::debug!(?response);
+	if !response.status().is_success() {
+		let status = response.status();
+		let error_text = response.text().await?;
+		match rate_limit_wait(status, &error_text) {
+			Some(retry_after) =>
+				return Ok(Err(RateLimited {
+					retry_after,
+					description: error_text,
+				})),
+			None => bail!("Failed to send Telegram message: {error_text}"),
+		}
+	}
+
+	let sent: BotApiResponse<BotApiMessage> = response.json().await?;
+	Ok(Ok(SentMessage {
+		chat_id: sent.result.chat.id,
+		message_id: sent.result.message_id,
+	}))
+}
+
+/// Username of the bot `bot_token` belongs to, via `getMe`. Fails with the Bot API's description of the problem for a
+/// revoked or mistyped token.
+/// A refused token fails with the HTTP status error at the root of the chain, so it can be told from an outage.
+pub async fn bot_username(bot_token: &str) -> Result<String> {
+	// The token is part of the URL, so keep it out of the error
+	let response = http::client()
+		.get(format!("https://api.telegram.org/bot{bot_token}/getMe"))
+		.send()
+		.await
+		.map_err(reqwest::Error::without_url)?;
+	let rejected = response.error_for_status_ref().err().map(reqwest::Error::without_url);
+	let parsed = parse_get_me(&response.text().await.map_err(reqwest::Error::without_url)?);
+	match rejected {
+		Some(status) => Err(status).wrap_err(parsed.err().map_or_else(|| "getMe failed".to_string(), |e| e.to_string())),
+		None => parsed,
+	}
+}
+
+fn parse_get_me(body: &str) -> Result<String> {
+	if let Ok(me) = serde_json::from_str::<BotApiResponse<BotApiUser>>(body) {
+		return Ok(format!("@{}", me.result.username));
+	}
+	match serde_json::from_str::<BotApiError>(body) {
+		Ok(BotApiError { description: Some(description), .. }) => bail!("{description}"),
+		_ => bail!("Unexpected getMe response: {body}"),
+	}
+}
+
+/// A reaction added to one of the bot's messages, flattened from either a
+/// `message_reaction` (groups, named reactor) or `message_reaction_count` (channels, anonymous) update.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Reaction {
+	pub chat_id: i64,
+	pub message_id: i64,
+	pub emoji: String,
 }
 
 impl TelegramNotifier {
 	pub fn new(config: TelegramConfig) -> Self {
-		Self { config, client: Client::new() }
+		Self {
+			config,
+			client: http::client(),
+			recent: RecentlyNotified::default(),
+		}
 	}
 
-	pub async fn send_ping_notification(&self, username: &str, platform: &str) -> Result<()> {
+	pub async fn send_ping_notification(&self, username: &str, platform: &str) -> Result<Option<SentMessage>> {
 		let text = format!("/Ping from: @{username}, {platform}");
-		self.send_message_to_alerts(&text).await
+		self.notify(Severity::High, NotificationKind::Dm, &text, ParseMode::Plain, &self.config.channel_alerts).await
 	}
 
-	pub async fn send_call_notification(&self, platform: &str) -> Result<()> {
+	pub async fn send_call_notification(&self, platform: &str) -> Result<Option<SentMessage>> {
 		let text = format!("Incoming call on {platform}");
-		self.send_message_to_alerts(&text).await
+		self.notify(Severity::High, NotificationKind::Dm, &text, ParseMode::Plain, &self.config.channel_alerts).await
+	}
+
+	pub async fn send_monitored_user_message(&self, username: &str, platform: &str, attachments: &[String], embeds: &[String]) -> Result<Option<SentMessage>> {
+		let text = monitored_user_text(username, platform, attachments, embeds);
+		self.notify(Severity::Normal, NotificationKind::Dm, &text, ParseMode::Plain, &self.config.channel_alerts).await
 	}
 
-	pub async fn send_monitored_user_message(&self, username: &str, platform: &str) -> Result<()> {
-		let text = format!("New message from @{username}, {platform}");
-		self.send_message_to_alerts(&text).await
+	pub async fn send_twitter_poll(&self, author: &str, text: &str, tweet_id: &str, destination: &TelegramDestination) -> Result<Option<SentMessage>> {
+		let message = twitter_poll_text(&self.config.templates, author, text, tweet_id)?;
+		self.send_item(
+			Severity::Normal,
+			NotificationKind::TwitterPoll,
+			&format!("twitter_poll:{tweet_id}"),
+			&message,
+			ParseMode::Html,
+			None,
+			destination,
+		)
+		.await
 	}
 
-	pub async fn send_twitter_poll(&self, author: &str, text: &str, tweet_id: &str) -> Result<()> {
-		let message = format!("Twitter poll from {author}:\n{text}\n\nhttps://twitter.com/twitter/statuses/{tweet_id}");
-		self.send_message_to_output(&message).await
+	/// `results` is the final tally, as rendered by the Twitter monitor.
+	pub async fn send_twitter_poll_closed(&self, author: &str, text: &str, results: &str, tweet_id: &str, destination: &TelegramDestination) -> Result<Option<SentMessage>> {
+		let message = format!("Twitter poll from {author} closed:\n{text}\n\n{results}\n\nhttps://twitter.com/twitter/statuses/{tweet_id}");
+		self.send_item(
+			Severity::Low,
+			NotificationKind::TwitterPoll,
+			&format!("twitter_poll_closed:{tweet_id}"),
+			&message,
+			ParseMode::Plain,
+			None,
+			destination,
+		)
+		.await
 	}
 
-	pub async fn send_youtube_notification(&self, channel_name: &str, title: &str, sentiment: &str, video_id: &str) -> Result<()> {
-		let message = format!("[{channel_name}] uploaded a new video: [{title}]\nPerception: {sentiment}\n\nhttps://youtube.com/watch?v={video_id}");
-		self.send_message_to_output(&message).await
+	pub async fn send_tweet(&self, author: &str, text: &str, tweet_id: &str, destination: &TelegramDestination) -> Result<Option<SentMessage>> {
+		let message = format!("Tweet from {author}:\n{text}\n\nhttps://twitter.com/twitter/statuses/{tweet_id}");
+		self.send_item(
+			Severity::Normal,
+			NotificationKind::Tweet,
+			&format!("tweet:{tweet_id}"),
+			&message,
+			ParseMode::Plain,
+			None,
+			destination,
+		)
+		.await
 	}
 
-	#[instrument(skip_all)]
-	pub async fn send_message_to_alerts(&self, text: &str) -> Result<()> {
-		self.send_message(text, &self.config.channel_alerts).await
+	/// `headline` says what happened, e.g. "uploaded a new video" or "🔴 LIVE".
+	pub async fn send_youtube_notification(&self, channel_name: &str, headline: &str, title: &str, sentiment: &str, video_id: &str) -> Result<Option<SentMessage>> {
+		let message = youtube_text(&self.config.templates, channel_name, headline, title, sentiment, video_id)?;
+		let thumbnail = format!("https://img.youtube.com/vi/{video_id}/hqdefault.jpg");
+		self.send_item(
+			Severity::Low,
+			NotificationKind::Youtube,
+			&format!("youtube:{video_id}"),
+			&message,
+			ParseMode::Html,
+			Some(&thumbnail),
+			&self.config.channel_output,
+		)
+		.await
 	}
 
-	#[instrument(skip_all)]
-	async fn send_message_to_output(&self, text: &str) -> Result<()> {
-		self.send_message(text, &self.config.channel_output).await
+	pub async fn send_youtube_keyword_alert(&self, channel_name: &str, title: &str, keyword: &str, video_id: &str) -> Result<Option<SentMessage>> {
+		let message = format!("⚠️ [{channel_name}] video title matches \"{keyword}\": [{title}]\n\nhttps://youtube.com/watch?v={video_id}");
+		self.send_item(
+			Severity::High,
+			NotificationKind::Youtube,
+			&format!("youtube_keyword:{video_id}"),
+			&message,
+			ParseMode::Plain,
+			None,
+			&self.config.channel_alerts,
+		)
+		.await
 	}
 
-	#[instrument(skip_all)]
-	async fn send_message(&self, text: &str, destination: &TelegramDestination) -> Result<()> {
-		let url = format!("https://api.telegram.org/bot{}/sendMessage", self.config.bot_token);
+	/// Today's LLM spend crossed `email.llm_daily_budget_cents`. `spend` is by component; `then` says what happens now.
+	pub async fn send_llm_budget_alert(&self, spend: &[(String, f64)], budget_cents: f64, then: &str) -> Result<Option<SentMessage>> {
+		let text = llm_budget_alert_text(spend, budget_cents, then);
+		self.notify(Severity::High, NotificationKind::Email, &text, ParseMode::Plain, &self.config.channel_alerts).await
+	}
 
-		let mut params = vec![("text", text.to_string())];
-		params.extend(destination.destination_params());
-		tracing::debug!(?params);
+	/// Forwarded email, deduped on its message id.
+	pub async fn send_email(&self, message_id: &str, email: &EmailNotification<'_>) -> Result<Option<SentMessage>> {
+		let text = self.config.templates.render(
+			TemplateKind::Email,
+			&[
+				("from", email.from),
+				("history", email.history),
+				("subject", email.subject),
+				("summary", email.summary),
+				("body", email.body),
+				("reply_to", email.reply_to),
+			],
+		)?;
+		self.send_item(
+			Severity::Normal,
+			NotificationKind::Email,
+			&format!("email:{message_id}"),
+			&text,
+			ParseMode::Plain,
+			None,
+			&self.config.channel_alerts,
+		)
+		.await
+	}
 
-		let response = self.client.post(&url).form(&params).send().await?;
-		tracing::debug!(?response);
+	/// Whether a `severity` notification is dropped for being below `telegram.min_severity`.
+	fn suppressed(&self, severity: Severity, kind: NotificationKind) -> bool {
+		let suppressed = severity < self.config.min_severity;
+		if suppressed {
+			info!("Dropping {severity:?} {kind:?} notification, below min_severity {:?}", self.config.min_severity);
+		}
+		suppressed
+	}
+
+	/// A one-off notification, not deduped. `Ok(None)` when suppressed by `telegram.min_severity`.
+	async fn notify(&self, severity: Severity, kind: NotificationKind, text: &str, mode: ParseMode, destination: &TelegramDestination) -> Result<Option<SentMessage>> {
+		if self.suppressed(severity, kind) {
+			return Ok(None);
+		}
+		self.send_message(kind, text, mode, destination).await.map(Some)
+	}
+
+	/// Send a notification about a logical `item` (e.g. `tweet:<id>`), unless it already went to `destination` within
+	/// [`DEDUP_WINDOW`](social_networks_utils::dedup::DEDUP_WINDOW), restarts included. `Ok(None)` when suppressed.
+	/// With a `photo`, `text` becomes its caption; a photo that can't be sent degrades to the plain text message. Also
+	/// `Ok(None)` when suppressed by `telegram.min_severity`.
+	#[allow(clippy::too_many_arguments)]
+	async fn send_item(
+		&self,
+		severity: Severity,
+		kind: NotificationKind,
+		item: &str,
+		text: &str,
+		mode: ParseMode,
+		photo: Option<&str>,
+		destination: &TelegramDestination,
+	) -> Result<Option<SentMessage>> {
+		if self.suppressed(severity, kind) {
+			return Ok(None);
+		}
+		let key = format!("{item}|{:?}", destination.destination_params());
+		if !self.recent.claim(&key).await {
+			info!("Already notified about {item} recently, not sending again");
+			return Ok(None);
+		}
+		let sent = match photo {
+			Some(photo_url) if utf16_len(text) <= CAPTION_LIMIT => match self.send_photo(photo_url, text, mode, destination).await {
+				Ok(sent) => Ok(sent),
+				Err(e) => {
+					warn!("Failed to send photo for {item}, sending text only: {e:#}");
+					self.send_message(kind, text, mode, destination).await
+				}
+			},
+			_ => self.send_message(kind, text, mode, destination).await,
+		};
+		match sent {
+			Ok(sent) => Ok(Some(sent)),
+			Err(e) => {
+				self.recent.release(&key).await;
+				Err(e)
+			}
+		}
+	}
+
+	/// Text over [`MESSAGE_LIMIT`] goes out as several messages, in order; the first one is returned.
+	#[instrument(skip_all, fields(?kind))]
+	async fn send_message(&self, kind: NotificationKind, text: &str, mode: ParseMode, destination: &TelegramDestination) -> Result<SentMessage> {
+		let mut first = None;
+		for chunk in split_message(text, MESSAGE_LIMIT) {
+			let sent = self.send_chunk(kind, &chunk, mode, destination).await?;
+			first.get_or_insert(sent);
+		}
+		Ok(first.expect("split_message yields at least one chunk"))
+	}
+
+	async fn send_chunk(&self, kind: NotificationKind, text: &str, mode: ParseMode, destination: &TelegramDestination) -> Result<SentMessage> {
+		let params = message_params(text, mode, destination, self.config.link_previews.enabled(kind));
+		self.enqueue("sendMessage", params).await
+	}
+
+	/// Photo by URL (Telegram fetches it) with `caption` under it, which must fit [`CAPTION_LIMIT`].
+	#[instrument(skip(self, caption, destination))]
+	pub async fn send_photo(&self, photo_url: &str, caption: &str, mode: ParseMode, destination: &TelegramDestination) -> Result<SentMessage> {
+		self.enqueue("sendPhoto", photo_params(photo_url, caption, mode, destination)).await
+	}
+
+	/// Queue a Bot API `method` call for the send worker and wait for its result.
+	async fn enqueue(&self, method: &'static str, params: Vec<(&str, String)>) -> Result<SentMessage> {
+		let (sent, result) = oneshot::channel();
+		let params = params.into_iter().map(|(key, value)| (key.to_string(), value)).collect();
+		let outgoing = Outgoing {
+			bot_token: self.config.bot_token.clone(),
+			method,
+			params,
+			sent,
+			rate_limited: 0,
+		};
+		if outbox(&self.config.send_rate).send(outgoing).is_err() {
+			bail!("Telegram send worker is gone");
+		}
+		match result.await {
+			Ok(result) => result,
+			Err(_) => bail!("Telegram send worker dropped the message"),
+		}
+	}
+
+	/// Poll `getUpdates` for reactions on messages the bot has sent.
+	///
+	/// Returns the reactions together with the offset to pass on the next call; passing it
+	/// confirms everything up to here so Telegram stops redelivering. The bot must be an admin
+	/// of the chat to receive reaction updates at all.
+	#[instrument(skip(self))]
+	pub async fn fetch_reactions(&self, offset: Option<i64>) -> Result<(Vec<Reaction>, Option<i64>)> {
+		let url = format!("https://api.telegram.org/bot{}/getUpdates", self.config.bot_token);
+
+		let mut params = vec![("timeout", "0".to_string()), ("allowed_updates", r#"["message_reaction","message_reaction_count"]"#.to_string())];
+		if let Some(offset) = offset {
+			params.push(("offset", offset.to_string()));
+		}
 
+		let response = self.client.post(&url).form(&params).send().await?;
 		if !response.status().is_success() {
 			let error_text = response.text().await?;
-			bail!("Failed to send Telegram message: {error_text}");
+			bail!("Failed to fetch Telegram updates: {error_text}");
+		}
+
+		let updates: BotApiResponse<Vec<BotApiUpdate>> = response.json().await?;
+		Ok(parse_reactions(updates.result, offset))
+	}
+}
+
+/// What kind of chat `destination` is, then the exact params every message to it is targeted with. Offline.
+pub fn describe_destination(destination: &TelegramDestination) -> String {
+	let kind = match destination {
+		TelegramDestination::Channel(_) => "channel",
+		TelegramDestination::Group(_) => "group",
+		TelegramDestination::GroupTopic { .. } => "group topic",
+	};
+	let params: Vec<String> = destination.destination_params().into_iter().map(|(key, value)| format!("{key}={value}")).collect();
+	format!("{kind}: {}", params.join(" "))
+}
+
+/// `sendMessage` form for `text` to `destination`. Previews are Telegram's default, so only turning them off is sent.
+fn message_params<'a>(text: &str, mode: ParseMode, destination: &'a TelegramDestination, link_preview: bool) -> Vec<(&'a str, String)> {
+	let mut params = vec![("text", text.to_string())];
+	if let Some(parse_mode) = mode.field() {
+		params.push(("parse_mode", parse_mode.to_string()));
+	}
+	params.extend(destination.destination_params());
+	if !link_preview {
+		params.push(("link_preview_options", r#"{"is_disabled":true}"#.to_string()));
+	}
+	params
+}
+
+/// How long a 429 response asks us to wait before retrying; `None` for any other failure.
+fn rate_limit_wait(status: reqwest::StatusCode, body: &str) -> Option<Duration> {
+	if status != reqwest::StatusCode::TOO_MANY_REQUESTS {
+		return None;
+	}
+	let error: BotApiError = serde_json::from_str(body).ok()?;
+	Some(Duration::from_secs(error.parameters?.retry_after?))
+}
+
+/// Length as the Bot API measures it, in UTF-16 code units: an emoji outside the BMP counts as two.
+fn utf16_len(s: &str) -> usize {
+	s.encode_utf16().count()
+}
+
+/// `text` cut into pieces of at most `limit` UTF-16 code units, on line boundaries where possible; a single line over
+/// `limit` is cut mid-line, between characters. Always at least one piece. Formatted text stays valid as long as no
+/// tag or entity spans lines.
+fn split_message(text: &str, limit: usize) -> Vec<String> {
+	let mut chunks = Vec::new();
+	let mut current = String::new();
+	let mut current_len = 0;
+	for line in text.split_inclusive('\n') {
+		let mut line = line;
+		let mut line_len = utf16_len(line);
+		if current_len + line_len > limit && current_len > 0 {
+			chunks.push(std::mem::take(&mut current));
+			current_len = 0;
+		}
+		while line_len > limit {
+			let cut = utf16_prefix(line, limit);
+			chunks.push(line[..cut].to_string());
+			line = &line[cut..];
+			line_len = utf16_len(line);
+		}
+		current.push_str(line);
+		current_len += line_len;
+	}
+	if !current.is_empty() || chunks.is_empty() {
+		chunks.push(current);
+	}
+	// The line break a piece was cut after would only show as trailing whitespace
+	for chunk in &mut chunks {
+		if chunk.ends_with('\n') && chunk.len() > 1 {
+			chunk.pop();
+		}
+	}
+	chunks
+}
+
+/// Byte length of the longest prefix of `line` within `limit` UTF-16 code units, never splitting a character. At least
+/// one character, so cutting always makes progress.
+fn utf16_prefix(line: &str, limit: usize) -> usize {
+	let mut units = 0;
+	for (i, c) in line.char_indices() {
+		units += c.len_utf16();
+		if units > limit {
+			return if i == 0 { c.len_utf8() } else { i };
+		}
+	}
+	line.len()
+}
+
+/// `sendPhoto` form for a photo by URL, with `caption` in place of [`message_params`]' `text`.
+fn photo_params<'a>(photo_url: &str, caption: &str, mode: ParseMode, destination: &'a TelegramDestination) -> Vec<(&'a str, String)> {
+	let mut params = vec![("photo", photo_url.to_string()), ("caption", caption.to_string())];
+	if let Some(parse_mode) = mode.field() {
+		params.push(("parse_mode", parse_mode.to_string()));
+	}
+	params.extend(destination.destination_params());
+	params
+}
+
+fn parse_reactions(updates: Vec<BotApiUpdate>, offset: Option<i64>) -> (Vec<Reaction>, Option<i64>) {
+	let next_offset = updates.iter().map(|u| u.update_id + 1).max().or(offset);
+
+	let mut reactions = Vec::new();
+	for update in updates {
+		if let Some(r) = update.message_reaction {
+			for kind in r.new_reaction {
+				if let Some(emoji) = kind.emoji {
+					reactions.push(Reaction {
+						chat_id: r.chat.id,
+						message_id: r.message_id,
+						emoji,
+					});
+				}
+			}
+		}
+		if let Some(r) = update.message_reaction_count {
+			for count in r.reactions {
+				if count.total_count > 0
+					&& let Some(emoji) = count.kind.emoji
+				{
+					reactions.push(Reaction {
+						chat_id: r.chat.id,
+						message_id: r.message_id,
+						emoji,
+					});
+				}
+			}
+		}
+	}
+
+	(reactions, next_offset)
+}
+
+#[derive(Debug, Deserialize)]
+struct BotApiResponse<T> {
+	result: T,
+}
+
+#[derive(Debug, Deserialize)]
+struct BotApiError {
+	description: Option<String>,
+	parameters: Option<BotApiErrorParameters>,
+}
+
+#[derive(Debug, Deserialize)]
+struct BotApiErrorParameters {
+	retry_after: Option<u64>,
+}
+
+#[derive(Debug, Deserialize)]
+struct BotApiUser {
+	username: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct BotApiChat {
+	id: i64,
+}
+
+#[derive(Debug, Deserialize)]
+struct BotApiMessage {
+	message_id: i64,
+	chat: BotApiChat,
+}
+
+#[derive(Debug, Deserialize)]
+struct BotApiUpdate {
+	update_id: i64,
+	message_reaction: Option<BotApiMessageReaction>,
+	message_reaction_count: Option<BotApiMessageReactionCount>,
+}
+
+#[derive(Debug, Deserialize)]
+struct BotApiMessageReaction {
+	chat: BotApiChat,
+	message_id: i64,
+	#[serde(default)]
+	new_reaction: Vec<BotApiReactionType>,
+}
+
+#[derive(Debug, Deserialize)]
+struct BotApiMessageReactionCount {
+	chat: BotApiChat,
+	message_id: i64,
+	#[serde(default)]
+	reactions: Vec<BotApiReactionCount>,
+}
+
+#[derive(Debug, Deserialize)]
+struct BotApiReactionCount {
+	#[serde(rename = "type")]
+	kind: BotApiReactionType,
+	total_count: u32,
+}
+
+/// Only `emoji` reactions carry an `emoji` field; custom-emoji and paid reactions are ignored.
+#[derive(Debug, Deserialize)]
+struct BotApiReactionType {
+	emoji: Option<String>,
+}
+
+/// The built-in format links the video title to the video.
+fn youtube_text(templates: &NotificationTemplates, channel_name: &str, headline: &str, title: &str, sentiment: &str, video_id: &str) -> Result<String> {
+	templates.render(
+		TemplateKind::Youtube,
+		&[
+			("channel", channel_name),
+			("headline", headline),
+			("title", title),
+			("sentiment", sentiment),
+			("url", &format!("https://youtube.com/watch?v={video_id}")),
+		],
+	)
+}
+
+fn twitter_poll_text(templates: &NotificationTemplates, author: &str, text: &str, tweet_id: &str) -> Result<String> {
+	templates.render(
+		TemplateKind::TwitterPoll,
+		&[("author", author), ("text", text), ("url", &format!("https://twitter.com/twitter/statuses/{tweet_id}"))],
+	)
+}
+
+/// Attachments are counted in the headline so media is obvious even when the links get cut off.
+fn monitored_user_text(username: &str, platform: &str, attachments: &[String], embeds: &[String]) -> String {
+	let mut text = format!("New message from @{username}, {platform}");
+	match attachments.len() {
+		0 => {}
+		1 => text.push_str(" (1 attachment)"),
+		n => text.push_str(&format!(" ({n} attachments)")),
+	}
+	for url in attachments {
+		text.push_str(&format!("\n{url}"));
+	}
+	for title in embeds {
+		text.push_str(&format!("\nEmbed: {title}"));
+	}
+	text
+}
+
+/// The spend is broken down by component, so it's clear which monitor ran it up.
+fn llm_budget_alert_text(spend: &[(String, f64)], budget_cents: f64, then: &str) -> String {
+	let total = total_llm_spend(spend);
+	let by_component: Vec<String> = spend.iter().map(|(component, cents)| format!("{component} {cents:.2}¢")).collect();
+	format!(
+		"💸 LLM spend today is {total:.2}¢ ({}), over the {budget_cents:.2}¢ daily budget. {then}",
+		by_component.join(", ")
+	)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_parse_mode_escape() {
+		let subject = "Re: <b>50% off</b> & \"free\" stuff_v2 (today!)";
+		assert_eq!(ParseMode::Plain.escape(subject), subject);
+		insta::assert_snapshot!(ParseMode::Html.escape(subject), @"Re: &lt;b&gt;50% off&lt;/b&gt; &amp; &quot;free&quot; stuff_v2 (today!)");
+		insta::assert_snapshot!(ParseMode::MarkdownV2.escape(subject), @r#"Re: <b\>50% off</b\> & "free" stuff\_v2 \(today\!\)"#);
+		insta::assert_snapshot!(ParseMode::MarkdownV2.link("a [b]", "https://example.com/x_(y)"), @r"[a \[b\]](https://example.com/x_(y\))");
+		insta::assert_snapshot!(ParseMode::Html.link("a <b>", "https://example.com/?a=1&b=2"), @r#"<a href="https://example.com/?a=1&amp;b=2">a &lt;b&gt;</a>"#);
+	}
+
+	#[test]
+	fn test_formatted_notifications() {
+		let templates = NotificationTemplates::default();
+		insta::assert_snapshot!(youtube_text(&templates, "Ben", "uploaded a new video", "BTC <to> $100k?", "bullish", "dQw4w9WgXcQ").unwrap(), @r#"
+		[Ben] uploaded a new video: <a href="https://youtube.com/watch?v=dQw4w9WgXcQ">BTC &lt;to&gt; $100k?</a>
+		Perception: bullish
+		"#);
+		insta::assert_snapshot!(twitter_poll_text(&templates, "Some Trader", "Up or down?\n- Up\n- Down", "123").unwrap(), @r#"
+		<a href="https://twitter.com/twitter/statuses/123">Twitter poll</a> from Some Trader:
+		Up or down?
+		- Up
+		- Down
+		"#);
+		let destination: TelegramDestination = serde_json::from_str(r#""WatchingTT""#).unwrap();
+		let params = message_params("<b>hi</b>", ParseMode::Html, &destination, true);
+		assert!(params.contains(&("parse_mode", "HTML".to_string())), "{params:?}");
+	}
+
+	#[test]
+	fn test_email_templates() {
+		let email = |summary, reply_to| {
+			[
+				("from", "Ann <ann@x.com>"),
+				("history", "seen once before"),
+				("subject", "Invoice"),
+				("summary", summary),
+				("body", "Please pay."),
+				("reply_to", reply_to),
+			]
+		};
+		let builtin = NotificationTemplates::default();
+		insta::assert_snapshot!(builtin.render(TemplateKind::Email, &email("", "")).unwrap(), @"
+		📧 New Email
+
+		From: Ann <ann@x.com> (seen once before)
+		Subject: Invoice
+
+		Please pay.
+		");
+		insta::assert_snapshot!(builtin.render(TemplateKind::Email, &email("Asks for payment", "billing@x.com")).unwrap(), @"
+		📧 New Email
+
+		From: Ann <ann@x.com> (seen once before)
+		Subject: Invoice
+
+		💡 Asks for payment
+
+		Please pay.
+		");
+
+		let custom = NotificationTemplates {
+			email: Some("${from}: ${subject}\n${reply_to?Reply-To: ${reply_to}\n}${body}".to_string()),
+			..Default::default()
+		};
+		custom.validate().unwrap();
+		insta::assert_snapshot!(custom.render(TemplateKind::Email, &email("", "")).unwrap(), @"
+		Ann <ann@x.com>: Invoice
+		Please pay.
+		");
+		insta::assert_snapshot!(custom.render(TemplateKind::Email, &email("", "billing@x.com")).unwrap(), @"
+		Ann <ann@x.com>: Invoice
+		Reply-To: billing@x.com
+		Please pay.
+		");
+
+		let unknown = NotificationTemplates {
+			youtube: Some("${channel}: ${views}".to_string()),
+			..Default::default()
+		};
+		insta::assert_snapshot!(unknown.validate().unwrap_err(), @"telegram.templates.youtube uses unknown variable `${views}`, expected one of: channel, headline, title, sentiment, url");
+	}
+
+	#[test]
+	fn test_describe_destination() {
+		let described: Vec<String> = [r#""WatchingTT""#, r#""@alerts""#, "-1001234567890", r#""-1001234567890/7""#]
+			.into_iter()
+			.map(|json| describe_destination(&serde_json::from_str(json).unwrap()))
+			.collect();
+		insta::assert_snapshot!(described.join("\n"), @"
+		group: chat_id=@WatchingTT
+		group: chat_id=@alerts
+		group: chat_id=-1001234567890
+		group topic: chat_id=-1001234567890 message_thread_id=7
+		");
+	}
+
+	#[test]
+	fn test_photo_params() {
+		let destination: TelegramDestination = serde_json::from_str(r#""WatchingTT""#).unwrap();
+		let caption = youtube_text(&NotificationTemplates::default(), "Ben", "uploaded a new video", "BTC", "bullish", "dQw4w9WgXcQ").unwrap();
+		let params = photo_params("https://img.youtube.com/vi/dQw4w9WgXcQ/hqdefault.jpg", &caption, ParseMode::Html, &destination);
+		insta::assert_debug_snapshot!(params, @r#"
+		[
+		    (
+		        "photo",
+		        "https://img.youtube.com/vi/dQw4w9WgXcQ/hqdefault.jpg",
+		    ),
+		    (
+		        "caption",
+		        "[Ben] uploaded a new video: <a href=\"https://youtube.com/watch?v=dQw4w9WgXcQ\">BTC</a>\nPerception: bullish",
+		    ),
+		    (
+		        "parse_mode",
+		        "HTML",
+		    ),
+		    (
+		        "chat_id",
+		        "@WatchingTT",
+		    ),
+		]
+		"#);
+	}
+
+	#[test]
+	fn test_monitored_user_text() {
+		assert_eq!(monitored_user_text("alice", "Telegram", &[], &[]), "New message from @alice, Telegram");
+		let attachments = ["https://cdn.discordapp.com/a.png".to_string(), "https://cdn.discordapp.com/b.mp4".to_string()];
+		insta::assert_snapshot!(monitored_user_text("bob", "Discord", &attachments, &["Some article".to_string()]), @r"
+		New message from @bob, Discord (2 attachments)
+		https://cdn.discordapp.com/a.png
+		https://cdn.discordapp.com/b.mp4
+		Embed: Some article
+		");
+	}
+
+	#[test]
+	fn test_llm_budget_alert_text() {
+		let spend = [("email".to_string(), 40.5), ("youtube".to_string(), 12.0)];
+		insta::assert_snapshot!(llm_budget_alert_text(&spend, 50.0, "Classification continues."), @"💸 LLM spend today is 52.50¢ (email 40.50¢, youtube 12.00¢), over the 50.00¢ daily budget. Classification continues.");
+	}
+
+	#[test]
+	fn test_split_message() {
+		// 150 lines of 60 chars, newline included
+		let text: String = (0..150).map(|i| format!("{i:0>59}\n")).collect();
+		assert_eq!(text.chars().count(), 9000);
+		let chunks = split_message(&text, MESSAGE_LIMIT);
+		assert_eq!(chunks.len(), 3);
+		assert!(chunks.iter().all(|c| utf16_len(c) <= MESSAGE_LIMIT));
+		assert_eq!(chunks.join("\n") + "\n", text, "split on line boundaries, in order");
+
+		assert_eq!(split_message("short", MESSAGE_LIMIT), vec!["short"]);
+		assert_eq!(split_message("", MESSAGE_LIMIT), vec![""]);
+		// A line longer than the limit is cut mid-line, on char boundaries
+		assert_eq!(split_message("ééééé\nab", 2), vec!["éé", "éé", "é", "ab"]);
+		// An emoji outside the BMP is two UTF-16 units, and never split
+		assert_eq!(split_message("😀😀a😀", 3), vec!["😀", "😀a", "😀"]);
+		let emoji_lines: String = (0..100).map(|_| format!("{}\n", "😀".repeat(30))).collect();
+		assert_eq!(emoji_lines.chars().count(), 3100);
+		assert!(split_message(&emoji_lines, MESSAGE_LIMIT).iter().all(|c| utf16_len(c) <= MESSAGE_LIMIT));
+		assert_eq!(split_message(&emoji_lines, MESSAGE_LIMIT).len(), 2);
+	}
+
+	#[test]
+	fn test_rate_limit_wait() {
+		let body = r#"{"ok":false,"error_code":429,"description":"Too Many Requests: retry after 7","parameters":{"retry_after":7}}"#;
+		assert_eq!(rate_limit_wait(reqwest::StatusCode::TOO_MANY_REQUESTS, body), Some(Duration::from_secs(7)));
+		assert_eq!(rate_limit_wait(reqwest::StatusCode::BAD_REQUEST, body), None);
+		let no_retry_after = r#"{"ok":false,"error_code":429,"description":"Too Many Requests"}"#;
+		assert_eq!(rate_limit_wait(reqwest::StatusCode::TOO_MANY_REQUESTS, no_retry_after), None);
+		assert_eq!(rate_limit_wait(reqwest::StatusCode::TOO_MANY_REQUESTS, "<html>"), None);
+	}
+
+	#[tokio::test]
+	async fn test_min_severity_drops_low() {
+		let config = TelegramConfig {
+			min_severity: Severity::Normal,
+			..Default::default()
+		};
+		let notifier = TelegramNotifier::new(config);
+		// Dropped before the dedup claim or any request, so this needs neither a database nor a reachable Bot API
+		let sent = notifier
+			.send_youtube_notification("chan", "uploaded a new video", "title", "neutral", "dQw4w9WgXcQ")
+			.await
+			.unwrap();
+		assert_eq!(sent, None);
+		assert!(notifier.suppressed(Severity::Low, NotificationKind::Youtube));
+		assert!(!notifier.suppressed(Severity::Normal, NotificationKind::Email));
+		assert!(!notifier.suppressed(Severity::High, NotificationKind::Dm));
+		assert_eq!(serde_json::from_str::<Severity>(r#""high""#).unwrap(), Severity::High);
+	}
+
+	#[test]
+	fn test_parse_get_me() {
+		let ok = r#"{"ok":true,"result":{"id":123,"is_bot":true,"first_name":"Alerts","username":"alerts_bot"}}"#;
+		assert_eq!(parse_get_me(ok).unwrap(), "@alerts_bot");
+		let revoked = r#"{"ok":false,"error_code":401,"description":"Unauthorized"}"#;
+		insta::assert_snapshot!(parse_get_me(revoked).unwrap_err(), @"Unauthorized");
+		insta::assert_snapshot!(parse_get_me("<html>").unwrap_err(), @"Unexpected getMe response: <html>");
+	}
+
+	#[test]
+	fn test_send_queue_spaces_sends() {
+		/// Drain `queue` the way the worker does, sending each message as soon as it's due
+		fn drain(queue: &mut SendQueue<&'static str>, start: Instant) -> Vec<(&'static str, Duration)> {
+			let mut sent = Vec::new();
+			while let Some((at, chat)) = queue.next(start) {
+				let chat = chat.to_string();
+				sent.push((queue.take(&chat, at).unwrap().1, at - start));
+			}
+			sent
+		}
+		let start = Instant::now();
+		let ms = Duration::from_millis;
+
+		// 25/s overall is a 40ms gap; 20/min into one chat is 3s
+		let mut queue = SendQueue::new(SendRate::default());
+		for (chat, message) in [("@a", "a1"), ("@a", "a2"), ("@b", "b1"), ("@c", "c1"), ("@b", "b2")] {
+			queue.push(chat, message);
 		}
+		// `a2` waiting on its chat doesn't hold up `b1` and `c1` behind it
+		assert_eq!(drain(&mut queue, start), vec![("a1", ms(0)), ("b1", ms(40)), ("c1", ms(80)), ("a2", ms(3000)), ("b2", ms(3040))]);
+
+		let mut unlimited = SendQueue::new(SendRate {
+			per_second: 0,
+			per_chat_per_minute: 0,
+		});
+		(0..5).for_each(|_| unlimited.push("@a", "a"));
+		assert_eq!(drain(&mut unlimited, start), vec![("a", Duration::ZERO); 5]);
+	}
+
+	#[tokio::test(start_paused = true)]
+	async fn test_rate_limited_chat_doesnt_hold_up_others() {
+		let start = Instant::now();
+		let attempts: std::sync::Arc<Mutex<Vec<(String, Duration)>>> = Default::default();
+		let post = {
+			let attempts = attempts.clone();
+			async move |outgoing: &Outgoing| {
+				let chat = outgoing.params[0].1.clone();
+				let mut attempts = attempts.lock().unwrap();
+				let retry = attempts.iter().any(|(tried, _)| *tried == chat);
+				attempts.push((chat.clone(), start.elapsed()));
+				if chat == "@a" && !retry {
+					return Ok(Err(RateLimited {
+						retry_after: Duration::from_secs(30),
+						description: "Too Many Requests: retry after 30".to_string(),
+					}));
+				}
+				Ok(Ok(SentMessage {
+					chat_id: if chat == "@a" { 1 } else { 2 },
+					message_id: 1,
+				}))
+			}
+		};
+		let (outbox, queued) = mpsc::unbounded_channel();
+		tokio::spawn(drain_outbox(SendRate::default(), queued, post));
+		let send = |chat: &str| {
+			let (sent, result) = oneshot::channel();
+			outbox
+				.send(Outgoing {
+					bot_token: "token".to_string(),
+					method: "sendMessage",
+					params: vec![("chat_id".to_string(), chat.to_string())],
+					sent,
+					rate_limited: 0,
+				})
+				.unwrap();
+			result
+		};
+
+		let (a, b) = (send("@a"), send("@b"));
+		assert_eq!(b.await.unwrap().unwrap().chat_id, 2);
+		assert_eq!(start.elapsed(), Duration::from_millis(40));
+		assert_eq!(a.await.unwrap().unwrap().chat_id, 1);
+		let secs = Duration::from_secs;
+		assert_eq!(
+			*attempts.lock().unwrap(),
+			vec![("@a".to_string(), secs(0)), ("@b".to_string(), Duration::from_millis(40)), ("@a".to_string(), secs(30))]
+		);
+	}
+
+	#[test]
+	fn test_link_preview_per_kind() {
+		let previews = LinkPreviews::default();
+		let destination: TelegramDestination = serde_json::from_str(r#""WatchingTT""#).unwrap();
+		let disabled = ("link_preview_options", r#"{"is_disabled":true}"#.to_string());
+
+		let youtube = message_params("new video", ParseMode::Plain, &destination, previews.enabled(NotificationKind::Youtube));
+		assert!(!youtube.contains(&disabled), "{youtube:?}");
+		let email = message_params("new email", ParseMode::Plain, &destination, previews.enabled(NotificationKind::Email));
+		assert!(email.contains(&disabled), "{email:?}");
+
+		let configured: LinkPreviews = serde_json::from_str(r#"{"youtube": false}"#).unwrap();
+		assert!(!configured.enabled(NotificationKind::Youtube));
+		assert!(!configured.enabled(NotificationKind::Email));
+		assert!(configured.enabled(NotificationKind::Tweet));
+	}
+
+	#[test]
+	fn test_parse_reactions() {
+		let json = r#"[
+			{
+				"update_id": 10,
+				"message_reaction": {
+					"chat": {"id": -1001},
+					"message_id": 42,
+					"date": 0,
+					"old_reaction": [],
+					"new_reaction": [{"type": "emoji", "emoji": "👎"}]
+				}
+			},
+			{
+				"update_id": 11,
+				"message_reaction_count": {
+					"chat": {"id": -1002},
+					"message_id": 7,
+					"date": 0,
+					"reactions": [
+						{"type": {"type": "emoji", "emoji": "👍"}, "total_count": 1},
+						{"type": {"type": "custom_emoji", "custom_emoji_id": "123"}, "total_count": 2}
+					]
+				}
+			}
+		]"#;
+		let updates: Vec<BotApiUpdate> = serde_json::from_str(json).unwrap();
+		let (reactions, next_offset) = parse_reactions(updates, Some(5));
+		insta::assert_debug_snapshot!(reactions, @r#"
+		[
+		    Reaction {
+		        chat_id: -1001,
+		        message_id: 42,
+		        emoji: "👎",
+		    },
+		    Reaction {
+		        chat_id: -1002,
+		        message_id: 7,
+		        emoji: "👍",
+		    },
+		]
+		"#);
+		assert_eq!(next_offset, Some(12));
+	}
 
-		Ok(())
+	#[test]
+	fn test_parse_reactions_empty_keeps_offset() {
+		let (reactions, next_offset) = parse_reactions(vec![], Some(5));
+		assert!(reactions.is_empty());
+		assert_eq!(next_offset, Some(5));
 	}
 }
@@ -1,33 +1,81 @@
-use std::{collections::HashMap, convert::Infallible};
-
 use clap::Args;
-use color_eyre::eyre::{Context, Result};
+use color_eyre::eyre::{Context, Result, bail};
 use jiff::{Timestamp, fmt::strtime};
-use serde::{Deserialize, Serialize};
+use serde::{Deserialize, Serialize, de::DeserializeOwned};
+use social_networks_utils::{
+	db::{Database, TrackedPoll, db_timestamp},
+	http,
+	profile::CycleTimer,
+	shutdown, snooze, status,
+	utils::{ActiveHours, human_duration},
+};
 use tokio::time::{self, Duration};
-use tracing::{error, info};
+use tracing::{error, info, warn};
 use v_utils::macros::MyConfigPrimitives;
 
 use crate::{
 	client::{AdapterError, Client},
-	telegram_dms::TelegramConfig,
+	telegram_dms::{TelegramConfig, TelegramDestination},
 	telegram_notifier::TelegramNotifier,
-	twitter_schedule::TwitterPollConfig,
+	twitter_schedule::{MAX_RATE_LIMIT_WAIT, TwitterPollConfig, rate_limit_reset_in},
 };
 
 const SURFACE: &str = "twitter";
 #[derive(Args)]
-pub struct TwitterArgs {}
+pub struct TwitterArgs {
+	/// Fetch the members of both lists, print how many each has and a few of their usernames, then exit. Checks the
+	/// bearer token and list config without polling anyone's tweets
+	#[arg(long)]
+	pub count_only: bool,
+}
 
 #[derive(Clone, Debug, Default, MyConfigPrimitives)]
 pub struct TwitterConfig {
 	pub bearer_token: String,
+	/// List id, or `@owner/list name` for a list `owner` owns (looked up once at startup)
 	pub everytime_polls_list: String,
+	/// Same as `everytime_polls_list`
 	pub sometimes_polls_list: String,
 	#[primitives(skip)]
 	pub oauth: Option<TwitterOauthConfig>,
 	#[primitives(skip)]
 	pub poll: Option<TwitterPollConfig>,
+	/// Only notify inside this window; tweets seen outside it are marked parsed and dropped.
+	#[primitives(skip)]
+	pub active_hours: Option<ActiveHours>,
+	/// Usernames (of members of the monitored lists) for which every new tweet is forwarded, not just polls
+	#[serde(default)]
+	#[primitives(skip)]
+	pub notify_all_tweets: Vec<String>,
+	/// Where notifications for `everytime_polls_list` go (default: `telegram.channel_output`)
+	#[serde(default)]
+	#[primitives(skip)]
+	pub everytime_polls_destination: Option<TelegramDestination>,
+	/// Where notifications for `sometimes_polls_list` go (default: `telegram.channel_output`)
+	#[serde(default)]
+	#[primitives(skip)]
+	pub sometimes_polls_destination: Option<TelegramDestination>,
+	/// How often to check for new tweets, e.g. `30s`, `5m`
+	#[serde(default = "__default_check_every", with = "human_duration")]
+	#[primitives(skip)]
+	pub check_every: Duration,
+}
+
+impl TwitterConfig {
+	/// Destination override configured for `list_id`, if any.
+	fn destination_for(&self, list_id: &str) -> Option<&TelegramDestination> {
+		if list_id == self.everytime_polls_list {
+			self.everytime_polls_destination.as_ref()
+		} else if list_id == self.sometimes_polls_list {
+			self.sometimes_polls_destination.as_ref()
+		} else {
+			None
+		}
+	}
+
+	fn notifies_all_tweets(&self, username: &str) -> bool {
+		self.notify_all_tweets.iter().any(|u| u.trim_start_matches('@').eq_ignore_ascii_case(username))
+	}
 }
 
 #[derive(Clone, Debug, MyConfigPrimitives)]
@@ -37,6 +85,16 @@ pub struct TwitterOauthConfig {
 	pub api_key_secret: String,
 	pub access_token: String,
 	pub access_token_secret: String,
+	/// OAuth 2.0 app client id. Together with `refresh_token`, tweets are posted with a user-context bearer token
+	/// instead of OAuth 1.0a signing
+	#[serde(default)]
+	pub client_id: Option<String>,
+	/// Only for confidential clients
+	#[serde(default)]
+	pub client_secret: Option<String>,
+	/// Initial refresh token from the PKCE authorization flow (scopes `tweet.read tweet.write users.read offline.access`)
+	#[serde(default)]
+	pub refresh_token: Option<String>,
 }
 
 pub struct TwitterMonitor {
@@ -55,15 +113,24 @@ impl Client for TwitterMonitor {
 		SURFACE
 	}
 
-	async fn listen(&mut self) -> Result<Infallible, AdapterError> {
+	async fn listen(&mut self) -> Result<(), AdapterError> {
 		println!("Twitter: Listening...");
 		loop {
-			match run_twitter_monitor(&self.twitter_config, &self.telegram_config).await {
+			// Resolved ids replace the names in the config, so reconnects don't look them up again
+			let result = match resolve_lists(&http::client(), &mut self.twitter_config).await {
+				Ok(()) => run_twitter_monitor(&self.twitter_config, &self.telegram_config).await,
+				Err(e) => Err(e),
+			};
+			match result {
+				Ok(()) => return Ok(()),
 				Err(TwitterError::Auth(detail)) => return Err(AdapterError::Auth { surface: SURFACE, detail }),
+				Err(TwitterError::Config(detail)) => return Err(AdapterError::Unhandled { surface: SURFACE, detail }),
 				Err(TwitterError::Recoverable(e)) => {
 					error!("Twitter monitor error: {e}");
 					error!("Reconnecting in 5 minutes...");
-					time::sleep(Duration::from_secs(5 * 60)).await;
+					if shutdown::requested_during(Duration::from_secs(5 * 60)).await {
+						return Ok(());
+					}
 				}
 			}
 		}
@@ -73,9 +140,25 @@ impl Client for TwitterMonitor {
 #[derive(Debug)]
 enum TwitterError {
 	Auth(String),
+	/// Config that can't work, e.g. a list name its owner has no list by; retrying can't help
+	Config(String),
 	Recoverable(color_eyre::eyre::Report),
 }
 
+impl TwitterError {
+	/// As the error of a one-off command, which has nothing to retry.
+	fn into_adapter_error(self) -> AdapterError {
+		match self {
+			TwitterError::Auth(detail) => AdapterError::Auth { surface: SURFACE, detail },
+			TwitterError::Config(detail) => AdapterError::Unhandled { surface: SURFACE, detail },
+			TwitterError::Recoverable(e) => AdapterError::Unhandled {
+				surface: SURFACE,
+				detail: format!("{e:#}"),
+			},
+		}
+	}
+}
+
 impl<E: Into<color_eyre::eyre::Report>> From<E> for TwitterError {
 	fn from(e: E) -> Self {
 		TwitterError::Recoverable(e.into())
@@ -96,160 +179,303 @@ async fn ok_or_classify(response: reqwest::Response, op: &str) -> Result<reqwest
 	Err(TwitterError::Recoverable(color_eyre::eyre::eyre!("{op}: {status}: {body}")))
 }
 
-async fn run_twitter_monitor(twitter_config: &TwitterConfig, telegram_config: &TelegramConfig) -> Result<Infallible, TwitterError> {
-	let client = reqwest::Client::new();
-	let telegram = TelegramNotifier::new(telegram_config.clone());
+/// Rate-limited requests are retried this many times before giving up on the call.
+const RATE_LIMIT_RETRIES: u32 = 3;
+/// Wait used when a 429 comes without a usable `x-rate-limit-reset` header.
+const DEFAULT_RATE_LIMIT_WAIT: Duration = Duration::from_secs(60);
+
+/// How long to back off after a 429, given the time left until the advertised reset.
+fn rate_limit_wait(reset_in: Option<Duration>) -> Duration {
+	reset_in.unwrap_or(DEFAULT_RATE_LIMIT_WAIT).min(MAX_RATE_LIMIT_WAIT)
+}
+
+/// GET a v2 endpoint with the bearer token and parse the JSON body. On 429, sleeps until the
+/// rate-limit reset (capped) and retries; other failures are classified by `ok_or_classify`.
+async fn twitter_get_json<T: DeserializeOwned>(client: &reqwest::Client, bearer_token: &str, url: &str, query: &[(&str, &str)], op: &str) -> Result<T, TwitterError> {
+	let mut attempt = 0;
+	loop {
+		let response = client
+			.get(url)
+			.query(query)
+			.header("Authorization", format!("Bearer {bearer_token}"))
+			.header("Content-Type", "application/json")
+			.send()
+			.await
+			.with_context(|| format!("{op}: request failed"))?;
+
+		if response.status() == reqwest::StatusCode::TOO_MANY_REQUESTS && attempt < RATE_LIMIT_RETRIES {
+			attempt += 1;
+			let wait = rate_limit_wait(rate_limit_reset_in(response.headers()));
+			warn!("{op}: rate limited, waiting {}s before retry {attempt}/{RATE_LIMIT_RETRIES}", wait.as_secs());
+			time::sleep(wait).await;
+			continue;
+		}
+
+		let response = ok_or_classify(response, op).await?;
+		return Ok(response.json().await.with_context(|| format!("{op}: failed to parse response"))?);
+	}
+}
 
-	// Load or create parsed tweets state
-	let state_file = xdg::BaseDirectories::with_prefix("social_networks")
-		.place_state_file("twitter_parsed.json")
-		.map_err(color_eyre::eyre::Report::from)?;
-	let mut parsed_state: ParsedTweets = if state_file.exists() {
-		let content = std::fs::read_to_string(&state_file)?;
-		serde_json::from_str(&content)?
-	} else {
-		ParsedTweets::default()
+/// GET every page of a paginated v2 endpoint, following `meta.next_token`.
+async fn twitter_get_all_pages<T: DeserializeOwned>(client: &reqwest::Client, bearer_token: &str, url: &str, query: &[(&str, &str)], op: &str) -> Result<Vec<T>, TwitterError> {
+	let mut items = Vec::new();
+	let mut next_token: Option<String> = None;
+	loop {
+		let mut page_query = query.to_vec();
+		if let Some(token) = &next_token {
+			page_query.push(("pagination_token", token));
+		}
+		let page: Page<T> = twitter_get_json(client, bearer_token, url, &page_query, op).await?;
+		items.extend(page.data);
+		next_token = page.meta.next_token;
+		if next_token.is_none() {
+			return Ok(items);
+		}
+	}
+}
+
+/// A configured list: its id, or a list its owner has, by name.
+#[derive(Debug, PartialEq, Eq)]
+enum ListRef<'a> {
+	Id(&'a str),
+	Owned { owner: &'a str, name: &'a str },
+}
+
+impl<'a> ListRef<'a> {
+	fn parse(list: &'a str) -> Result<Self> {
+		let list = list.trim();
+		if !list.is_empty() && list.bytes().all(|b| b.is_ascii_digit()) {
+			return Ok(Self::Id(list));
+		}
+		match list.strip_prefix('@').and_then(|rest| rest.split_once('/')) {
+			Some((owner, name)) if !owner.is_empty() && !name.trim().is_empty() => Ok(Self::Owned { owner, name: name.trim() }),
+			_ => bail!("Twitter list `{list}` is neither a list id nor `@owner/list name`"),
+		}
+	}
+}
+
+/// Replace lists configured as `@owner/list name` by their ids.
+async fn resolve_lists(client: &reqwest::Client, twitter_config: &mut TwitterConfig) -> Result<(), TwitterError> {
+	let bearer_token = &twitter_config.bearer_token;
+	for list in [&mut twitter_config.everytime_polls_list, &mut twitter_config.sometimes_polls_list] {
+		let list_ref = ListRef::parse(list).map_err(|e| TwitterError::Config(e.to_string()))?;
+		if let ListRef::Owned { owner, name } = list_ref {
+			let id = owned_list_id(client, bearer_token, owner, name).await?;
+			info!("Resolved Twitter list @{owner}/{name} to {id}");
+			*list = id;
+		}
+	}
+	Ok(())
+}
+
+/// Id of the list named `name` (case-insensitively) among those `owner` owns.
+async fn owned_list_id(client: &reqwest::Client, bearer_token: &str, owner: &str, name: &str) -> Result<String, TwitterError> {
+	let url = format!("https://api.twitter.com/2/users/by/username/{owner}");
+	let user: UserResponse = twitter_get_json(client, bearer_token, &url, &[], "user_by_username").await?;
+	let Some(user) = user.data else {
+		return Err(TwitterError::Config(format!("No Twitter user @{owner}, who should own list `{name}`")));
 	};
 
-	info!("--Twitter-- monitor started");
+	let url = format!("https://api.twitter.com/2/users/{}/owned_lists", user.id);
+	let lists: Vec<TwitterList> = twitter_get_all_pages(client, bearer_token, &url, &[("max_results", "100")], "owned_lists").await?;
+	match find_list_id(&lists, name) {
+		Some(id) => Ok(id.to_string()),
+		None => {
+			let names: Vec<&str> = lists.iter().map(|l| l.name.as_str()).collect();
+			Err(TwitterError::Config(format!("@{owner} owns no list named `{name}` (their lists: {})", names.join(", "))))
+		}
+	}
+}
 
-	// Track last tweet IDs for each user in each list
-	let mut user_last_tweets: HashMap<String, String> = HashMap::new();
+fn find_list_id<'a>(lists: &'a [TwitterList], name: &str) -> Option<&'a str> {
+	let name = name.to_lowercase();
+	lists.iter().find(|list| list.name.to_lowercase() == name).map(|list| list.id.as_str())
+}
 
-	//LOOP: daemon - runs until process termination
+async fn run_twitter_monitor(twitter_config: &TwitterConfig, telegram_config: &TelegramConfig) -> Result<(), TwitterError> {
+	let client = http::client();
+	let telegram = TelegramNotifier::new(telegram_config.clone());
+	let db = Database::try_new().await.context("Failed to open database")?;
+
+	let lists = [&twitter_config.everytime_polls_list, &twitter_config.sometimes_polls_list].map(|id| MonitoredList {
+		id,
+		destination: twitter_config.destination_for(id).unwrap_or(&telegram_config.channel_output),
+	});
+
+	info!("--Twitter-- monitor started");
+	status::connected(SURFACE);
+
+	//LOOP: daemon - runs until shutdown
 	loop {
+		let mut timer = CycleTimer::start("twitter");
+
 		// Process everytime polls list
-		match process_list(
-			&client,
-			twitter_config,
-			&twitter_config.everytime_polls_list,
-			&telegram,
-			&mut user_last_tweets,
-			&mut parsed_state.poll_tweets,
-		)
-		.await
-		{
+		match process_list(&client, twitter_config, &lists[0], &telegram, &db, &mut timer).await {
 			Ok(()) => {}
-			Err(TwitterError::Auth(d)) => return Err(TwitterError::Auth(d)),
-			Err(TwitterError::Recoverable(e)) => error!("Error processing everytime polls list: {e}"),
+			Err(TwitterError::Recoverable(e)) => {
+				status::error(SURFACE);
+				error!("Error processing everytime polls list: {e}");
+			}
+			Err(e) => return Err(e),
 		}
 
 		// Process sometimes polls list
-		match process_list(
-			&client,
-			twitter_config,
-			&twitter_config.sometimes_polls_list,
-			&telegram,
-			&mut user_last_tweets,
-			&mut parsed_state.maybe_poll_tweets,
-		)
-		.await
-		{
+		match process_list(&client, twitter_config, &lists[1], &telegram, &db, &mut timer).await {
 			Ok(()) => {}
-			Err(TwitterError::Auth(d)) => return Err(TwitterError::Auth(d)),
-			Err(TwitterError::Recoverable(e)) => error!("Error processing sometimes polls list: {e}"),
+			Err(TwitterError::Recoverable(e)) => {
+				status::error(SURFACE);
+				error!("Error processing sometimes polls list: {e}");
+			}
+			Err(e) => return Err(e),
 		}
 
-		// Save state
-		let state_json = serde_json::to_string(&parsed_state)?;
-		std::fs::write(&state_file, state_json)?;
+		match report_closed_polls(&client, twitter_config, &telegram_config.channel_output, &telegram, &db).await {
+			Ok(()) => {}
+			Err(TwitterError::Recoverable(e)) => {
+				status::error(SURFACE);
+				error!("Error reporting closed polls: {e}");
+			}
+			Err(e) => return Err(e),
+		}
+		status::event(SURFACE);
+		timer.finish();
 
 		let now = Timestamp::now().to_zoned(jiff::tz::TimeZone::UTC);
 		info!("Heartbeat. Time: {}", strtime::format("%m/%d/%y-%H:%M", &now).unwrap());
 
-		// Sleep for 60 seconds
-		time::sleep(Duration::from_secs(60)).await;
+		if shutdown::requested_during(twitter_config.check_every).await {
+			return Ok(());
+		}
 	}
 }
 
+/// A monitored Twitter list and the Telegram destination its notifications go to.
+struct MonitoredList<'a> {
+	id: &'a str,
+	destination: &'a TelegramDestination,
+}
+
 async fn process_list(
 	client: &reqwest::Client,
 	twitter_config: &TwitterConfig,
-	list_id: &str,
+	list: &MonitoredList<'_>,
 	telegram: &TelegramNotifier,
-	user_last_tweets: &mut HashMap<String, String>,
-	parsed_tweets: &mut Vec<String>,
+	db: &Database,
+	timer: &mut CycleTimer,
 ) -> Result<(), TwitterError> {
 	// Get list members
-	let url = format!("https://api.twitter.com/2/lists/{list_id}/members");
-	let response = client
-		.get(&url)
-		.header("Authorization", format!("Bearer {}", twitter_config.bearer_token))
-		.header("Content-Type", "application/json")
-		.send()
-		.await
-		.context("Failed to get list members")?;
-	let response = ok_or_classify(response, "list_members").await?;
-
-	let list_response: TwitterListResponse = response.json().await.context("Failed to parse list response")?;
+	let url = format!("https://api.twitter.com/2/lists/{}/members", list.id);
+	let list_response: TwitterListResponse = twitter_get_json(client, &twitter_config.bearer_token, &url, &[], "list_members").await?;
 
 	// Check each user's latest tweet
-	for (user_idx, member) in list_response.data.iter().enumerate() {
-		// Ensure parsed_tweets has enough entries
-		while parsed_tweets.len() <= user_idx {
-			parsed_tweets.push(String::new());
-		}
-
-		let parsed_id = parsed_tweets[user_idx].clone();
-
-		match check_for_updates(client, twitter_config, member, &parsed_id, telegram, user_last_tweets, parsed_tweets, user_idx).await {
+	for member in &list_response.data {
+		let _item = timer.time(&format!("@{}", member.username));
+		match check_for_updates(client, twitter_config, list, member, telegram, db).await {
 			Ok(()) => {}
-			Err(TwitterError::Auth(d)) => return Err(TwitterError::Auth(d)),
 			Err(TwitterError::Recoverable(e)) => error!("Error checking updates for user {}: {e}", member.name),
+			Err(e) => return Err(e),
 		}
 	}
 
 	Ok(())
 }
 
-#[allow(clippy::too_many_arguments)]
+/// Every member of the list, across pages. Only `--count-only` pages: the monitor re-fetches members every check, so
+/// paging it would spend one request per 100 members on every check, against the endpoint's rate limit.
+async fn list_members(client: &reqwest::Client, bearer_token: &str, list_id: &str) -> Result<Vec<TwitterApiUser>, TwitterError> {
+	let url = format!("https://api.twitter.com/2/lists/{list_id}/members");
+	twitter_get_all_pages(client, bearer_token, &url, &[("max_results", "100")], "list_members").await
+}
+
+/// Usernames printed per list by `--count-only`.
+const COUNT_SAMPLE: usize = 5;
+
+/// `twitter --count-only`: print each list's member count and a sample of its members.
+pub async fn count_members(twitter_config: &TwitterConfig) -> Result<(), AdapterError> {
+	let client = http::client();
+	let mut twitter_config = twitter_config.clone();
+	resolve_lists(&client, &mut twitter_config).await.map_err(TwitterError::into_adapter_error)?;
+	for (key, list_id) in [
+		("everytime_polls_list", &twitter_config.everytime_polls_list),
+		("sometimes_polls_list", &twitter_config.sometimes_polls_list),
+	] {
+		let members = list_members(&client, &twitter_config.bearer_token, list_id).await.map_err(TwitterError::into_adapter_error)?;
+		println!("{}", member_summary(key, list_id, &members));
+	}
+	Ok(())
+}
+
+fn member_summary(key: &str, list_id: &str, members: &[TwitterApiUser]) -> String {
+	let mut summary = format!("{key} ({list_id}): {} members", members.len());
+	if !members.is_empty() {
+		let sample: Vec<String> = members.iter().take(COUNT_SAMPLE).map(|m| format!("@{}", m.username)).collect();
+		let more = if members.len() > COUNT_SAMPLE { ", ..." } else { "" };
+		summary.push_str(&format!("\n  {}{more}", sample.join(", ")));
+	}
+	summary
+}
+
 async fn check_for_updates(
 	client: &reqwest::Client,
 	twitter_config: &TwitterConfig,
+	list: &MonitoredList<'_>,
 	member: &TwitterApiUser,
-	parsed_id: &str,
 	telegram: &TelegramNotifier,
-	_user_last_tweets: &mut HashMap<String, String>,
-	parsed_tweets: &mut [String],
-	user_idx: usize,
+	db: &Database,
 ) -> Result<(), TwitterError> {
 	// Get user's latest tweets
 	let url = format!("https://api.twitter.com/2/users/{}/tweets", member.id);
-	let response = client
-		.get(&url)
-		.header("Authorization", format!("Bearer {}", twitter_config.bearer_token))
-		.header("Content-Type", "application/json")
-		.send()
-		.await
-		.context("Failed to get user tweets")?;
-	let response = ok_or_classify(response, "user_tweets").await?;
-
-	let tweets_response: UserTweetsResponse = response.json().await.context("Failed to parse tweets response")?;
-
-	if tweets_response.data.is_empty() {
-		return Ok(());
-	}
+	let tweets_response: UserTweetsResponse = twitter_get_json(client, &twitter_config.bearer_token, &url, &[("tweet.fields", "attachments")], "user_tweets").await?;
 
-	let latest_tweet_id = &tweets_response.data[0].id;
+	let Some(latest) = tweets_response.data.first() else {
+		return Ok(());
+	};
 
-	// Check if we've already parsed this tweet
-	if latest_tweet_id == parsed_id {
+	let seen = db.get_last_seen_tweet(&member.id, list.id).await?;
+	let Some(seen) = seen else {
+		// First time we see this member in this list: take their current tweet as the baseline rather than alerting on it
+		info!("Baselining {} in list {} at tweet {}", member.username, list.id, latest.id);
+		db.set_last_seen_tweet(&member.id, list.id, &latest.id).await?;
 		return Ok(());
+	};
+
+	// Oldest first, advancing the stored id after each, so a failure part-way resumes from there
+	for tweet in tweets_newer_than(&tweets_response.data, &seen) {
+		if tweet.has_poll() || twitter_config.notifies_all_tweets(&member.username) {
+			handle_tweet(client, twitter_config, list, member, &tweet.id, telegram, db).await?;
+		}
+		db.set_last_seen_tweet(&member.id, list.id, &tweet.id).await?;
 	}
 
+	Ok(())
+}
+
+/// Tweets with an id greater than `last_seen` (ids are snowflakes, so this is "posted later"), oldest first.
+fn tweets_newer_than<'a>(tweets: &'a [Tweet], last_seen: &str) -> Vec<&'a Tweet> {
+	let key = |id: &str| (id.len(), id.to_string());
+	let last_seen = key(last_seen);
+	let mut newer: Vec<&Tweet> = tweets.iter().filter(|t| key(&t.id) > last_seen).collect();
+	newer.sort_by_key(|t| key(&t.id));
+	newer
+}
+
+/// Fetch one tweet with its poll expanded and notify about it: polls always, other tweets only for notify-all accounts.
+async fn handle_tweet(
+	client: &reqwest::Client,
+	twitter_config: &TwitterConfig,
+	list: &MonitoredList<'_>,
+	member: &TwitterApiUser,
+	tweet_id: &str,
+	telegram: &TelegramNotifier,
+	db: &Database,
+) -> Result<(), TwitterError> {
 	// Get tweet details with poll expansion
-	let url = format!("https://api.twitter.com/2/tweets/{latest_tweet_id}");
-	let response = client
-		.get(&url)
-		.query(&[("expansions", "attachments.poll_ids")])
-		.header("Authorization", format!("Bearer {}", twitter_config.bearer_token))
-		.header("Content-Type", "application/json")
-		.send()
-		.await
-		.context("Failed to get tweet details")?;
-	let response = ok_or_classify(response, "tweet_details").await?;
-
-	let tweet_response: TweetResponse = response.json().await.context("Failed to parse tweet response")?;
+	let url = format!("https://api.twitter.com/2/tweets/{tweet_id}");
+	let tweet_response: TweetResponse = twitter_get_json(client, &twitter_config.bearer_token, &url, POLL_QUERY, "tweet_details").await?;
+	let Some(tweet) = &tweet_response.data else {
+		info!("Tweet {tweet_id} from {} is gone (deleted or protected), skipping it", member.name);
+		return Ok(());
+	};
 
 	// Check if this tweet has a poll
 	if let Some(includes) = &tweet_response.includes
@@ -257,27 +483,141 @@ async fn check_for_updates(
 		&& !polls.is_empty()
 	{
 		let poll = &polls[0];
+		if let Some(ends_at) = poll.end_datetime {
+			db.track_poll(&tweet.id, list.id, &member.name, &tweet.text, &db_timestamp(ends_at)).await?;
+		}
 		let mut poll_text = format!("Twitter poll from {}:\n", member.name);
-		poll_text.push_str(&format!("- {}\n", tweet_response.data.text));
+		poll_text.push_str(&format!("- {}\n", tweet.text));
 		for option in &poll.options {
 			poll_text.push_str(&format!("    ├{}: {}\n", option.label, option.votes));
 		}
 
 		println!("{}", poll_text.trim());
-		info!("Found poll from {}: {}", member.name, tweet_response.data.text);
+		info!("Found poll from {}: {}", member.name, tweet.text);
 
 		// Send to Telegram
-		if let Err(e) = telegram.send_twitter_poll(&member.name, &tweet_response.data.text, &tweet_response.data.id).await {
+		if twitter_config.active_hours.as_ref().is_some_and(|h| !h.is_active(Timestamp::now())) {
+			info!("Outside active hours, not notifying about poll from {}", member.name);
+		} else if snooze::is_snoozed("twitter") {
+			info!("Twitter snoozed, not notifying about poll from {}", member.name);
+		} else if let Err(e) = telegram.send_twitter_poll(&member.name, &tweet.text, &tweet.id, list.destination).await {
 			error!("Failed to send poll notification: {e}");
 		}
+	} else if twitter_config.notifies_all_tweets(&member.username) {
+		println!("Tweet from {}: {}", member.name, tweet.text);
+		info!("Found tweet from notify-all account {}: {}", member.username, tweet.text);
+
+		if twitter_config.active_hours.as_ref().is_some_and(|h| !h.is_active(Timestamp::now())) {
+			info!("Outside active hours, not notifying about tweet from {}", member.name);
+		} else if snooze::is_snoozed("twitter") {
+			info!("Twitter snoozed, not notifying about tweet from {}", member.name);
+		} else if let Err(e) = telegram.send_tweet(&member.name, &tweet.text, &tweet.id, list.destination).await {
+			error!("Failed to send tweet notification: {e}");
+		}
 	}
 
-	// Update parsed tweet ID
-	parsed_tweets[user_idx] = latest_tweet_id.clone();
+	Ok(())
+}
+
+/// Tweet lookup with the poll attached, including what's needed to tell when it closes.
+const POLL_QUERY: &[(&str, &str)] = &[("expansions", "attachments.poll_ids"), ("poll.fields", "options,end_datetime,voting_status")];
+/// Closed polls not reported within this long after they ended are given up on (e.g. the tweet was deleted).
+const POLL_REPORT_GRACE: jiff::SignedDuration = jiff::SignedDuration::from_hours(24);
+
+/// Re-fetch tracked polls whose end time has passed and send their final results.
+/// `fallback` is used for polls whose list has no destination override (or that were tracked before lists were recorded).
+async fn report_closed_polls(
+	client: &reqwest::Client,
+	twitter_config: &TwitterConfig,
+	fallback: &TelegramDestination,
+	telegram: &TelegramNotifier,
+	db: &Database,
+) -> Result<(), TwitterError> {
+	let now = Timestamp::now();
+	let due = db.due_polls(&db_timestamp(now), &db_timestamp(now - POLL_REPORT_GRACE)).await?;
+	report_each(due, |tracked| report_closed_poll(client, twitter_config, fallback, telegram, db, tracked, now)).await
+}
+
+/// Run `report` on each due poll in turn. A recoverable failure is logged and the next poll is tried, so one poll that keeps
+/// failing (it's the oldest, so it comes first every cycle) doesn't hold up the rest until it falls out of the grace period.
+async fn report_each<F, Fut>(due: Vec<TrackedPoll>, mut report: F) -> Result<(), TwitterError>
+where
+	F: FnMut(TrackedPoll) -> Fut,
+	Fut: Future<Output = Result<(), TwitterError>>, {
+	for tracked in due {
+		let tweet_id = tracked.tweet_id.clone();
+		match report(tracked).await {
+			Ok(()) => {}
+			Err(TwitterError::Recoverable(e)) => error!("Failed to report closed poll {tweet_id}: {e}"),
+			Err(e) => return Err(e),
+		}
+	}
+	Ok(())
+}
+
+async fn report_closed_poll(
+	client: &reqwest::Client,
+	twitter_config: &TwitterConfig,
+	fallback: &TelegramDestination,
+	telegram: &TelegramNotifier,
+	db: &Database,
+	tracked: TrackedPoll,
+	now: Timestamp,
+) -> Result<(), TwitterError> {
+	let url = format!("https://api.twitter.com/2/tweets/{}", tracked.tweet_id);
+	let tweet_response: TweetResponse = twitter_get_json(client, &twitter_config.bearer_token, &url, POLL_QUERY, "closed_poll").await?;
+	if tweet_response.data.is_none() {
+		info!("Poll tweet {} from {} is gone (deleted or protected), dropping it", tracked.tweet_id, tracked.author);
+		db.mark_poll_closed(&tracked.tweet_id).await?;
+		return Ok(());
+	}
+	let Some(poll) = tweet_response.includes.as_ref().and_then(|i| i.polls.as_ref()).and_then(|p| p.first()) else {
+		info!("Tweet {} no longer has a poll, dropping it", tracked.tweet_id);
+		db.mark_poll_closed(&tracked.tweet_id).await?;
+		return Ok(());
+	};
+	// Twitter can lag a little behind `end_datetime`; the final tally is only there once it says closed
+	if poll.voting_status.as_deref() == Some("open") {
+		return Ok(());
+	}
 
+	let results = poll_results(&poll.options);
+	let destination = tracked.list_id.as_deref().and_then(|id| twitter_config.destination_for(id)).unwrap_or(fallback);
+	// Left unmarked, so the results still go out if the snooze ends within the grace period
+	if snooze::is_snoozed("twitter") {
+		info!("Twitter snoozed, deferring closed poll from {}", tracked.author);
+		return Ok(());
+	}
+	info!("Poll from {} closed: {}", tracked.author, results.replace('\n', "; "));
+	if twitter_config.active_hours.as_ref().is_some_and(|h| !h.is_active(now)) {
+		info!("Outside active hours, not notifying about closed poll from {}", tracked.author);
+	} else if let Err(e) = telegram.send_twitter_poll_closed(&tracked.author, &tracked.text, &results, &tracked.tweet_id, destination).await {
+		error!("Failed to send closed poll notification: {e}");
+		return Ok(());
+	}
+	db.mark_poll_closed(&tracked.tweet_id).await?;
 	Ok(())
 }
 
+/// Final tally, one option per line, followed by the winner (or the tied leaders).
+fn poll_results(options: &[PollOption]) -> String {
+	let total: u32 = options.iter().map(|o| o.votes).sum();
+	let mut out = String::new();
+	for option in options {
+		let pct = if total == 0 { 0.0 } else { f64::from(option.votes) * 100.0 / f64::from(total) };
+		out.push_str(&format!("├{}: {} ({pct:.1}%)\n", option.label, option.votes));
+	}
+
+	let max = options.iter().map(|o| o.votes).max().unwrap_or(0);
+	let leaders: Vec<&str> = options.iter().filter(|o| o.votes == max).map(|o| o.label.as_str()).collect();
+	match leaders.as_slice() {
+		_ if total == 0 => out.push_str("No votes"),
+		[winner] => out.push_str(&format!("Winner: {winner}")),
+		tied => out.push_str(&format!("Tie: {}", tied.join(" / "))),
+	}
+	out
+}
+
 #[derive(Debug, Deserialize, Serialize)]
 struct TwitterApiUser {
 	id: String,
@@ -290,15 +630,57 @@ struct TwitterListResponse {
 	data: Vec<TwitterApiUser>,
 }
 
+#[derive(Debug, Deserialize, Serialize)]
+struct UserResponse {
+	/// Missing (with `errors` instead) when there is no such user
+	data: Option<TwitterApiUser>,
+}
+
+/// One page of a paginated v2 response; `data` is left out of empty pages.
+#[derive(Debug, Deserialize, Serialize)]
+struct Page<T> {
+	#[serde(default = "Vec::new")]
+	data: Vec<T>,
+	#[serde(default)]
+	meta: PageMeta,
+}
+
+#[derive(Debug, Default, Deserialize, Serialize)]
+struct PageMeta {
+	#[serde(default)]
+	next_token: Option<String>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+struct TwitterList {
+	id: String,
+	name: String,
+}
+
 #[derive(Debug, Deserialize, Serialize)]
 struct Tweet {
 	id: String,
 	text: String,
+	/// Only present when requested with `tweet.fields=attachments`
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	attachments: Option<TweetAttachments>,
+}
+impl Tweet {
+	fn has_poll(&self) -> bool {
+		self.attachments.as_ref().is_some_and(|a| !a.poll_ids.is_empty())
+	}
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+struct TweetAttachments {
+	#[serde(default)]
+	poll_ids: Vec<String>,
 }
 
 #[derive(Debug, Deserialize, Serialize)]
 struct TweetResponse {
-	data: Tweet,
+	/// Missing (with `errors` instead) when the tweet was deleted or its author went protected
+	data: Option<Tweet>,
 	includes: Option<TweetIncludes>,
 }
 
@@ -311,6 +693,11 @@ struct TweetIncludes {
 struct Poll {
 	id: String,
 	options: Vec<PollOption>,
+	#[serde(default)]
+	end_datetime: Option<Timestamp>,
+	/// `open` or `closed`
+	#[serde(default)]
+	voting_status: Option<String>,
 }
 
 #[derive(Debug, Deserialize, Serialize)]
@@ -324,10 +711,19 @@ struct UserTweetsResponse {
 	data: Vec<Tweet>,
 }
 
-#[derive(Debug, Default, Deserialize, Serialize)]
-struct ParsedTweets {
-	poll_tweets: Vec<String>,
-	maybe_poll_tweets: Vec<String>,
+/// Check `bearer_token` against the project's tweet usage: app-authenticated like everything the monitor reads, but
+/// counts against no read quota.
+/// A refused token fails with the HTTP status error at the root of the chain, so it can be told from an outage.
+pub async fn verify_bearer_token(bearer_token: &str) -> Result<()> {
+	let response = http::client().get("https://api.twitter.com/2/usage/tweets").bearer_auth(bearer_token).send().await?;
+	if let Some(status) = response.error_for_status_ref().err() {
+		return Err(status).wrap_err(response.text().await?.trim().to_string());
+	}
+	Ok(())
+}
+
+fn __default_check_every() -> Duration {
+	Duration::from_secs(60)
 }
 
 #[cfg(test)]
@@ -345,9 +741,9 @@ mod tests {
 				}
 			]
 		}"#;
-		let response: TwitterListResponse = serde_json::from_str(json).unwrap();
+		let response: Page<TwitterApiUser> = serde_json::from_str(json).unwrap();
 		insta::assert_debug_snapshot!(response, @r#"
-		TwitterListResponse {
+		Page {
 		    data: [
 		        TwitterApiUser {
 		            id: "1234567890",
@@ -355,8 +751,80 @@ mod tests {
 		            username: "testuser",
 		        },
 		    ],
+		    meta: PageMeta {
+		        next_token: None,
+		    },
+		}
+		"#);
+	}
+
+	#[test]
+	fn test_resolve_owned_list() {
+		let json = r#"{
+			"data": [
+				{ "id": "1507244316154023968", "name": "Sometimes polls" },
+				{ "id": "1507245210547409040", "name": "Everytime polls" }
+			],
+			"meta": { "result_count": 2, "next_token": "7140dibdnow9c7btw3w3y5b6jqjnk3k4g5zkmfkvqwa42" }
+		}"#;
+		let page: Page<TwitterList> = serde_json::from_str(json).unwrap();
+		insta::assert_debug_snapshot!(page, @r#"
+		Page {
+		    data: [
+		        TwitterList {
+		            id: "1507244316154023968",
+		            name: "Sometimes polls",
+		        },
+		        TwitterList {
+		            id: "1507245210547409040",
+		            name: "Everytime polls",
+		        },
+		    ],
+		    meta: PageMeta {
+		        next_token: Some(
+		            "7140dibdnow9c7btw3w3y5b6jqjnk3k4g5zkmfkvqwa42",
+		        ),
+		    },
 		}
 		"#);
+		assert_eq!(find_list_id(&page.data, "everytime polls"), Some("1507245210547409040"));
+		assert_eq!(find_list_id(&page.data, "Polls"), None);
+
+		let last: Page<TwitterList> = serde_json::from_str(r#"{ "meta": { "result_count": 0 } }"#).unwrap();
+		assert!(last.data.is_empty() && last.meta.next_token.is_none());
+
+		assert_eq!(ListRef::parse("1507245210547409040").unwrap(), ListRef::Id("1507245210547409040"));
+		assert_eq!(
+			ListRef::parse("@valeratrades/Everytime polls").unwrap(),
+			ListRef::Owned {
+				owner: "valeratrades",
+				name: "Everytime polls"
+			}
+		);
+		assert_eq!(
+			ListRef::parse("Everytime polls").unwrap_err().to_string(),
+			"Twitter list `Everytime polls` is neither a list id nor `@owner/list name`"
+		);
+	}
+
+	#[test]
+	fn test_member_summary() {
+		let members: Vec<TwitterApiUser> = (1..=7)
+			.map(|i| TwitterApiUser {
+				id: i.to_string(),
+				name: format!("User {i}"),
+				username: format!("user{i}"),
+			})
+			.collect();
+		insta::assert_snapshot!(member_summary("everytime_polls_list", "1", &members), @r"
+		everytime_polls_list (1): 7 members
+		  @user1, @user2, @user3, @user4, @user5, ...
+		");
+		insta::assert_snapshot!(member_summary("sometimes_polls_list", "2", &members[..2]), @r"
+		sometimes_polls_list (2): 2 members
+		  @user1, @user2
+		");
+		insta::assert_snapshot!(member_summary("sometimes_polls_list", "2", &[]), @"sometimes_polls_list (2): 0 members");
 	}
 
 	#[test]
@@ -376,6 +844,7 @@ mod tests {
 		        Tweet {
 		            id: "9876543210",
 		            text: "This is a test tweet",
+		            attachments: None,
 		        },
 		    ],
 		}
@@ -414,10 +883,13 @@ mod tests {
 		let response: TweetResponse = serde_json::from_str(json).unwrap();
 		insta::assert_debug_snapshot!(response, @r#"
 		TweetResponse {
-		    data: Tweet {
-		        id: "1234567890",
-		        text: "What's your favorite language?",
-		    },
+		    data: Some(
+		        Tweet {
+		            id: "1234567890",
+		            text: "What's your favorite language?",
+		            attachments: None,
+		        },
+		    ),
 		    includes: Some(
 		        TweetIncludes {
 		            polls: Some(
@@ -438,6 +910,8 @@ mod tests {
 		                                votes: 75,
 		                            },
 		                        ],
+		                        end_datetime: None,
+		                        voting_status: None,
 		                    },
 		                ],
 		            ),
@@ -458,34 +932,166 @@ mod tests {
 		let response: TweetResponse = serde_json::from_str(json).unwrap();
 		insta::assert_debug_snapshot!(response, @r#"
 		TweetResponse {
-		    data: Tweet {
-		        id: "1234567890",
-		        text: "Just a regular tweet",
-		    },
+		    data: Some(
+		        Tweet {
+		            id: "1234567890",
+		            text: "Just a regular tweet",
+		            attachments: None,
+		        },
+		    ),
 		    includes: None,
 		}
 		"#);
 	}
 
 	#[test]
-	fn test_deserialize_parsed_tweets_state() {
+	fn test_deserialize_closed_poll() {
 		let json = r#"{
-			"poll_tweets": ["123", "456", "789"],
-			"maybe_poll_tweets": ["111", "222"]
+			"data": { "id": "1", "text": "Up or down?" },
+			"includes": {
+				"polls": [
+					{
+						"id": "p1",
+						"options": [{ "label": "Up", "votes": 3 }, { "label": "Down", "votes": 5 }],
+						"end_datetime": "2026-01-01T12:00:00.000Z",
+						"voting_status": "closed"
+					}
+				]
+			}
 		}"#;
-		let state: ParsedTweets = serde_json::from_str(json).unwrap();
-		insta::assert_debug_snapshot!(state, @r#"
-		ParsedTweets {
-		    poll_tweets: [
-		        "123",
-		        "456",
-		        "789",
-		    ],
-		    maybe_poll_tweets: [
-		        "111",
-		        "222",
-		    ],
-		}
-		"#);
+		let response: TweetResponse = serde_json::from_str(json).unwrap();
+		let poll = &response.includes.unwrap().polls.unwrap()[0];
+		assert_eq!(db_timestamp(poll.end_datetime.unwrap()), "2026-01-01T12:00:00Z");
+		assert_eq!(poll.voting_status.as_deref(), Some("closed"));
+	}
+
+	#[test]
+	fn test_deserialize_deleted_tweet() {
+		let json = r#"{
+			"errors": [
+				{
+					"value": "1",
+					"detail": "Could not find tweet with id: [1].",
+					"title": "Not Found Error",
+					"type": "https://api.twitter.com/2/problems/resource-not-found"
+				}
+			]
+		}"#;
+		let response: TweetResponse = serde_json::from_str(json).unwrap();
+		assert!(response.data.is_none());
+		assert!(response.includes.is_none());
+	}
+
+	#[tokio::test]
+	async fn test_report_each_skips_failed_poll() {
+		let tracked = |id: &str| TrackedPoll {
+			tweet_id: id.to_string(),
+			list_id: None,
+			author: "alice".to_string(),
+			text: "Up or down?".to_string(),
+			poll_ends_at: "2026-01-01T12:00:00Z".to_string(),
+		};
+		let due = || vec![tracked("1"), tracked("2"), tracked("3")];
+
+		let mut reported = Vec::new();
+		let result = report_each(due(), |poll| {
+			let outcome = match poll.tweet_id.as_str() {
+				"2" => Err(TwitterError::Recoverable(color_eyre::eyre::eyre!("closed_poll: failed to parse response"))),
+				_ => Ok(()),
+			};
+			reported.push(poll.tweet_id);
+			std::future::ready(outcome)
+		})
+		.await;
+		assert!(result.is_ok());
+		assert_eq!(reported, ["1", "2", "3"]);
+
+		// Auth failures would fail every poll the same way, so they still end the pass
+		let mut reported = Vec::new();
+		let result = report_each(due(), |poll| {
+			let outcome = match poll.tweet_id.as_str() {
+				"2" => Err(TwitterError::Auth("401".to_string())),
+				_ => Ok(()),
+			};
+			reported.push(poll.tweet_id);
+			std::future::ready(outcome)
+		})
+		.await;
+		assert!(matches!(result, Err(TwitterError::Auth(_))));
+		assert_eq!(reported, ["1", "2"]);
+	}
+
+	#[test]
+	fn test_poll_results() {
+		let option = |label: &str, votes| PollOption { label: label.to_string(), votes };
+		insta::assert_snapshot!(poll_results(&[option("Up", 30), option("Down", 10)]), @r"
+		├Up: 30 (75.0%)
+		├Down: 10 (25.0%)
+		Winner: Up
+		");
+		insta::assert_snapshot!(poll_results(&[option("A", 2), option("B", 1), option("C", 2)]), @r"
+		├A: 2 (40.0%)
+		├B: 1 (20.0%)
+		├C: 2 (40.0%)
+		Tie: A / C
+		");
+		insta::assert_snapshot!(poll_results(&[option("A", 0), option("B", 0)]), @r"
+		├A: 0 (0.0%)
+		├B: 0 (0.0%)
+		No votes
+		");
+	}
+
+	#[test]
+	fn test_tweets_newer_than() {
+		let json = r#"{
+			"data": [
+				{ "id": "1000000000000000004", "text": "newest" },
+				{ "id": "1000000000000000003", "text": "poll", "attachments": { "poll_ids": ["p1"] } },
+				{ "id": "999999999999999999", "text": "older, shorter id" },
+				{ "id": "999999999999999998", "text": "already seen" }
+			]
+		}"#;
+		let response: UserTweetsResponse = serde_json::from_str(json).unwrap();
+
+		let newer = tweets_newer_than(&response.data, "999999999999999998");
+		let summary: Vec<_> = newer.iter().map(|t| (t.text.as_str(), t.has_poll())).collect();
+		assert_eq!(summary, vec![("older, shorter id", false), ("poll", true), ("newest", false)]);
+
+		assert!(tweets_newer_than(&response.data, "1000000000000000004").is_empty());
+	}
+
+	#[test]
+	fn test_rate_limit_wait() {
+		assert_eq!(rate_limit_wait(Some(Duration::from_secs(90))), Duration::from_secs(90));
+		assert_eq!(rate_limit_wait(None), DEFAULT_RATE_LIMIT_WAIT);
+		assert_eq!(rate_limit_wait(Some(Duration::from_secs(24 * 60 * 60))), MAX_RATE_LIMIT_WAIT);
+	}
+
+	#[test]
+	fn test_destination_for() {
+		let config: TwitterConfig = serde_json::from_value(serde_json::json!({
+			"bearer_token": "x",
+			"everytime_polls_list": "1",
+			"sometimes_polls_list": "2",
+			"sometimes_polls_destination": "LowPriority",
+		}))
+		.unwrap();
+		assert_eq!(config.destination_for("1"), None);
+		assert_eq!(config.destination_for("2"), Some(&TelegramDestination::Group(tg::TopLevelId::AtName("LowPriority".to_string()))));
+		assert_eq!(config.destination_for("3"), None);
+	}
+
+	#[test]
+	fn test_notifies_all_tweets() {
+		let config = TwitterConfig {
+			notify_all_tweets: vec!["@CryptoCred".to_string(), "hsakatrades".to_string()],
+			..Default::default()
+		};
+		assert!(config.notifies_all_tweets("CryptoCred"));
+		assert!(config.notifies_all_tweets("cryptocred"));
+		assert!(config.notifies_all_tweets("HsakaTrades"));
+		assert!(!config.notifies_all_tweets("someone_else"));
+		assert!(!TwitterConfig::default().notifies_all_tweets("CryptoCred"));
 	}
 }
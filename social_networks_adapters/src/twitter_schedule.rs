@@ -1,7 +1,8 @@
 use std::{
 	collections::{BTreeMap, HashMap},
-	convert::Infallible,
-	time::{SystemTime, UNIX_EPOCH},
+	future::Future,
+	path::Path,
+	time::{Duration, SystemTime, UNIX_EPOCH},
 };
 
 use clap::Args;
@@ -11,14 +12,19 @@ use jiff::{Timestamp, fmt::strtime};
 use rand::RngExt;
 use serde::{Deserialize, Serialize};
 use sha1::Sha1;
-use social_networks_utils::utils::{btc_price, format_num_with_thousands};
+use social_networks_utils::{
+	clock::Clock,
+	db::{Database, db_timestamp},
+	http, shutdown, status, template,
+	utils::{coin_price, format_price, reconnect_delay, with_jitter, write_atomic, write_private},
+};
 use tokio::time;
 use tracing::{error, info, instrument};
 use v_utils::{macros::MyConfigPrimitives, trades::Timeframe};
 
 use crate::{
 	client::{AdapterError, Client as AdapterClient},
-	twitter::TwitterConfig,
+	twitter::{TwitterConfig, TwitterOauthConfig},
 };
 
 const SURFACE: &str = "twitter_schedule";
@@ -29,23 +35,261 @@ pub struct TwitterScheduleArgs {
 	/// Skip the first poll posting and go straight to waiting for the next scheduled cycle
 	#[arg(long)]
 	pub skip_first: bool,
+	/// Print the next N post times (honoring --skip-first and the already-posted slot) and exit
+	#[arg(long, value_name = "N")]
+	pub list_slots: Option<usize>,
+	/// Resolve each cycle's poll and print the request instead of posting it; slot state is left untouched
+	#[arg(long)]
+	pub dry_run: bool,
+	/// Fast-forward through N cycles on a simulated clock, printing when each would run and what it would post, then exit
+	#[arg(long, value_name = "N")]
+	pub simulate: Option<usize>,
 }
 #[derive(Clone, Debug, MyConfigPrimitives)]
 pub struct TwitterPollConfig {
-	pub text: String,
+	/// Poll template, or a list of templates to pick one from each cycle
+	#[primitives(skip)]
+	pub text: PollTemplates,
+	/// How to pick among several `text` templates
+	#[serde(default)]
+	#[primitives(skip)]
+	pub text_selection: TemplateSelection,
 	pub duration_hours: u32,
+	/// Fixed interval between polls; the fallback when `schedule_cron` is unset
 	pub schedule_every: Timeframe,
+	/// Cron expression (evaluated in UTC) pinning polls to wall-clock times, e.g. `0 9 * * *`; takes precedence over `schedule_every`
+	#[serde(default)]
+	#[primitives(skip)]
+	pub schedule_cron: Option<String>,
 	#[serde(default = "__default_num_of_retries")]
 	pub num_of_retries: u8,
+	/// Replies posted under the poll in order, each to the previous one, making a thread. Same `${var}` substitution as `text`.
+	#[serde(default)]
+	#[primitives(skip)]
+	pub thread_followups: Vec<String>,
+	/// Post a template with no `- [ ]` options as a plain tweet instead of rejecting it
+	#[serde(default)]
+	pub allow_plain_tweets: bool,
+}
+
+/// Posting more often than this looks like spam to Twitter.
+const MIN_SCHEDULE_INTERVAL: Duration = Duration::from_secs(15 * 60);
+/// Twitter's bounds on poll duration, in minutes (5 min – 7 days).
+const POLL_DURATION_MINUTES: std::ops::RangeInclusive<u64> = 5..=7 * 24 * 60;
+
+/// How many upcoming cron fires `validate` checks the spacing of.
+const CRON_VALIDATE_FIRES: usize = 16;
+
+impl TwitterPollConfig {
+	/// Reject schedules Twitter would rate-limit or refuse, before anything gets posted.
+	pub fn validate(&self) -> Result<()> {
+		match self.schedule()? {
+			Schedule::Every(interval) =>
+				if interval < MIN_SCHEDULE_INTERVAL {
+					bail!(
+						"twitter.poll.schedule_every is {interval:?}, below the {}-minute minimum; posting polls this often gets the account rate-limited or flagged for spam. Use e.g. \"1h\" or \"1d\"",
+						MIN_SCHEDULE_INTERVAL.as_secs() / 60
+					);
+				},
+			schedule @ Schedule::Cron(_) => {
+				let fires: Vec<Timestamp> = std::iter::successors(schedule.next_after(Timestamp::now()).ok(), |t| schedule.next_after(*t).ok())
+					.take(CRON_VALIDATE_FIRES)
+					.collect();
+				if fires.is_empty() {
+					bail!("twitter.poll.schedule_cron = {schedule} never fires");
+				}
+				if let Some(w) = fires
+					.windows(2)
+					.find(|w| w[1].duration_since(w[0]) < jiff::SignedDuration::try_from(MIN_SCHEDULE_INTERVAL).unwrap())
+				{
+					bail!(
+						"twitter.poll.schedule_cron = {schedule} fires at {} and again at {}, below the {}-minute minimum; posting polls this often gets the account rate-limited or flagged for spam",
+						w[0],
+						w[1],
+						MIN_SCHEDULE_INTERVAL.as_secs() / 60
+					);
+				}
+			}
+		}
+		let minutes = u64::from(self.duration_hours) * 60;
+		if !POLL_DURATION_MINUTES.contains(&minutes) {
+			bail!(
+				"twitter.poll.duration_hours = {} is outside Twitter's poll duration bounds (5 minutes to 7 days); set it between 1 and {}",
+				self.duration_hours,
+				POLL_DURATION_MINUTES.end() / 60
+			);
+		}
+		Ok(())
+	}
+
+	fn schedule(&self) -> Result<Schedule> {
+		match &self.schedule_cron {
+			Some(expr) => Ok(Schedule::Cron(Box::new(
+				expr.parse().map_err(|e| eyre!("twitter.poll.schedule_cron = {expr:?} is not a valid cron expression: {e}"))?,
+			))),
+			None => Ok(Schedule::Every(self.schedule_every.duration())),
+		}
+	}
+}
+
+/// When polls go out: a fixed interval from whenever the previous cycle finished, or pinned to cron fire times.
+#[derive(Clone, Debug)]
+enum Schedule {
+	Every(Duration),
+	Cron(Box<croner::Cron>),
+}
+
+impl Schedule {
+	/// When the loop started at `now` posts first. An interval posts right away (or one interval later with
+	/// `skip_first`); cron waits for its next fire (and skips that one with `skip_first`).
+	fn first_post(&self, now: Timestamp, skip_first: bool) -> Result<Timestamp> {
+		let first = match self {
+			Schedule::Every(_) => now,
+			Schedule::Cron(_) => self.next_after(now)?,
+		};
+		if skip_first { self.next_after(first) } else { Ok(first) }
+	}
+
+	/// Next time to post after a cycle at `t`.
+	fn next_after(&self, t: Timestamp) -> Result<Timestamp> {
+		match self {
+			Schedule::Every(interval) => Ok(t.checked_add(jiff::SignedDuration::try_from(*interval)?)?),
+			Schedule::Cron(cron) => {
+				let next = cron.find_next_occurrence(&to_chrono(t), false).map_err(|e| eyre!("No cron fire after {t}: {e}"))?;
+				Ok(Timestamp::new(next.timestamp(), next.timestamp_subsec_nanos() as i32)?)
+			}
+		}
+	}
+
+	/// Key of the slot `now` falls in, and its index for round-robin (`None` for cron: there is no epoch-aligned
+	/// count of fires, so the caller continues from the previous slot's).
+	fn slot(&self, now: Timestamp) -> Result<(String, Option<i64>)> {
+		match self {
+			Schedule::Every(interval) => Ok((slot_key(now, *interval), Some(slot_index(now, *interval)))),
+			Schedule::Cron(cron) => {
+				let fire = cron.find_previous_occurrence(&to_chrono(now), true).map_err(|e| eyre!("No cron fire before {now}: {e}"))?;
+				Ok((format!("{}/cron", Timestamp::from_second(fire.timestamp())?), None))
+			}
+		}
+	}
+}
+
+impl std::fmt::Display for Schedule {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		match self {
+			Schedule::Every(interval) => write!(f, "every {interval:?}"),
+			Schedule::Cron(cron) => write!(f, "cron `{}` (UTC)", cron.as_str()),
+		}
+	}
+}
+
+fn to_chrono(t: Timestamp) -> chrono::DateTime<chrono::Utc> {
+	chrono::DateTime::from_timestamp(t.as_second(), t.subsec_nanosecond() as u32).expect("jiff timestamps are within chrono's range")
+}
+
+/// Sleep until wall-clock `at`; returns immediately if it already passed.
+/// Whether a shutdown cut the sleep short.
+async fn sleep_until(at: Timestamp) -> bool {
+	shutdown::requested_during(at.duration_since(Timestamp::now()).try_into().unwrap_or_default()).await
+}
+
+/// Time source of the posting loop: a [`Clock`] that also sleeps and counts cycles. A simulated clock (`--simulate`)
+/// jumps straight to each wake-up instead of sleeping, and runs out after `cycles_left` cycles, recording when each
+/// one started.
+enum ScheduleClock {
+	Real,
+	Simulated { now: Timestamp, cycles_left: usize, cycles: Vec<Timestamp> },
+}
+impl Clock for ScheduleClock {
+	fn now(&self) -> Timestamp {
+		match self {
+			ScheduleClock::Real => Timestamp::now(),
+			ScheduleClock::Simulated { now, .. } => *now,
+		}
+	}
+}
+impl ScheduleClock {
+	fn simulated(start: Timestamp, cycles: usize) -> Self {
+		ScheduleClock::Simulated {
+			now: start,
+			cycles_left: cycles,
+			cycles: Vec::with_capacity(cycles),
+		}
+	}
+
+	fn is_simulated(&self) -> bool {
+		matches!(self, ScheduleClock::Simulated { .. })
+	}
+
+	/// Whether a shutdown cut the sleep short; never on a simulated clock.
+	async fn sleep_until(&mut self, at: Timestamp) -> bool {
+		match self {
+			ScheduleClock::Real => sleep_until(at).await,
+			ScheduleClock::Simulated { now, .. } => {
+				*now = (*now).max(at);
+				false
+			}
+		}
+	}
+
+	/// Whether to start another cycle: always on the real clock, while any are left on a simulated one.
+	fn next_cycle(&mut self) -> bool {
+		match self {
+			ScheduleClock::Real => true,
+			ScheduleClock::Simulated { now, cycles_left, cycles } => {
+				if *cycles_left == 0 {
+					return false;
+				}
+				*cycles_left -= 1;
+				cycles.push(*now);
+				true
+			}
+		}
+	}
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(untagged)]
+pub enum PollTemplates {
+	One(String),
+	Many(Vec<String>),
+}
+impl PollTemplates {
+	pub fn as_slice(&self) -> &[String] {
+		match self {
+			PollTemplates::One(text) => std::slice::from_ref(text),
+			PollTemplates::Many(texts) => texts,
+		}
+	}
+}
+
+#[derive(Clone, Copy, Debug, Default, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum TemplateSelection {
+	#[default]
+	Random,
+	/// Cycle through templates in order, keyed on the schedule slot so restarts don't reset it
+	RoundRobin,
 }
 
 pub struct TwitterSchedule {
 	twitter_config: TwitterConfig,
 	skip_first: bool,
+	dry_run: bool,
 }
 impl TwitterSchedule {
 	pub fn new(twitter_config: TwitterConfig, skip_first: bool) -> Self {
-		Self { twitter_config, skip_first }
+		Self {
+			twitter_config,
+			skip_first,
+			dry_run: false,
+		}
+	}
+
+	/// Print what each cycle would post instead of posting it.
+	pub fn dry_run(mut self, dry_run: bool) -> Self {
+		self.dry_run = dry_run;
+		self
 	}
 }
 
@@ -58,17 +302,29 @@ impl AdapterClient for TwitterSchedule {
 		SURFACE
 	}
 
-	async fn listen(&mut self) -> Result<Infallible, AdapterError> {
+	async fn listen(&mut self) -> Result<(), AdapterError> {
 		println!("Twitter Schedule: Starting scheduled poll posting...");
-		match schedule_sentiment_poll(&self.twitter_config, self.skip_first).await {
+		let state_file = slot_state_file().map_err(|e| AdapterError::Unhandled {
+			surface: SURFACE,
+			detail: format!("{e:#}"),
+		})?;
+		match schedule_sentiment_poll(&self.twitter_config, &state_file, self.skip_first, self.dry_run, &mut ScheduleClock::Real).await {
+			Ok(()) => Ok(()),
 			Err(ScheduleError::Auth(detail)) => Err(AdapterError::Auth { surface: SURFACE, detail }),
-			Err(ScheduleError::Unhandled(detail)) => Err(AdapterError::Unhandled { surface: SURFACE, detail }),
+			Err(ScheduleError::Unhandled(detail) | ScheduleError::RateLimited { detail, .. } | ScheduleError::Duplicate(detail)) => Err(AdapterError::Unhandled { surface: SURFACE, detail }),
 		}
 	}
 }
 
 enum ScheduleError {
 	Auth(String),
+	/// Twitter refused the tweet as identical to a recent one; retrying the same text cannot help.
+	Duplicate(String),
+	/// HTTP 429. `retry_after` comes from `x-rate-limit-reset` when Twitter sends it.
+	RateLimited {
+		retry_after: Option<Duration>,
+		detail: String,
+	},
 	Unhandled(String),
 }
 
@@ -78,78 +334,411 @@ impl<E: Into<color_eyre::eyre::Report>> From<E> for ScheduleError {
 	}
 }
 
-/// Runs a scheduling loop that posts sentiment polls at regular intervals
-#[instrument(skip(twitter_config))]
-async fn schedule_sentiment_poll(twitter_config: &TwitterConfig, skip_first: bool) -> Result<Infallible, ScheduleError> {
+/// Runs a scheduling loop that posts sentiment polls at regular intervals or cron fire times. Only returns (`Ok`) on
+/// shutdown, or once a simulated `clock` runs out of cycles; those just print what they would post. Posted slots are
+/// recorded in `state_file`, which simulated cycles only read.
+#[instrument(skip(twitter_config, clock))]
+async fn schedule_sentiment_poll(twitter_config: &TwitterConfig, state_file: &Path, skip_first: bool, dry_run: bool, clock: &mut ScheduleClock) -> Result<(), ScheduleError> {
 	println!("Twitter Schedule: Scheduler initialized");
 
 	let poll_config = twitter_config
 		.poll
 		.as_ref()
 		.ok_or_else(|| ScheduleError::Unhandled("twitter.poll config not found".to_string()))?;
+	poll_config.validate()?;
 
-	// Get the schedule interval
-	let schedule_duration = poll_config.schedule_every.duration();
-	info!("schedule_interval={schedule_duration:?} retries={} skip_first={skip_first}", poll_config.num_of_retries);
-	println!("Schedule interval: {schedule_duration:?}");
+	let schedule = poll_config.schedule()?;
+	info!("schedule={schedule} retries={} skip_first={skip_first} dry_run={dry_run}", poll_config.num_of_retries);
+	println!("Schedule: {schedule}");
+	if dry_run {
+		println!("Dry run: polls are printed, not posted");
+	}
+	let live = !clock.is_simulated();
+	if !live {
+		println!("Simulation: cycles are fast-forwarded and printed, nothing is posted");
+	}
+	// Simulated cycles carry on from the recorded slot without ever writing it
+	let mut simulated_last = if live { None } else { load_slot_state(state_file)?.last };
 
-	if skip_first {
-		let next_time = Timestamp::now()
-			.to_zoned(jiff::tz::TimeZone::UTC)
-			.checked_add(jiff::Span::try_from(schedule_duration).unwrap())
-			.unwrap();
-		let next_time_str = strtime::format("%Y-%m-%d %H:%M:%S", &next_time).unwrap();
+	let start = clock.now();
+	let first = schedule.first_post(start, skip_first)?;
+	if first > start {
+		let next_time_str = strtime::format("%Y-%m-%d %H:%M:%S", &first.to_zoned(jiff::tz::TimeZone::UTC)).unwrap();
 
-		info!("skip_first=true next={next_time_str}");
-		println!("Skipping first post, next poll: {next_time_str}");
+		info!("skip_first={skip_first} next={next_time_str}");
+		if skip_first {
+			println!("Skipping first post, next poll: {next_time_str}");
+		} else {
+			println!("First poll: {next_time_str}");
+		}
 
-		time::sleep(schedule_duration).await;
+		if clock.sleep_until(first).await {
+			return Ok(());
+		}
+	}
+	if live {
+		status::connected(SURFACE);
 	}
 
-	//LOOP: daemon - runs until process termination
-	loop {
-		let now = Timestamp::now().to_zoned(jiff::tz::TimeZone::UTC);
-		let time_str = strtime::format("%Y-%m-%d %H:%M:%S", &now).unwrap();
+	//LOOP: daemon - runs until shutdown (or the simulated clock's last cycle)
+	while clock.next_cycle() {
+		let cycle_at = clock.now();
+		let time_str = strtime::format("%Y-%m-%d %H:%M:%S", &cycle_at.to_zoned(jiff::tz::TimeZone::UTC)).unwrap();
 
-		info!("cycle_start time={time_str}");
+		info!("cycle_start time={time_str} simulated={}", !live);
 		println!("\n[{time_str}] Starting poll posting cycle");
 
-		// Post the poll with retries
-		let mut success = false;
-		for attempt in 1..=poll_config.num_of_retries {
-			match post_poll(twitter_config).await {
-				Ok(()) => {
-					info!("post_success attempt={attempt}");
-					println!("✓ Poll posted successfully");
-					success = true;
-					break;
-				}
-				Err(ScheduleError::Auth(detail)) => return Err(ScheduleError::Auth(detail)),
-				Err(ScheduleError::Unhandled(e)) => {
-					error!("post_failed attempt={attempt}/{} error={e}", poll_config.num_of_retries);
-					if attempt == poll_config.num_of_retries {
-						println!("✗ Failed to post poll: {e}");
-					}
-				}
+		if live {
+			if post_slot_poll(twitter_config, state_file, &schedule, cycle_at, dry_run).await? {
+				status::event(SURFACE);
+			} else {
+				status::error(SURFACE);
 			}
+		} else {
+			simulate_slot(poll_config, &schedule, cycle_at, &mut simulated_last)?;
 		}
 
-		let next_time = Timestamp::now()
-			.to_zoned(jiff::tz::TimeZone::UTC)
-			.checked_add(jiff::Span::try_from(schedule_duration).unwrap())
-			.unwrap();
-		let next_time_str = strtime::format("%Y-%m-%d %H:%M:%S", &next_time).unwrap();
+		let next_time = schedule.next_after(clock.now())?;
+		let next_time_str = strtime::format("%Y-%m-%d %H:%M:%S", &next_time.to_zoned(jiff::tz::TimeZone::UTC)).unwrap();
 
-		info!("cycle_complete success={success} next={next_time_str}");
+		info!("cycle_complete next={next_time_str}");
 		println!("Next poll: {next_time_str}");
 
 		// Sleep until next cycle
-		time::sleep(schedule_duration).await;
+		if clock.sleep_until(next_time).await {
+			return Ok(());
+		}
+	}
+	Ok(())
+}
+
+/// Longest we are willing to sit on a rate-limit reset before trying again.
+pub(crate) const MAX_RATE_LIMIT_WAIT: Duration = Duration::from_secs(15 * 60);
+
+/// Call `post` up to `num_of_retries` times, sleeping between attempts: until the rate-limit
+/// reset on 429, jittered exponential backoff otherwise. Returns the first successful result;
+/// only auth errors escape.
+async fn post_with_retries<T, F, Fut>(num_of_retries: u8, mut post: F) -> Result<Option<T>, ScheduleError>
+where
+	F: FnMut() -> Fut,
+	Fut: Future<Output = Result<T, ScheduleError>>, {
+	for attempt in 1..=num_of_retries {
+		let wait = match post().await {
+			Ok(posted) => {
+				info!("post_success attempt={attempt}");
+				println!("✓ Poll posted successfully");
+				return Ok(Some(posted));
+			}
+			Err(ScheduleError::Auth(detail)) => return Err(ScheduleError::Auth(detail)),
+			Err(ScheduleError::Duplicate(detail)) => {
+				error!("post_duplicate attempt={attempt}/{num_of_retries} error={detail}");
+				println!("✗ Poll rejected as duplicate content even with a timestamp appended, skipping this cycle");
+				return Ok(None);
+			}
+			Err(ScheduleError::RateLimited { retry_after, detail }) => {
+				error!("post_rate_limited attempt={attempt}/{num_of_retries} error={detail}");
+				retry_after.map(|d| d.min(MAX_RATE_LIMIT_WAIT)).unwrap_or_else(|| with_jitter(reconnect_delay(attempt.into())))
+			}
+			Err(ScheduleError::Unhandled(e)) => {
+				error!("post_failed attempt={attempt}/{num_of_retries} error={e}");
+				with_jitter(reconnect_delay(attempt.into()))
+			}
+		};
+
+		if attempt == num_of_retries {
+			println!("✗ Failed to post poll after {num_of_retries} attempts");
+		} else {
+			info!("retrying in {:.1}s", wait.as_secs_f64());
+			time::sleep(wait).await;
+		}
+	}
+	Ok(None)
+}
+
+/// Post the poll for the slot `now` falls in at most once, even across lost responses and restarts. With `dry_run`,
+/// only prints the request that would be posted; one that can't be built (a variable's lookup failing) is logged and
+/// the slot skipped, like a post that keeps failing.
+async fn post_slot_poll(twitter_config: &TwitterConfig, state_file: &Path, schedule: &Schedule, now: Timestamp, dry_run: bool) -> Result<bool, ScheduleError> {
+	let poll_config = twitter_config
+		.poll
+		.as_ref()
+		.ok_or_else(|| ScheduleError::Unhandled("twitter.poll config not found".to_string()))?;
+	let mut state = load_slot_state(state_file)?;
+	let (key, index, template) = slot_template(poll_config, schedule, now, state.last.as_ref())?;
+	if dry_run {
+		let thread = match build_poll_thread(poll_config, template).await {
+			Ok(thread) => thread,
+			Err(e) => {
+				error!("dry_run slot={key} error={e:#}");
+				println!("✗ [dry run] Failed to build the poll for slot {key}: {e:#}");
+				return Ok(false);
+			}
+		};
+		let request = serde_json::to_string_pretty(&thread)?;
+		info!("dry_run slot={key} request={request}");
+		println!("[dry run] Would post for slot {key}:\n{request}");
+		return Ok(true);
+	}
+	let resumed = match state.last.take().filter(|m| m.key == key) {
+		Some(SlotMarker { tweet_id: Some(id), .. }) => {
+			info!("slot={key} already posted tweet_id={id}");
+			println!("Poll for this slot already posted (tweet {id}), skipping");
+			return Ok(true);
+		}
+		resumed => resumed,
+	};
+
+	// Marker goes down before the first attempt, so a post whose response we lose (or a crash mid-post) is found next time
+	let marked_at = resumed.as_ref().map_or_else(Timestamp::now, |m| m.marked_at);
+	state.last = Some(SlotMarker {
+		key: key.clone(),
+		index,
+		marked_at,
+		tweet_id: None,
+	});
+	save_slot_state(state_file, &state)?;
+
+	// Every attempt after the first (or the first one on a resumed slot) looks for the poll before re-posting
+	let mut check_existing = resumed.is_some();
+	let posted = post_with_retries(poll_config.num_of_retries, || {
+		let check = std::mem::replace(&mut check_existing, true);
+		post_poll_once(twitter_config, template, marked_at, check)
+	})
+	.await?;
+
+	if let Some(tweet_id) = &posted {
+		state.last = Some(SlotMarker {
+			key,
+			index,
+			marked_at,
+			tweet_id: Some(tweet_id.clone()),
+		});
+		save_slot_state(state_file, &state)?;
+	}
+	Ok(posted.is_some())
+}
+
+/// Slot `now` falls in, its round-robin index (continuing from `last`), and the template picked for it.
+fn slot_template<'a>(poll_config: &'a TwitterPollConfig, schedule: &Schedule, now: Timestamp, last: Option<&SlotMarker>) -> Result<(String, i64, &'a str), ScheduleError> {
+	let (key, aligned_index) = schedule.slot(now)?;
+	let index = aligned_index.unwrap_or_else(|| next_slot_index(last, &key));
+	let template = choose_template(poll_config.text.as_slice(), poll_config.text_selection, index, &mut rand::rng())
+		.ok_or_else(|| ScheduleError::Unhandled("twitter.poll.text has no templates".to_string()))?;
+	Ok((key, index, template))
+}
+
+/// Print what [`post_slot_poll`] would post for the slot `now` falls in. `last` stands in for the slot state file, so
+/// round-robin advances across simulated cycles. Templates are shown unresolved: variables would be fetched at the
+/// real time, not the simulated one.
+fn simulate_slot(poll_config: &TwitterPollConfig, schedule: &Schedule, now: Timestamp, last: &mut Option<SlotMarker>) -> Result<(), ScheduleError> {
+	if let Some(SlotMarker { key, tweet_id: Some(id), .. }) = last.as_ref()
+		&& *key == schedule.slot(now)?.0
+	{
+		println!("[simulate] Poll for slot {key} already posted (tweet {id}), skipping");
+		return Ok(());
+	}
+	let (key, index, template) = slot_template(poll_config, schedule, now, last.as_ref())?;
+	info!("simulate slot={key} index={index}");
+	println!("[simulate] Would post for slot {key}:\n{template}");
+	*last = Some(SlotMarker {
+		key,
+		index,
+		marked_at: now,
+		tweet_id: None,
+	});
+	Ok(())
+}
+
+/// Run `n` cycles of the posting loop on a simulated clock starting now, printing when each would run and what it
+/// would post. Nothing is posted and slot state is left untouched.
+pub async fn simulate(twitter_config: &TwitterConfig, skip_first: bool, n: usize) -> Result<()> {
+	match schedule_sentiment_poll(twitter_config, &slot_state_file()?, skip_first, false, &mut ScheduleClock::simulated(Timestamp::now(), n)).await {
+		Ok(()) => Ok(()),
+		Err(ScheduleError::Auth(detail) | ScheduleError::Unhandled(detail) | ScheduleError::RateLimited { detail, .. } | ScheduleError::Duplicate(detail)) => Err(eyre!(detail)),
+	}
+}
+
+/// Print the next `n` times the scheduler would post, in `twitter.active_hours`' timezone (system local if unset).
+pub fn list_slots(twitter_config: &TwitterConfig, skip_first: bool, n: usize) -> Result<()> {
+	let poll_config = twitter_config.poll.as_ref().ok_or_else(|| eyre!("twitter.poll config not found"))?;
+	poll_config.validate()?;
+	let schedule = poll_config.schedule()?;
+	let state = load_slot_state(&slot_state_file()?)?;
+	let posted_slot = state.last.as_ref().filter(|m| m.tweet_id.is_some()).map(|m| m.key.as_str());
+	let tz = twitter_config.active_hours.as_ref().map_or_else(jiff::tz::TimeZone::system, |h| h.tz.clone());
+
+	println!("Next {n} polls ({schedule}):");
+	for at in upcoming_post_times(&schedule, Timestamp::now(), skip_first, posted_slot, n)? {
+		println!("  {}", strtime::format("%Y-%m-%d %H:%M:%S %Z", &at.to_zoned(tz.clone()))?);
+	}
+	Ok(())
+}
+
+/// Cycle start times of the posting loop that would actually post: the loop starts at [`Schedule::first_post`] and
+/// then waits for [`Schedule::next_after`] each cycle; a cycle in `posted_slot` is skipped. Ignores the few seconds
+/// each post takes, which an interval schedule drifts by.
+fn upcoming_post_times(schedule: &Schedule, now: Timestamp, skip_first: bool, posted_slot: Option<&str>, n: usize) -> Result<Vec<Timestamp>> {
+	let first = schedule.first_post(now, skip_first)?;
+	let mut times = Vec::with_capacity(n);
+	for t in std::iter::successors(Some(first), |t| schedule.next_after(*t).ok()) {
+		if times.len() == n {
+			break;
+		}
+		if posted_slot != Some(schedule.slot(t)?.0.as_str()) {
+			times.push(t);
+		}
+	}
+	Ok(times)
+}
+
+/// Identifies a scheduling slot: start of the `interval`-aligned window `now` falls in, plus the interval.
+/// Consecutive cycles are at least `interval` apart, so they never share a key.
+fn slot_key(now: Timestamp, interval: Duration) -> String {
+	let secs = interval.as_secs().max(1) as i64;
+	let start = Timestamp::from_second(slot_index(now, interval) * secs).expect("floored timestamp is in range");
+	format!("{start}/{secs}s")
+}
+
+/// Number of whole `interval`s since the epoch.
+fn slot_index(now: Timestamp, interval: Duration) -> i64 {
+	now.as_second().div_euclid(interval.as_secs().max(1) as i64)
+}
+
+/// Round-robin index for a slot without an aligned one: the same as `last`'s when resuming it, one past otherwise.
+fn next_slot_index(last: Option<&SlotMarker>, key: &str) -> i64 {
+	match last {
+		Some(m) if m.key == key => m.index,
+		Some(m) => m.index + 1,
+		None => 0,
+	}
+}
+
+/// Template to post this cycle; `None` only for an empty list.
+fn choose_template<'a>(templates: &'a [String], selection: TemplateSelection, slot_index: i64, rng: &mut impl rand::Rng) -> Option<&'a str> {
+	if templates.is_empty() {
+		return None;
+	}
+	let idx = match selection {
+		TemplateSelection::Random => rng.random_range(0..templates.len()),
+		TemplateSelection::RoundRobin => slot_index.rem_euclid(templates.len() as i64) as usize,
+	};
+	Some(&templates[idx])
+}
+
+/// Last slot we started posting for. `tweet_id` is only set once the post is confirmed.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+struct SlotMarker {
+	key: String,
+	/// Round-robin index the slot's template was picked with
+	#[serde(default)]
+	index: i64,
+	marked_at: Timestamp,
+	tweet_id: Option<String>,
+}
+
+#[derive(Debug, Default, Deserialize, Serialize)]
+struct PollSlotState {
+	last: Option<SlotMarker>,
+}
+
+fn slot_state_file() -> Result<std::path::PathBuf> {
+	Ok(xdg::BaseDirectories::with_prefix("social_networks").place_state_file("twitter_poll_slot.json")?)
+}
+
+fn load_slot_state(state_file: &Path) -> Result<PollSlotState> {
+	if !state_file.exists() {
+		return Ok(PollSlotState::default());
+	}
+	let content = std::fs::read_to_string(state_file)?;
+	Ok(serde_json::from_str(&content)?)
+}
+
+fn save_slot_state(state_file: &Path, state: &PollSlotState) -> Result<()> {
+	write_atomic(state_file, serde_json::to_string_pretty(state)?)?;
+	Ok(())
+}
+
+/// One posting attempt for a slot marked at `marked_at`. With `check_existing`, first looks for a poll the account
+/// posted since then (an earlier attempt that went through but whose response we lost) and returns its id instead.
+async fn post_poll_once(twitter_config: &TwitterConfig, template: &str, marked_at: Timestamp, check_existing: bool) -> Result<String, ScheduleError> {
+	if check_existing && let Some(tweet_id) = find_posted_poll(twitter_config, marked_at).await? {
+		info!("slot poll already live tweet_id={tweet_id}");
+		println!("Found poll from an earlier attempt (tweet {tweet_id}), not re-posting");
+		return Ok(tweet_id);
+	}
+	post_poll(twitter_config, template).await
+}
+
+#[instrument(skip(twitter_config))]
+async fn find_posted_poll(twitter_config: &TwitterConfig, since: Timestamp) -> Result<Option<String>, ScheduleError> {
+	let oauth = twitter_config
+		.oauth
+		.as_ref()
+		.ok_or_else(|| ScheduleError::Unhandled("twitter.oauth config not found".to_string()))?;
+	let client = http::client();
+	let bearer = format!("Bearer {}", twitter_config.bearer_token);
+
+	let url = format!("https://api.twitter.com/2/users/by/username/{}", oauth.acc_username);
+	let response = client.get(&url).header("Authorization", &bearer).send().await.context("Failed to look up own account")?;
+	let user: UserLookupResponse = ok_or_classify(response).await?.json().await.context("Failed to parse user lookup")?;
+
+	let url = format!("https://api.twitter.com/2/users/{}/tweets", user.data.id);
+	let response = client
+		.get(&url)
+		.query(&[("max_results", "5"), ("tweet.fields", "created_at,attachments")])
+		.header("Authorization", &bearer)
+		.send()
+		.await
+		.context("Failed to get own recent tweets")?;
+	let tweets: RecentTweetsResponse = ok_or_classify(response).await?.json().await.context("Failed to parse own recent tweets")?;
+
+	Ok(poll_posted_since(&tweets.data, since).map(str::to_owned))
+}
+
+/// Id of the newest poll tweet created at or after `since`.
+fn poll_posted_since(tweets: &[RecentTweet], since: Timestamp) -> Option<&str> {
+	tweets
+		.iter()
+		.find(|t| t.created_at >= since && t.attachments.as_ref().is_some_and(|a| !a.poll_ids.is_empty()))
+		.map(|t| t.id.as_str())
+}
+
+async fn ok_or_classify(response: reqwest::Response) -> Result<reqwest::Response, ScheduleError> {
+	let status = response.status();
+	if status.is_success() {
+		return Ok(response);
+	}
+	let retry_after = rate_limit_reset_in(response.headers());
+	let body = response.text().await.unwrap_or_default();
+	Err(classify_api_error(status.as_u16(), &body, retry_after))
+}
+
+fn classify_api_error(status: u16, body: &str, retry_after: Option<Duration>) -> ScheduleError {
+	let detail = format!("Twitter API error (status {status}): {body}");
+	// Comes back as a 403, so must be told apart from a real auth failure
+	if is_duplicate_content(body) {
+		return ScheduleError::Duplicate(detail);
 	}
+	match status {
+		401 | 403 => ScheduleError::Auth(detail),
+		429 => ScheduleError::RateLimited { retry_after, detail },
+		_ => ScheduleError::Unhandled(detail),
+	}
+}
+
+/// v2 says "You are not allowed to create a Tweet with duplicate content."; v1.1 used error code 187.
+fn is_duplicate_content(body: &str) -> bool {
+	if body.to_lowercase().contains("duplicate content") {
+		return true;
+	}
+	serde_json::from_str::<serde_json::Value>(body)
+		.ok()
+		.and_then(|v| v.get("errors")?.as_array().cloned())
+		.is_some_and(|errors| errors.iter().any(|e| e.get("code").and_then(serde_json::Value::as_i64) == Some(187)))
 }
 
 #[instrument(skip(twitter_config))]
-async fn post_poll(twitter_config: &TwitterConfig) -> Result<(), ScheduleError> {
+async fn post_poll(twitter_config: &TwitterConfig, template: &str) -> Result<String, ScheduleError> {
 	let oauth = twitter_config
 		.oauth
 		.as_ref()
@@ -162,30 +751,153 @@ async fn post_poll(twitter_config: &TwitterConfig) -> Result<(), ScheduleError>
 	info!("account={}", oauth.acc_username);
 	println!("Posting poll from account: {}", oauth.acc_username);
 
-	// Parse poll text and extract options with lazy variable resolution
-	let (tweet_text, poll_options) = parse_poll_text_async(&poll_config.text, poll_config).await?;
-	let duration_minutes = poll_config.duration_hours * 60;
+	let thread = build_poll_thread(poll_config, template).await?;
+	let request = &thread.lead;
 
-	let request = CreateTweetRequest {
-		text: tweet_text.clone(),
-		poll: Some(PollOptions {
-			duration_minutes,
-			options: poll_options.clone(),
-		}),
+	let auth = match oauth2_credentials(oauth)? {
+		Some(creds) => TweetAuth::Bearer(oauth2_access_token(&creds).await?),
+		None => TweetAuth::OAuth1(oauth),
+	};
+	let post = |request| post_tweet(&auth, request);
+	let response = match post(request).await {
+		Err(ScheduleError::Duplicate(detail)) => {
+			info!("duplicate_content, retrying with disambiguator: {detail}");
+			println!("Twitter rejected the poll as duplicate content, retrying with a timestamp appended");
+			post(&CreateTweetRequest {
+				text: disambiguate(&request.text, Timestamp::now()),
+				poll: request.poll.clone(),
+				reply_to: None,
+			})
+			.await?
+		}
+		r => r?,
 	};
-
-	let response = post_tweet(&oauth.api_key, &oauth.api_key_secret, &oauth.access_token, &oauth.access_token_secret, &request).await?;
 
 	info!("posted tweet_id={} text={}", response.data.id, response.data.text);
 	println!("Tweet ID: {}", response.data.id);
 
+	// A plain tweet has no poll to follow up on
+	if let Some(poll) = &request.poll
+		&& let Err(e) = record_posted_poll(&response.data.id, &response.data.text, &poll.options).await
+	{
+		error!("Failed to record posted poll {}: {e:#}", response.data.id);
+	}
+
+	post_thread_followups(&auth, &response.data.id, &thread.followups).await?;
+
+	Ok(response.data.id)
+}
+
+/// Reply to `lead_id` with each follow-up in turn, each under the previous one. The poll is already live, so a failed
+/// follow-up is logged and ends the thread early instead of failing (and re-posting) the poll; only auth errors escape.
+async fn post_thread_followups(auth: &TweetAuth<'_>, lead_id: &str, followups: &[String]) -> Result<(), ScheduleError> {
+	let mut parent = lead_id.to_string();
+	for (i, text) in followups.iter().enumerate() {
+		let request = CreateTweetRequest {
+			text: text.clone(),
+			poll: None,
+			reply_to: Some(ReplyTo {
+				in_reply_to_tweet_id: parent.clone(),
+			}),
+		};
+		let detail = match post_tweet(auth, &request).await {
+			Ok(response) => {
+				info!("posted followup {}/{} tweet_id={}", i + 1, followups.len(), response.data.id);
+				println!("Follow-up {} ID: {}", i + 1, response.data.id);
+				parent = response.data.id;
+				continue;
+			}
+			Err(ScheduleError::Auth(detail)) => return Err(ScheduleError::Auth(detail)),
+			Err(ScheduleError::Duplicate(detail) | ScheduleError::RateLimited { detail, .. } | ScheduleError::Unhandled(detail)) => detail,
+		};
+		error!("followup_failed {}/{} in_reply_to={parent} error={detail}", i + 1, followups.len());
+		println!("✗ Failed to post follow-up {} of {}, leaving the thread short", i + 1, followups.len());
+		break;
+	}
 	Ok(())
 }
 
+/// The tweet is already live, so failing to record it must not fail the post.
+async fn record_posted_poll(tweet_id: &str, text: &str, options: &[String]) -> Result<()> {
+	let db = Database::try_new().await?;
+	db.record_posted_poll(tweet_id, text, options, &db_timestamp(Timestamp::now())).await
+}
+
+/// What `post_poll` posts for `template`: the poll, then `thread_followups` as replies.
+#[derive(Debug, Serialize)]
+struct PollThread {
+	lead: CreateTweetRequest,
+	followups: Vec<String>,
+}
+
+/// Resolves variables once for the whole thread, so e.g. `${btc_price}` reads the same in the poll and its replies.
+async fn build_poll_thread(poll_config: &TwitterPollConfig, template: &str) -> Result<PollThread> {
+	let texts: Vec<&str> = std::iter::once(template).chain(poll_config.thread_followups.iter().map(String::as_str)).collect();
+	let variables = resolve_variables(&texts, poll_config).await?;
+	let (text, options) = match poll_config.allow_plain_tweets {
+		true => match split_poll_text(template, &variables)? {
+			(text, options) if text.is_empty() && options.is_empty() => bail!("Template is empty"),
+			parsed => parsed,
+		},
+		false => parse_poll_text(template, &variables)?,
+	};
+	let followups = poll_config
+		.thread_followups
+		.iter()
+		.enumerate()
+		.map(|(i, followup)| parse_followup_text(followup, &variables).wrap_err_with(|| format!("twitter.poll.thread_followups[{i}]")))
+		.collect::<Result<_>>()?;
+	Ok(PollThread {
+		lead: CreateTweetRequest {
+			text,
+			poll: (!options.is_empty()).then(|| PollOptions {
+				duration_minutes: poll_config.duration_hours * 60,
+				options,
+			}),
+			reply_to: None,
+		},
+		followups,
+	})
+}
+
+/// How `post_tweet` authenticates as the posting account.
+enum TweetAuth<'a> {
+	/// Signed per request with the app's and the user's key pairs
+	OAuth1(&'a TwitterOauthConfig),
+	/// OAuth 2.0 user-context access token
+	Bearer(String),
+}
+
 #[instrument(skip_all)]
-async fn post_tweet(api_key: &str, api_key_secret: &str, access_token: &str, access_token_secret: &str, tweet: &CreateTweetRequest) -> Result<CreateTweetResponse, ScheduleError> {
+async fn post_tweet(auth: &TweetAuth<'_>, tweet: &CreateTweetRequest) -> Result<CreateTweetResponse, ScheduleError> {
 	let url = "https://api.twitter.com/2/tweets";
-	let method = "POST";
+	let auth_header = match auth {
+		TweetAuth::OAuth1(oauth) => oauth1_header("POST", url, oauth)?,
+		TweetAuth::Bearer(token) => format!("Bearer {token}"),
+	};
+
+	// Make the request
+	let client = http::client();
+	let response = client
+		.post(url)
+		.header("Authorization", auth_header)
+		.header("Content-Type", "application/json")
+		.json(tweet)
+		.send()
+		.await
+		.context("Failed to send tweet request")?;
+
+	let response_text = ok_or_classify(response).await?.text().await.context("Failed to read response body")?;
+
+	let tweet_response: CreateTweetResponse = serde_json::from_str(&response_text).context("Failed to parse tweet response")?;
+
+	Ok(tweet_response)
+}
+
+/// OAuth 1.0a HMAC-SHA1 `Authorization` header for a request with a JSON body (so only oauth params are signed).
+fn oauth1_header(method: &str, url: &str, oauth: &TwitterOauthConfig) -> Result<String> {
+	let (api_key, api_key_secret) = (oauth.api_key.as_str(), oauth.api_key_secret.as_str());
+	let (access_token, access_token_secret) = (oauth.access_token.as_str(), oauth.access_token_secret.as_str());
 
 	// Generate OAuth parameters
 	let timestamp = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs().to_string();
@@ -221,36 +933,113 @@ async fn post_tweet(api_key: &str, api_key_secret: &str, access_token: &str, acc
 	let mut auth_header_params = oauth_params.clone();
 	auth_header_params.insert("oauth_signature", &signature);
 
-	let auth_header = format!(
+	Ok(format!(
 		"OAuth {}",
 		auth_header_params.iter().map(|(k, v)| format!(r#"{}="{}""#, k, percent_encode(v))).collect::<Vec<_>>().join(", ")
-	);
+	))
+}
 
-	// Make the request
-	let client = reqwest::Client::new();
-	let response = client
-		.post(url)
-		.header("Authorization", auth_header)
-		.header("Content-Type", "application/json")
-		.json(tweet)
-		.send()
-		.await
-		.context("Failed to send tweet request")?;
+const OAUTH2_TOKEN_URL: &str = "https://api.twitter.com/2/oauth2/token";
+/// Refresh this long before the access token's stated expiry, so it can't lapse mid-request.
+const OAUTH2_EXPIRY_MARGIN: jiff::SignedDuration = jiff::SignedDuration::from_mins(5);
 
-	let status = response.status();
-	let response_text = response.text().await.context("Failed to read response body")?;
+struct OAuth2Credentials<'a> {
+	client_id: &'a str,
+	client_secret: Option<&'a str>,
+	refresh_token: &'a str,
+}
 
-	if !status.is_success() {
-		let detail = format!("Twitter API error (status {status}): {response_text}");
-		if matches!(status.as_u16(), 401 | 403) {
-			return Err(ScheduleError::Auth(detail));
-		}
-		return Err(ScheduleError::Unhandled(detail));
+/// `Some` when the config opts into OAuth 2.0; OAuth 1.0a stays the default.
+fn oauth2_credentials(oauth: &TwitterOauthConfig) -> Result<Option<OAuth2Credentials<'_>>> {
+	match (&oauth.client_id, &oauth.refresh_token) {
+		(Some(client_id), Some(refresh_token)) => Ok(Some(OAuth2Credentials {
+			client_id,
+			client_secret: oauth.client_secret.as_deref(),
+			refresh_token,
+		})),
+		(None, None) => Ok(None),
+		_ => bail!("twitter.oauth.client_id and twitter.oauth.refresh_token must be set together to post with OAuth 2.0"),
 	}
+}
 
-	let tweet_response: CreateTweetResponse = serde_json::from_str(&response_text).context("Failed to parse tweet response")?;
+/// Access token from the last refresh. Twitter refresh tokens are single use, so the rotated one is kept here
+/// rather than in the config.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+struct OAuth2Token {
+	access_token: String,
+	refresh_token: String,
+	expires_at: Timestamp,
+	/// Config refresh token this chain of refreshes started from; putting a new one in the config restarts it.
+	seeded_from: String,
+}
 
-	Ok(tweet_response)
+impl OAuth2Token {
+	fn is_fresh(&self, creds: &OAuth2Credentials, now: Timestamp) -> bool {
+		self.seeded_from == creds.refresh_token && now < self.expires_at - OAUTH2_EXPIRY_MARGIN
+	}
+}
+
+/// Refresh token to redeem next: the latest rotated one, unless the config has moved on to a new seed.
+fn next_refresh_token<'a>(cached: Option<&'a OAuth2Token>, creds: &OAuth2Credentials<'a>) -> &'a str {
+	cached.filter(|t| t.seeded_from == creds.refresh_token).map_or(creds.refresh_token, |t| t.refresh_token.as_str())
+}
+
+#[derive(Debug, Deserialize)]
+struct OAuth2TokenResponse {
+	access_token: String,
+	expires_in: i64,
+	refresh_token: Option<String>,
+}
+
+fn oauth2_token_file() -> Result<std::path::PathBuf> {
+	Ok(xdg::BaseDirectories::with_prefix("social_networks").place_state_file("twitter_oauth2_token.json")?)
+}
+
+/// A usable OAuth 2.0 access token, redeeming the refresh token when the cached one is missing or about to expire.
+#[instrument(skip_all)]
+async fn oauth2_access_token(creds: &OAuth2Credentials<'_>) -> Result<String, ScheduleError> {
+	let token_file = oauth2_token_file()?;
+	let cached: Option<OAuth2Token> = match std::fs::read_to_string(&token_file) {
+		Ok(content) => Some(serde_json::from_str(&content).context("Malformed Twitter OAuth2 token file")?),
+		Err(e) if e.kind() == std::io::ErrorKind::NotFound => None,
+		Err(e) => return Err(e.into()),
+	};
+	let now = Timestamp::now();
+	if let Some(token) = cached.as_ref().filter(|t| t.is_fresh(creds, now)) {
+		return Ok(token.access_token.clone());
+	}
+
+	let refresh_token = next_refresh_token(cached.as_ref(), creds);
+	let mut request = http::client()
+		.post(OAUTH2_TOKEN_URL)
+		.form(&[("grant_type", "refresh_token"), ("refresh_token", refresh_token), ("client_id", creds.client_id)]);
+	if let Some(secret) = creds.client_secret {
+		request = request.basic_auth(creds.client_id, Some(secret));
+	}
+	let response = request.send().await.context("Failed to reach Twitter's OAuth2 token endpoint")?;
+	let status = response.status();
+	if !status.is_success() {
+		let body = response.text().await.unwrap_or_default();
+		let detail = format!("Twitter OAuth2 token refresh failed (status {status}): {body}");
+		// A spent or revoked refresh token comes back as a 400 `invalid_request`; only a new authorization fixes it
+		return Err(if matches!(status.as_u16(), 400 | 401 | 403) {
+			ScheduleError::Auth(detail)
+		} else {
+			ScheduleError::Unhandled(detail)
+		});
+	}
+	let fresh: OAuth2TokenResponse = response.json().await.context("Failed to parse OAuth2 token response")?;
+
+	let token = OAuth2Token {
+		access_token: fresh.access_token,
+		refresh_token: fresh.refresh_token.unwrap_or_else(|| refresh_token.to_string()),
+		expires_at: now + jiff::SignedDuration::from_secs(fresh.expires_in),
+		seeded_from: creds.refresh_token.to_string(),
+	};
+	// The refresh token is single-use: losing the file mid-write would need a new authorization
+	write_private(&token_file, serde_json::to_string_pretty(&token)?)?;
+	info!("refreshed oauth2 access token, expires_at={}", token.expires_at);
+	Ok(token.access_token)
 }
 
 /// Variables provider with lazy async evaluation
@@ -259,12 +1048,9 @@ struct VariableProvider {
 	config: TwitterPollConfig,
 }
 impl VariableProvider {
-	async fn btc_price(&self) -> Result<String> {
-		let price = btc_price(self.config.num_of_retries).await?;
-		let rounded_to_100 = ((price + 50) / 100) * 100;
-		let s = format_num_with_thousands(rounded_to_100, ",");
-
-		Ok(s)
+	async fn coin_price(&self, symbol: &str) -> Result<String> {
+		let price = coin_price(symbol, self.config.num_of_retries).await?;
+		Ok(format_price(price))
 	}
 
 	async fn date(&self) -> Result<String> {
@@ -276,67 +1062,97 @@ impl VariableProvider {
 
 	#[instrument(skip(self))]
 	async fn resolve(&self, variable_name: &str) -> Result<String> {
-		match variable_name {
-			"btc_price" => self.btc_price().await,
-			"date" => self.date().await,
-			_ => Err(eyre!("Unknown variable: {variable_name}")),
+		match Variable::parse(variable_name) {
+			Some(Variable::Price(symbol)) => self.coin_price(&symbol).await,
+			Some(Variable::Date) => self.date().await,
+			None => Err(eyre!("Unknown variable: {variable_name}")),
 		}
 	}
 }
 
-/// Extract variable names from text (finds all ${var_name} patterns)
-fn extract_variable_names(text: &str) -> Vec<String> {
-	let mut variables = Vec::new();
-	let mut chars = text.chars().peekable();
-
-	while let Some(ch) = chars.next() {
-		if ch == '$' && chars.peek() == Some(&'{') {
-			chars.next(); // consume '{'
-			let mut var_name = String::new();
-			while let Some(&c) = chars.peek() {
-				if c == '}' {
-					chars.next(); // consume '}'
-					variables.push(var_name);
-					break;
-				} else {
-					var_name.push(c);
-					chars.next();
-				}
-			}
-		}
+/// What a `${...}` placeholder in a poll template stands for.
+#[derive(Debug, PartialEq, Eq)]
+enum Variable {
+	Date,
+	/// Coin symbol, uppercased. Written `${price:ETH}`, or `${eth_price}` as shorthand.
+	Price(String),
+}
+impl Variable {
+	fn parse(name: &str) -> Option<Self> {
+		let symbol = match name.strip_prefix("price:") {
+			Some(symbol) => symbol,
+			None if name == "date" => return Some(Variable::Date),
+			None => name.strip_suffix("_price")?,
+		};
+		let symbol = symbol.trim();
+		(!symbol.is_empty() && symbol.chars().all(|c| c.is_ascii_alphanumeric())).then(|| Variable::Price(symbol.to_uppercase()))
 	}
-
-	variables
 }
 
+/// Values for every `${...}` placeholder appearing in `texts`; only the variables actually used are resolved.
 #[instrument(skip(poll_config))]
-async fn parse_poll_text_async(text: &str, poll_config: &TwitterPollConfig) -> Result<(String, Vec<String>)> {
-	// First, extract variable names needed
-	let variable_names = extract_variable_names(text);
+async fn resolve_variables(texts: &[&str], poll_config: &TwitterPollConfig) -> Result<HashMap<String, String>> {
+	let mut variable_names = Vec::new();
+	for text in texts {
+		variable_names.extend(template::variable_names(text)?);
+	}
+	variable_names.sort();
+	variable_names.dedup();
 	info!(?variable_names);
 
-	// Resolve only the variables we need
 	let provider = VariableProvider { config: poll_config.clone() };
 	let mut variables = HashMap::new();
-
 	for var_name in variable_names {
 		let value = provider.resolve(&var_name).await?;
 		variables.insert(var_name, value);
 	}
+	Ok(variables)
+}
 
-	// Now parse the poll text
-	let result = parse_poll_text(text, &variables)?;
-	Ok(result)
+fn check_tweet_length(text: &str) -> Result<()> {
+	let length = weighted_tweet_length(text);
+	if length > MAX_TWEET_LENGTH {
+		bail!(
+			"Tweet text is {length} characters by Twitter's count, {} over the {MAX_TWEET_LENGTH} limit",
+			length - MAX_TWEET_LENGTH
+		);
+	}
+	Ok(())
+}
+
+/// A thread reply: plain text, variables substituted.
+fn parse_followup_text(text: &str, variables: &HashMap<String, String>) -> Result<String> {
+	let text = template::render(text.trim(), variables)?;
+	if text.is_empty() {
+		bail!("Follow-up tweet is empty");
+	}
+	check_tweet_length(&text)?;
+	Ok(text)
 }
 
 fn parse_poll_text(text: &str, variables: &HashMap<String, String>) -> Result<(String, Vec<String>)> {
+	let (tweet_text, poll_options) = split_poll_text(text, variables)?;
+	if poll_options.is_empty() {
+		bail!("No poll options found in text. Use '- [ ] option' format");
+	}
+	Ok((tweet_text, poll_options))
+}
+
+/// [`parse_poll_text`], except that zero options is fine: the template is then a plain tweet.
+fn split_poll_text(text: &str, variables: &HashMap<String, String>) -> Result<(String, Vec<String>)> {
+	// Malformed placeholders error here too, not only when resolving them
+	template::variable_names(text)?;
 	let mut tweet_lines = Vec::new();
 	let mut poll_options = Vec::new();
 
 	for line in text.lines() {
 		let trimmed = line.trim();
-		if let Some(option_text) = trimmed.strip_prefix("- [ ] ") {
+		if let Some(option_text) = trimmed.strip_prefix("- [ ]").filter(|rest| rest.is_empty() || rest.starts_with(char::is_whitespace)) {
 			// This is a poll option
+			let option_text = option_text.trim();
+			if option_text.is_empty() {
+				bail!("Poll option {} is empty", poll_options.len() + 1);
+			}
 			poll_options.push(option_text.to_string());
 		} else {
 			// This is part of the tweet text (including empty lines)
@@ -344,29 +1160,84 @@ fn parse_poll_text(text: &str, variables: &HashMap<String, String>) -> Result<(S
 		}
 	}
 
-	// Join tweet lines and perform variable substitution
-	let mut tweet_text = tweet_lines.join("\n");
-
-	// Trim trailing/leading whitespace from the entire tweet text
-	tweet_text = tweet_text.trim().to_string();
-
-	// Substitute variables
-	for (key, value) in variables {
-		let placeholder = format!("${{{key}}}");
-		tweet_text = tweet_text.replace(&placeholder, value);
-	}
-
-	if poll_options.is_empty() {
-		bail!("No poll options found in text. Use '- [ ] option' format");
-	}
+	// Join tweet lines, trim the whole text, then substitute variables
+	let tweet_text = template::render(tweet_lines.join("\n").trim(), variables)?;
 
 	if poll_options.len() > 4 {
 		bail!("Twitter polls support maximum 4 options, found {}", poll_options.len());
 	}
 
+	check_tweet_length(&tweet_text)?;
+
 	Ok((tweet_text, poll_options))
 }
 
+const MAX_TWEET_LENGTH: usize = 280;
+/// Every URL counts as this many characters, whatever its length (t.co wrapping).
+const TWEET_URL_LENGTH: usize = 23;
+
+/// Length as Twitter counts it (twitter-text v3): Latin and common punctuation count 1, other code points
+/// (CJK, emoji, ...) count 2, `http(s)://` links count 23. Emoji sequences count 2 as a whole; this handles
+/// ZWJ sequences, variation selectors, skin-tone modifiers and flags, which covers what templates use.
+fn weighted_tweet_length(text: &str) -> usize {
+	let light = |c: char| matches!(c as u32, 0..=4351 | 8192..=8205 | 8208..=8223 | 8242..=8247);
+	let regional_indicator = |c: char| matches!(c as u32, 0x1f1e6..=0x1f1ff);
+
+	let mut length = 0;
+	let mut rest = text;
+	let mut prev: Option<char> = None;
+	let mut pending_flag_half = false;
+	while let Some(c) = rest.chars().next() {
+		if rest.starts_with("https://") || rest.starts_with("http://") {
+			let end = rest.find(char::is_whitespace).unwrap_or(rest.len());
+			length += TWEET_URL_LENGTH;
+			rest = &rest[end..];
+			prev = None;
+			continue;
+		}
+
+		let joined = prev == Some('\u{200D}');
+		let modifier = matches!(c, '\u{200D}' | '\u{FE0E}' | '\u{FE0F}' | '\u{1F3FB}'..='\u{1F3FF}');
+		if regional_indicator(c) {
+			// A flag is a pair of regional indicators and counts 2 in total
+			if !pending_flag_half {
+				length += 2;
+			}
+			pending_flag_half = !pending_flag_half;
+		} else if !joined && !modifier {
+			length += if light(c) { 1 } else { 2 };
+			pending_flag_half = false;
+		}
+
+		prev = Some(c);
+		rest = &rest[c.len_utf8()..];
+	}
+	length
+}
+
+/// Time left until the `x-rate-limit-reset` epoch-seconds header, if present and in the future.
+pub(crate) fn rate_limit_reset_in(headers: &reqwest::header::HeaderMap) -> Option<Duration> {
+	let reset: i64 = headers.get("x-rate-limit-reset")?.to_str().ok()?.parse().ok()?;
+	let secs = reset - Timestamp::now().as_second();
+	(secs > 0).then(|| Duration::from_secs(secs as u64))
+}
+
+/// Make `text` distinct from an earlier identical tweet by appending the posting time. If that would go over
+/// [`MAX_TWEET_LENGTH`], the end of `text` is cut to make room, marked with `…`.
+fn disambiguate(text: &str, now: Timestamp) -> String {
+	let stamp = strtime::format("%Y-%m-%d %H:%M UTC", &now.to_zoned(jiff::tz::TimeZone::UTC)).expect("valid format string");
+	let stamped = format!("{text}\n\n{stamp}");
+	if weighted_tweet_length(&stamped) <= MAX_TWEET_LENGTH {
+		return stamped;
+	}
+	let mut base = text.trim_end().to_string();
+	while !base.is_empty() && weighted_tweet_length(&format!("{base}…\n\n{stamp}")) > MAX_TWEET_LENGTH {
+		base.pop();
+		base.truncate(base.trim_end().len());
+	}
+	format!("{base}…\n\n{stamp}")
+}
+
 fn percent_encode(s: &str) -> String {
 	s.chars()
 		.map(|c| match c {
@@ -381,9 +1252,16 @@ struct CreateTweetRequest {
 	text: String,
 	#[serde(skip_serializing_if = "Option::is_none")]
 	poll: Option<PollOptions>,
+	#[serde(rename = "reply", skip_serializing_if = "Option::is_none")]
+	reply_to: Option<ReplyTo>,
 }
 
 #[derive(Debug, Serialize)]
+struct ReplyTo {
+	in_reply_to_tweet_id: String,
+}
+
+#[derive(Clone, Debug, Serialize)]
 struct PollOptions {
 	duration_minutes: u32,
 	options: Vec<String>,
@@ -400,6 +1278,35 @@ struct TweetData {
 	text: String,
 }
 
+#[derive(Debug, Deserialize)]
+struct UserLookupResponse {
+	data: UserLookupData,
+}
+
+#[derive(Debug, Deserialize)]
+struct UserLookupData {
+	id: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct RecentTweetsResponse {
+	#[serde(default)]
+	data: Vec<RecentTweet>,
+}
+
+#[derive(Debug, Deserialize)]
+struct RecentTweet {
+	id: String,
+	created_at: Timestamp,
+	attachments: Option<TweetAttachments>,
+}
+
+#[derive(Debug, Deserialize)]
+struct TweetAttachments {
+	#[serde(default)]
+	poll_ids: Vec<String>,
+}
+
 #[cfg(test)]
 mod tests {
 	use super::*;
@@ -409,6 +1316,7 @@ mod tests {
 		let request = CreateTweetRequest {
 			text: "hello world".to_string(),
 			poll: None,
+			reply_to: None,
 		};
 		let json = serde_json::to_string(&request).unwrap();
 		insta::assert_snapshot!(json, @r#"{"text":"hello world"}"#);
@@ -422,11 +1330,60 @@ mod tests {
 				duration_minutes: 1440,
 				options: vec!["yes".to_string(), "yes".to_string()],
 			}),
+			reply_to: None,
 		};
 		let json = serde_json::to_string(&request).unwrap();
 		insta::assert_snapshot!(json, @r#"{"text":"rust go brrr?","poll":{"duration_minutes":1440,"options":["yes","yes"]}}"#);
 	}
 
+	#[test]
+	fn test_oauth2_token_refresh() {
+		let creds = OAuth2Credentials {
+			client_id: "client",
+			client_secret: None,
+			refresh_token: "seed",
+		};
+		let now: Timestamp = "2026-03-01T12:00:00Z".parse().unwrap();
+		let token = OAuth2Token {
+			access_token: "access".to_string(),
+			refresh_token: "rotated".to_string(),
+			expires_at: "2026-03-01T14:00:00Z".parse().unwrap(),
+			seeded_from: "seed".to_string(),
+		};
+
+		assert!(token.is_fresh(&creds, now));
+		assert!(!token.is_fresh(&creds, "2026-03-01T13:56:00Z".parse().unwrap()));
+		assert_eq!(next_refresh_token(Some(&token), &creds), "rotated");
+		assert_eq!(next_refresh_token(None, &creds), "seed");
+
+		// A new refresh token in the config replaces the cached chain
+		let reseeded = OAuth2Credentials { refresh_token: "new seed", ..creds };
+		assert!(!token.is_fresh(&reseeded, now));
+		assert_eq!(next_refresh_token(Some(&token), &reseeded), "new seed");
+	}
+
+	#[test]
+	fn test_oauth2_credentials() {
+		let mut oauth = TwitterOauthConfig {
+			acc_username: "me".to_string(),
+			api_key: "k".to_string(),
+			api_key_secret: "ks".to_string(),
+			access_token: "t".to_string(),
+			access_token_secret: "ts".to_string(),
+			client_id: None,
+			client_secret: None,
+			refresh_token: None,
+		};
+		assert!(oauth2_credentials(&oauth).unwrap().is_none());
+
+		oauth.client_id = Some("client".to_string());
+		assert!(oauth2_credentials(&oauth).err().unwrap().to_string().contains("must be set together"));
+
+		oauth.refresh_token = Some("seed".to_string());
+		let creds = oauth2_credentials(&oauth).unwrap().unwrap();
+		assert_eq!((creds.client_id, creds.client_secret, creds.refresh_token), ("client", None, "seed"));
+	}
+
 	#[test]
 	fn test_percent_encode() {
 		assert_eq!(percent_encode("hello world"), "hello%20world");
@@ -446,14 +1403,40 @@ mod tests {
 	#[test]
 	fn test_extract_variable_names() {
 		let text = "Price: ${btc_price}, Date: ${date}";
-		let vars = extract_variable_names(text);
+		let vars = template::variable_names(text).unwrap();
 		assert_eq!(vars, vec!["btc_price", "date"]);
 	}
 
+	#[test]
+	fn test_extract_price_variables() {
+		let text = "BTC ${btc_price}, ETH ${eth_price}, SOL ${price:SOL}, ${price:doge}";
+		let vars = template::variable_names(text).unwrap();
+		assert_eq!(vars, vec!["btc_price", "eth_price", "price:SOL", "price:doge"]);
+		let parsed: Vec<_> = vars.iter().map(|v| Variable::parse(v)).collect();
+		assert_eq!(
+			parsed,
+			vec![
+				Some(Variable::Price("BTC".to_string())),
+				Some(Variable::Price("ETH".to_string())),
+				Some(Variable::Price("SOL".to_string())),
+				Some(Variable::Price("DOGE".to_string())),
+			]
+		);
+	}
+
+	#[test]
+	fn test_parse_variable() {
+		assert_eq!(Variable::parse("date"), Some(Variable::Date));
+		assert_eq!(Variable::parse("price:"), None);
+		assert_eq!(Variable::parse("_price"), None);
+		assert_eq!(Variable::parse("price:ETH-USDT"), None);
+		assert_eq!(Variable::parse("weather"), None);
+	}
+
 	#[test]
 	fn test_extract_variable_names_empty() {
 		let text = "No variables here";
-		let vars = extract_variable_names(text);
+		let vars = template::variable_names(text).unwrap();
 		assert!(vars.is_empty());
 	}
 
@@ -497,6 +1480,127 @@ for ref, current price: ${btc_price}
 		assert_eq!(options, vec!["buy", "sell"]);
 	}
 
+	#[test]
+	fn test_weighted_tweet_length() {
+		assert_eq!(weighted_tweet_length("btc up or down?"), 15);
+		assert_eq!(weighted_tweet_length("café — “quoted”"), 15);
+		assert_eq!(weighted_tweet_length("日本語"), 6);
+
+		// Emoji count 2 each, including ZWJ families, skin tones, variation selectors and flags
+		assert_eq!(weighted_tweet_length("🚀"), 2);
+		assert_eq!(weighted_tweet_length("👨\u{200D}👩\u{200D}👧"), 2);
+		assert_eq!(weighted_tweet_length("👍🏽"), 2);
+		assert_eq!(weighted_tweet_length("❤\u{FE0F}"), 2);
+		assert_eq!(weighted_tweet_length("🇺🇸🇯🇵"), 4);
+		assert_eq!(weighted_tweet_length("up 🚀 or down 📉?"), 17);
+
+		// URLs count 23 no matter their length
+		assert_eq!(weighted_tweet_length("https://t.co"), 23);
+		let long_url = format!("https://example.com/{}", "a".repeat(200));
+		assert_eq!(weighted_tweet_length(&format!("chart: {long_url} and http://x.io/y")), 7 + 23 + 5 + 23);
+	}
+
+	#[test]
+	fn test_parse_poll_text_too_long() {
+		let at_limit = format!("{}\n- [ ] up\n- [ ] down", "a".repeat(MAX_TWEET_LENGTH));
+		assert!(parse_poll_text(&at_limit, &HashMap::new()).is_ok());
+
+		let over = format!("{}🚀\n- [ ] up\n- [ ] down", "a".repeat(MAX_TWEET_LENGTH - 1));
+		let err = parse_poll_text(&over, &HashMap::new()).unwrap_err();
+		assert_eq!(err.to_string(), "Tweet text is 281 characters by Twitter's count, 1 over the 280 limit");
+
+		// Links are cheap, so a template full of them still fits
+		let links = format!("{}\n- [ ] up\n- [ ] down", vec![format!("https://example.com/{}", "x".repeat(100)); 11].join(" "));
+		assert!(parse_poll_text(&links, &HashMap::new()).is_ok());
+	}
+
+	#[test]
+	fn test_parse_poll_text_edge_cases() {
+		// CRLF line endings and padded checkboxes
+		let (tweet_text, options) = parse_poll_text("up or down?\r\n- [ ]   up  \r\n\t- [ ] down\r\n", &HashMap::new()).unwrap();
+		assert_eq!((tweet_text.as_str(), options), ("up or down?", vec!["up".to_string(), "down".to_string()]));
+
+		let err = parse_poll_text("pick\n- [ ] up\n- [ ]   \n", &HashMap::new()).unwrap_err();
+		assert_eq!(err.to_string(), "Poll option 2 is empty");
+		// Not a checkbox, just text that starts like one
+		assert_eq!(parse_poll_text("- [ ]x\n- [ ] a\n- [ ] b", &HashMap::new()).unwrap().0, "- [ ]x");
+	}
+
+	#[test]
+	fn test_escaped_placeholder() {
+		let template = "Do you write $${btc_price} or ${btc_price}? $$${x}\n- [ ] up\n- [ ] down";
+		assert_eq!(template::variable_names(template).unwrap(), vec!["btc_price"]);
+		let variables = HashMap::from([("btc_price".to_string(), "$50000".to_string())]);
+		let (tweet_text, options) = parse_poll_text(template, &variables).unwrap();
+		assert_eq!(tweet_text, "Do you write ${btc_price} or $50000? $${x}");
+		assert_eq!(options, vec!["up", "down"]);
+		// An escaped `${` needs no closing brace
+		assert_eq!(template::render("literal $${ alone", &HashMap::new()).unwrap(), "literal ${ alone");
+		// Substituted values are not parsed again
+		let variables = HashMap::from([("a".to_string(), "${b}".to_string()), ("b".to_string(), "no".to_string())]);
+		assert_eq!(template::render("${a}", &variables).unwrap(), "${b}");
+	}
+
+	#[test]
+	fn test_malformed_placeholders() {
+		let err = |text| template::variable_names(text).unwrap_err().to_string();
+		assert_eq!(err("btc at ${unclosed"), "Unclosed placeholder `${unclosed`, expected `${name}` (or `$${` for a literal `${`)");
+		assert_eq!(
+			err("${unclosed and ${date}"),
+			"Unclosed placeholder `${unclosed and ${dat`, expected `${name}` (or `$${` for a literal `${`)"
+		);
+		assert_eq!(err("${a\n}"), "Unclosed placeholder `${a`, expected `${name}` (or `$${` for a literal `${`)");
+		assert_eq!(err("${}"), "Empty placeholder `${}`");
+		// A lone `$` is plain text
+		assert_eq!(template::variable_names("costs $5, or $$5").unwrap(), Vec::<String>::new());
+		assert_eq!(template::variable_names("$").unwrap(), Vec::<String>::new());
+
+		let err = parse_poll_text("btc at ${btc_price\n- [ ] up\n- [ ] down", &HashMap::new()).unwrap_err();
+		assert_eq!(err.to_string(), "Unclosed placeholder `${btc_price`, expected `${name}` (or `$${` for a literal `${`)");
+	}
+
+	proptest::proptest! {
+		#[test]
+		fn prop_extract_variable_names_roundtrip(parts in proptest::collection::vec(("[^$]{0,20}", "[a-z0-9_:]{1,12}"), 0..6), tail in "[^$]{0,20}") {
+			let text: String = parts.iter().map(|(filler, name)| format!("{filler}${{{name}}}")).collect::<String>() + &tail;
+			let names: Vec<_> = parts.into_iter().map(|(_, name)| name).collect();
+			proptest::prop_assert_eq!(template::variable_names(&text).unwrap(), names);
+		}
+
+		#[test]
+		fn prop_unclosed_placeholder_errors(before in "[^$]{0,20}", unclosed in "[^$}?]{0,20}", name in "[a-z0-9_:]{1,12}") {
+			let text = format!("{before}${{{unclosed}${{{name}}}");
+			proptest::prop_assert!(template::variable_names(&text).unwrap_err().to_string().starts_with("Unclosed placeholder"));
+			// Unclosed at the very end
+			let text = format!("{before}${{{name}}}${{{unclosed}");
+			proptest::prop_assert!(template::variable_names(&text).unwrap_err().to_string().starts_with("Unclosed placeholder"));
+		}
+
+		#[test]
+		fn prop_poll_options_respect_cap(
+			question in "[a-z ?]{1,40}",
+			options in proptest::collection::vec(("[a-z]{1,10}", "[ \t]{0,3}", proptest::bool::ANY), 0..8),
+		) {
+			let mut text = format!("{question}\n");
+			for (option, padding, crlf) in &options {
+				text.push_str(&format!("{padding}- [ ]{padding} {option}{padding}{}", if *crlf { "\r\n" } else { "\n" }));
+			}
+			match parse_poll_text(&text, &HashMap::new()) {
+				Ok((_, parsed)) => {
+					proptest::prop_assert!((1..=4).contains(&options.len()));
+					proptest::prop_assert_eq!(parsed, options.into_iter().map(|(option, ..)| option).collect::<Vec<_>>());
+				}
+				Err(_) => proptest::prop_assert!(options.is_empty() || options.len() > 4),
+			}
+		}
+
+		#[test]
+		fn prop_parsers_never_panic(text in "(\\PC|\\$\\{|\\}|- \\[ \\]|\r\n|\n){0,60}") {
+			let _ = template::variable_names(&text);
+			let _ = parse_poll_text(&text, &HashMap::from([("date".to_string(), "${".to_string())]));
+		}
+	}
+
 	#[test]
 	fn test_parse_poll_text_no_options() {
 		let text = "just text, no options";
@@ -520,38 +1624,423 @@ for ref, current price: ${btc_price}
 		assert!(result.unwrap_err().to_string().contains("maximum 4 options"));
 	}
 
+	#[tokio::test(start_paused = true)]
+	async fn test_post_with_retries_waits_between_attempts() {
+		let mut calls: Vec<tokio::time::Instant> = Vec::new();
+		let posted = post_with_retries(3, || {
+			calls.push(tokio::time::Instant::now());
+			let n = calls.len();
+			async move { if n < 3 { Err(ScheduleError::Unhandled("boom".to_string())) } else { Ok(()) } }
+		})
+		.await;
+		assert!(matches!(posted, Ok(Some(()))));
+		assert_eq!(calls.len(), 3);
+		assert!(calls[1] - calls[0] >= Duration::from_secs(1));
+		assert!(calls[2] - calls[1] > calls[1] - calls[0]);
+	}
+
+	#[tokio::test(start_paused = true)]
+	async fn test_post_with_retries_honors_rate_limit_reset() {
+		let mut calls: Vec<tokio::time::Instant> = Vec::new();
+		let posted = post_with_retries(2, || {
+			calls.push(tokio::time::Instant::now());
+			let n = calls.len();
+			async move {
+				if n == 1 {
+					Err(ScheduleError::RateLimited {
+						retry_after: Some(Duration::from_secs(120)),
+						detail: "429".to_string(),
+					})
+				} else {
+					Ok(())
+				}
+			}
+		})
+		.await;
+		assert!(matches!(posted, Ok(Some(()))));
+		assert_eq!(calls[1] - calls[0], Duration::from_secs(120));
+	}
+
+	#[tokio::test(start_paused = true)]
+	async fn test_post_with_retries_gives_up_and_stops_on_auth() {
+		let posted = post_with_retries(2, || async { Err::<(), _>(ScheduleError::Unhandled("boom".to_string())) }).await;
+		assert!(matches!(posted, Ok(None::<()>)));
+
+		let mut n = 0;
+		let posted = post_with_retries(5, || {
+			n += 1;
+			async { Err::<(), _>(ScheduleError::Auth("401".to_string())) }
+		})
+		.await;
+		assert!(matches!(posted, Err(ScheduleError::Auth(_))));
+		assert_eq!(n, 1);
+	}
+
+	#[test]
+	fn test_slot_key() {
+		let hour = Duration::from_secs(3600);
+		let at = |s: &str| s.parse::<Timestamp>().unwrap();
+		assert_eq!(slot_key(at("2026-03-01T14:05:00Z"), hour), "2026-03-01T14:00:00Z/3600s");
+		assert_eq!(slot_key(at("2026-03-01T14:05:00Z"), hour), slot_key(at("2026-03-01T14:59:59Z"), hour));
+		assert_ne!(slot_key(at("2026-03-01T14:59:59Z"), hour), slot_key(at("2026-03-01T15:00:00Z"), hour));
+		assert_eq!(slot_key(at("2026-03-01T14:05:00Z"), Duration::from_secs(86400)), "2026-03-01T00:00:00Z/86400s");
+	}
+
+	#[test]
+	fn test_poll_posted_since_detects_already_posted_slot() {
+		let json = r#"{
+			"data": [
+				{"id": "3", "created_at": "2026-03-01T14:06:10.000Z", "text": "gm"},
+				{"id": "2", "created_at": "2026-03-01T14:05:30.000Z", "text": "btc up or down?", "attachments": {"poll_ids": ["99"]}},
+				{"id": "1", "created_at": "2026-03-01T13:00:00.000Z", "text": "last hour", "attachments": {"poll_ids": ["98"]}}
+			]
+		}"#;
+		let tweets: RecentTweetsResponse = serde_json::from_str(json).unwrap();
+		let marked_at: Timestamp = "2026-03-01T14:05:00Z".parse().unwrap();
+		assert_eq!(poll_posted_since(&tweets.data, marked_at), Some("2"));
+
+		// The previous slot's poll does not count for a slot marked after it
+		let marked_at: Timestamp = "2026-03-01T14:06:00Z".parse().unwrap();
+		assert_eq!(poll_posted_since(&tweets.data, marked_at), None);
+
+		let empty: RecentTweetsResponse = serde_json::from_str(r#"{"meta": {"result_count": 0}}"#).unwrap();
+		assert_eq!(poll_posted_since(&empty.data, marked_at), None);
+	}
+
 	fn dummy_poll_config() -> TwitterPollConfig {
 		use v_utils::trades::Timeframe;
 		// Create a timeframe of 1 hour for testing
 		let schedule_every: Timeframe = serde_json::from_str("\"1h\"").unwrap();
 		TwitterPollConfig {
-			text: String::new(),
+			text: PollTemplates::One(String::new()),
+			text_selection: TemplateSelection::default(),
 			duration_hours: 24,
 			schedule_every,
+			schedule_cron: None,
 			num_of_retries: 3,
+			thread_followups: Vec::new(),
+			allow_plain_tweets: false,
 		}
 	}
 
 	#[tokio::test]
-	async fn test_parse_poll_text_async_with_date() {
+	async fn test_build_poll_thread_with_date() {
 		let text = r#"Today: ${date}
 - [ ] yes
 - [ ] no"#;
 		let config = dummy_poll_config();
-		let (tweet_text, options) = parse_poll_text_async(text, &config).await.unwrap();
-		assert!(tweet_text.starts_with("Today: "));
-		assert!(tweet_text.contains("UTC"));
-		assert_eq!(options, vec!["yes", "no"]);
+		let lead = build_poll_thread(&config, text).await.unwrap().lead;
+		assert!(lead.text.starts_with("Today: "));
+		assert!(lead.text.contains("UTC"));
+		assert_eq!(lead.poll.unwrap().options, vec!["yes", "no"]);
 	}
 
 	#[tokio::test]
-	async fn test_parse_poll_text_async_no_variables() {
+	async fn test_build_poll_thread_no_variables() {
 		let text = r#"Simple poll
 - [ ] option1
 - [ ] option2"#;
 		let config = dummy_poll_config();
-		let (tweet_text, options) = parse_poll_text_async(text, &config).await.unwrap();
-		assert_eq!(tweet_text, "Simple poll");
-		assert_eq!(options, vec!["option1", "option2"]);
+		let lead = build_poll_thread(&config, text).await.unwrap().lead;
+		assert_eq!(lead.text, "Simple poll");
+		assert_eq!(lead.poll.unwrap().options, vec!["option1", "option2"]);
+	}
+
+	#[tokio::test]
+	async fn test_build_poll_request() {
+		let config = dummy_poll_config();
+		let request = build_poll_thread(&config, "Simple poll\n- [ ] up\n- [ ] down").await.unwrap().lead;
+		insta::assert_snapshot!(serde_json::to_string_pretty(&request).unwrap(), @r#"
+		{
+		  "text": "Simple poll",
+		  "poll": {
+		    "duration_minutes": 1440,
+		    "options": [
+		      "up",
+		      "down"
+		    ]
+		  }
+		}
+		"#);
+	}
+
+	#[tokio::test]
+	async fn test_build_plain_tweet() {
+		let mut config = dummy_poll_config();
+		let err = build_poll_thread(&config, "gm, no poll today").await.unwrap_err();
+		assert!(err.to_string().contains("No poll options found"));
+
+		config.allow_plain_tweets = true;
+		let request = build_poll_thread(&config, "gm, no poll today").await.unwrap().lead;
+		insta::assert_snapshot!(serde_json::to_string_pretty(&request).unwrap(), @r#"
+		{
+		  "text": "gm, no poll today"
+		}
+		"#);
+	}
+
+	#[tokio::test]
+	async fn test_build_poll_thread() {
+		let mut config = dummy_poll_config();
+		config.thread_followups = vec!["  context: ${date}  ".to_string(), "results in 24h".to_string()];
+		let thread = build_poll_thread(&config, "Poll of ${date}\n- [ ] up\n- [ ] down").await.unwrap();
+		let date = thread.lead.text.strip_prefix("Poll of ").unwrap();
+		assert_eq!(thread.followups, [format!("context: {date}"), "results in 24h".to_string()]);
+
+		config.thread_followups = vec!["ok".to_string(), " ".to_string()];
+		let err = build_poll_thread(&config, "Poll\n- [ ] up\n- [ ] down").await.unwrap_err();
+		assert_eq!(format!("{err:#}"), "twitter.poll.thread_followups[1]: Follow-up tweet is empty");
+		config.thread_followups = vec!["${nope}".to_string()];
+		assert!(build_poll_thread(&config, "Poll\n- [ ] up\n- [ ] down").await.is_err());
+	}
+
+	#[test]
+	fn test_serialize_reply() {
+		let request = CreateTweetRequest {
+			text: "more context".to_string(),
+			poll: None,
+			reply_to: Some(ReplyTo {
+				in_reply_to_tweet_id: "123".to_string(),
+			}),
+		};
+		insta::assert_snapshot!(serde_json::to_string(&request).unwrap(), @r#"{"text":"more context","reply":{"in_reply_to_tweet_id":"123"}}"#);
+	}
+
+	#[test]
+	fn test_poll_templates_deserialize() {
+		let one: PollTemplates = serde_json::from_str(r#""only one""#).unwrap();
+		assert_eq!(one.as_slice(), ["only one"]);
+		let many: PollTemplates = serde_json::from_str(r#"["a", "b"]"#).unwrap();
+		assert_eq!(many.as_slice(), ["a", "b"]);
+	}
+
+	#[test]
+	fn test_choose_template() {
+		use rand::SeedableRng as _;
+		let templates: Vec<String> = ["a", "b", "c"].map(String::from).to_vec();
+
+		let picks = |seed| {
+			let mut rng = rand::rngs::StdRng::seed_from_u64(seed);
+			(0..20).map(|i| choose_template(&templates, TemplateSelection::Random, i, &mut rng).unwrap()).collect::<Vec<_>>()
+		};
+		assert_eq!(picks(42), picks(42));
+		assert!(templates.iter().all(|t| picks(42).contains(&t.as_str())));
+
+		let mut rng = rand::rngs::StdRng::seed_from_u64(0);
+		let round_robin: Vec<_> = (7..11).map(|i| choose_template(&templates, TemplateSelection::RoundRobin, i, &mut rng).unwrap()).collect();
+		assert_eq!(round_robin, ["b", "c", "a", "b"]);
+
+		assert_eq!(choose_template(&[], TemplateSelection::Random, 0, &mut rng), None);
+	}
+
+	#[tokio::test]
+	async fn test_chosen_template_variables_resolved() {
+		use rand::SeedableRng as _;
+		let mut config = dummy_poll_config();
+		config.text = PollTemplates::Many(vec!["first ${date}\n- [ ] a\n- [ ] b".to_string(), "second ${date}\n- [ ] c\n- [ ] d".to_string()]);
+		config.text_selection = TemplateSelection::RoundRobin;
+
+		let mut rng = rand::rngs::StdRng::seed_from_u64(0);
+		let template = choose_template(config.text.as_slice(), config.text_selection, 1, &mut rng).unwrap();
+		let lead = build_poll_thread(&config, template).await.unwrap().lead;
+		assert!(lead.text.starts_with("second "));
+		assert!(!lead.text.contains("${date}"));
+		assert_eq!(lead.poll.unwrap().options, vec!["c", "d"]);
+	}
+
+	#[test]
+	fn test_validate_rejects_too_frequent_schedule() {
+		let mut config = dummy_poll_config();
+		assert!(config.validate().is_ok());
+
+		config.schedule_every = serde_json::from_str("\"1m\"").unwrap();
+		let err = config.validate().unwrap_err().to_string();
+		assert!(err.contains("schedule_every"), "{err}");
+		assert!(err.contains("15-minute minimum"), "{err}");
+
+		config.schedule_every = serde_json::from_str("\"15m\"").unwrap();
+		assert!(config.validate().is_ok());
+	}
+
+	#[test]
+	fn test_validate_rejects_out_of_range_duration() {
+		let mut config = dummy_poll_config();
+		config.duration_hours = 0;
+		assert!(config.validate().unwrap_err().to_string().contains("duration_hours = 0"));
+
+		config.duration_hours = 24 * 7 + 1;
+		assert!(config.validate().unwrap_err().to_string().contains("between 1 and 168"));
+
+		config.duration_hours = 24 * 7;
+		assert!(config.validate().is_ok());
+	}
+
+	#[test]
+	fn test_upcoming_post_times() {
+		let hour = Duration::from_secs(3600);
+		let every = Schedule::Every(hour);
+		let now: Timestamp = "2026-03-01T14:05:00Z".parse().unwrap();
+		let fmt = |times: Result<Vec<Timestamp>>| times.unwrap().iter().map(ToString::to_string).collect::<Vec<_>>();
+
+		assert_eq!(
+			fmt(upcoming_post_times(&every, now, false, None, 3)),
+			["2026-03-01T14:05:00Z", "2026-03-01T15:05:00Z", "2026-03-01T16:05:00Z"]
+		);
+		assert_eq!(fmt(upcoming_post_times(&every, now, true, None, 2)), ["2026-03-01T15:05:00Z", "2026-03-01T16:05:00Z"]);
+
+		// Current slot already posted: the immediate cycle is a no-op
+		let posted = slot_key(now, hour);
+		assert_eq!(fmt(upcoming_post_times(&every, now, false, Some(&posted), 2)), ["2026-03-01T15:05:00Z", "2026-03-01T16:05:00Z"]);
+	}
+
+	#[tokio::test]
+	async fn test_simulated_cycles() {
+		let start: Timestamp = "2026-03-01T14:05:00Z".parse().unwrap();
+		let dir = std::env::temp_dir().join(format!("social_networks_simulate_test_{}", std::process::id()));
+		std::fs::create_dir_all(&dir).unwrap();
+		let state_file = dir.join("twitter_poll_slot.json");
+		let _ = std::fs::remove_file(&state_file);
+		let run = |poll: TwitterPollConfig, skip_first: bool, n: usize| {
+			let state_file = state_file.clone();
+			async move {
+				let config = TwitterConfig {
+					poll: Some(poll),
+					..Default::default()
+				};
+				let mut clock = ScheduleClock::simulated(start, n);
+				assert!(schedule_sentiment_poll(&config, &state_file, skip_first, false, &mut clock).await.is_ok());
+				let ScheduleClock::Simulated { now, cycles, .. } = clock else { unreachable!() };
+				(now.to_string(), cycles.iter().map(ToString::to_string).collect::<Vec<_>>())
+			}
+		};
+
+		let (now, cycles) = run(dummy_poll_config(), false, 5).await;
+		assert_eq!(
+			cycles,
+			[
+				"2026-03-01T14:05:00Z",
+				"2026-03-01T15:05:00Z",
+				"2026-03-01T16:05:00Z",
+				"2026-03-01T17:05:00Z",
+				"2026-03-01T18:05:00Z"
+			]
+		);
+		// Sleeps through to the cycle after the last one, like the real loop would
+		assert_eq!(now, "2026-03-01T19:05:00Z");
+
+		let mut cron = dummy_poll_config();
+		cron.schedule_cron = Some("0 9 * * *".to_string());
+		let (_, cycles) = run(cron, true, 2).await;
+		assert_eq!(cycles, ["2026-03-03T09:00:00Z", "2026-03-04T09:00:00Z"]);
+		// Simulating never writes slot state
+		assert!(!state_file.exists());
+
+		std::fs::remove_dir_all(&dir).unwrap();
+	}
+
+	#[tokio::test]
+	async fn test_dry_run_skips_unbuildable_slot() {
+		let dir = std::env::temp_dir().join(format!("social_networks_dry_run_test_{}", std::process::id()));
+		std::fs::create_dir_all(&dir).unwrap();
+		let state_file = dir.join("twitter_poll_slot.json");
+		let _ = std::fs::remove_file(&state_file);
+		let mut poll = dummy_poll_config();
+		poll.text = PollTemplates::One("gm, no poll today".to_string());
+		let config = TwitterConfig {
+			poll: Some(poll),
+			..Default::default()
+		};
+		let schedule = config.poll.as_ref().unwrap().schedule().unwrap();
+		let now: Timestamp = "2026-03-01T14:05:00Z".parse().unwrap();
+
+		// The slot fails instead of the scheduler
+		assert!(matches!(post_slot_poll(&config, &state_file, &schedule, now, true).await, Ok(false)));
+		assert!(!state_file.exists());
+
+		std::fs::remove_dir_all(&dir).unwrap();
+	}
+
+	#[test]
+	fn test_cron_schedule() {
+		let mut config = dummy_poll_config();
+		config.schedule_cron = Some("0 9 * * *".to_string());
+		let schedule = config.schedule().unwrap();
+		assert_eq!(schedule.to_string(), "cron `0 9 * * *` (UTC)");
+		let now: Timestamp = "2026-03-01T14:05:00Z".parse().unwrap();
+		let fmt = |times: Result<Vec<Timestamp>>| times.unwrap().iter().map(ToString::to_string).collect::<Vec<_>>();
+
+		// Pinned to 09:00 UTC regardless of start time, instead of drifting with it
+		assert_eq!(fmt(upcoming_post_times(&schedule, now, false, None, 2)), ["2026-03-02T09:00:00Z", "2026-03-03T09:00:00Z"]);
+		assert_eq!(fmt(upcoming_post_times(&schedule, now, true, None, 1)), ["2026-03-03T09:00:00Z"]);
+		assert_eq!(schedule.next_after("2026-03-02T09:00:00Z".parse().unwrap()).unwrap().to_string(), "2026-03-03T09:00:00Z");
+
+		// A late wake-up still lands in the fire's slot
+		let (key, index) = schedule.slot("2026-03-02T09:00:03Z".parse().unwrap()).unwrap();
+		assert_eq!(key, "2026-03-02T09:00:00Z/cron");
+		assert_eq!(index, None);
+		assert_eq!(schedule.slot("2026-03-02T08:59:59Z".parse().unwrap()).unwrap().0, "2026-03-01T09:00:00Z/cron");
+
+		assert_eq!(Schedule::Every(Duration::from_secs(3600)).to_string(), "every 3600s");
+	}
+
+	#[test]
+	fn test_next_slot_index() {
+		let marker = SlotMarker {
+			key: "2026-03-01T09:00:00Z/cron".to_string(),
+			index: 4,
+			marked_at: "2026-03-01T09:00:00Z".parse().unwrap(),
+			tweet_id: None,
+		};
+		assert_eq!(next_slot_index(None, "2026-03-01T09:00:00Z/cron"), 0);
+		assert_eq!(next_slot_index(Some(&marker), "2026-03-01T09:00:00Z/cron"), 4);
+		assert_eq!(next_slot_index(Some(&marker), "2026-03-02T09:00:00Z/cron"), 5);
+	}
+
+	#[test]
+	fn test_validate_cron() {
+		let mut config = dummy_poll_config();
+		config.schedule_cron = Some("0 9 * * *".to_string());
+		assert!(config.validate().is_ok());
+
+		config.schedule_cron = Some("not cron".to_string());
+		assert!(config.validate().unwrap_err().to_string().contains("not a valid cron expression"));
+
+		// Takes precedence over a valid `schedule_every`
+		config.schedule_cron = Some("*/5 * * * *".to_string());
+		let err = config.validate().unwrap_err().to_string();
+		assert!(err.contains("schedule_cron"), "{err}");
+		assert!(err.contains("15-minute minimum"), "{err}");
+	}
+
+	#[test]
+	fn test_classify_duplicate_content() {
+		let v2 = r#"{"detail":"You are not allowed to create a Tweet with duplicate content.","type":"about:blank","title":"Forbidden","status":403}"#;
+		assert!(matches!(classify_api_error(403, v2, None), ScheduleError::Duplicate(_)));
+
+		let v1 = r#"{"errors":[{"code":187,"message":"Status is a duplicate."}]}"#;
+		assert!(matches!(classify_api_error(403, v1, None), ScheduleError::Duplicate(_)));
+
+		let forbidden = r#"{"title":"Forbidden","detail":"Forbidden","type":"about:blank","status":403}"#;
+		assert!(matches!(classify_api_error(403, forbidden, None), ScheduleError::Auth(_)));
+		assert!(matches!(classify_api_error(500, "oops", None), ScheduleError::Unhandled(_)));
+	}
+
+	#[test]
+	fn test_disambiguate() {
+		let now: Timestamp = "2026-03-01T14:05:09Z".parse().unwrap();
+		insta::assert_snapshot!(disambiguate("btc up or down?", now), @r"
+		btc up or down?
+
+		2026-03-01 14:05 UTC
+		");
+
+		// Right under the limit alone, so without cutting the stamp would push it over
+		let long = "a".repeat(MAX_TWEET_LENGTH - 1);
+		let stamped = disambiguate(&long, now);
+		assert_eq!(weighted_tweet_length(&stamped), MAX_TWEET_LENGTH);
+		assert!(stamped.ends_with("a…\n\n2026-03-01 14:05 UTC"), "{stamped}");
+		let cut = disambiguate(&format!("{} 🚀🚀", "a".repeat(MAX_TWEET_LENGTH - 6)), now);
+		assert!(weighted_tweet_length(&cut) <= MAX_TWEET_LENGTH);
+		assert!(cut.ends_with("…\n\n2026-03-01 14:05 UTC"), "{cut}");
 	}
 }
@@ -1,29 +1,65 @@
-use std::{collections::HashMap, convert::Infallible};
+use std::{
+	collections::HashMap,
+	path::{Path, PathBuf},
+};
 
 use clap::Args;
-use color_eyre::eyre::{Context, Result, bail};
+use color_eyre::eyre::{Context, ContextCompat, Result, bail};
 use jiff::{SignedDuration, Timestamp};
 use quick_xml::{Reader, events::Event};
-use serde::{Deserialize, Serialize};
-use social_networks_utils::utils::btc_price;
-use tokio::time::{self, Duration};
-use tracing::{debug, error, info, instrument};
+use serde::Deserialize;
+use social_networks_utils::{
+	clock::{Clock, SystemClock},
+	db::{Database, db_timestamp},
+	http,
+	profile::CycleTimer,
+	shutdown, snooze, status,
+	utils::{coin_price, human_duration},
+};
+use tokio::time::Duration;
+use tracing::{debug, error, info, instrument, warn};
 use v_utils::macros::MyConfigPrimitives;
 
 use crate::{
 	client::{AdapterError, Client},
+	discord_webhook::DiscordWebhook,
 	telegram_dms::TelegramConfig,
 	telegram_notifier::TelegramNotifier,
 };
 
 const SURFACE: &str = "youtube";
+/// `seen_items` namespace of handled videos, keyed by video id (unique across channels)
+const SEEN_NAMESPACE: &str = "youtube";
 #[derive(Args)]
-pub struct YoutubeArgs {}
+pub struct YoutubeArgs {
+	/// Run the RSS parser over every `.xml` feed saved in DIR, print what each one parsed to, and exit
+	#[arg(long, value_name = "DIR")]
+	pub parse_samples: Option<PathBuf>,
+}
 
 #[derive(Clone, Debug, Default, MyConfigPrimitives)]
 pub struct YoutubeConfig {
+	/// Display name -> channel id (`UC...`), `@handle`, or channel URL. Handles and URLs are resolved to ids on startup.
 	#[primitives(skip)]
 	pub channels: HashMap<String, String>,
+	/// Don't notify about YouTube Shorts
+	#[serde(default)]
+	pub skip_shorts: bool,
+	/// Case-insensitive words that trigger an extra alert when they appear in a new video's title
+	#[serde(default)]
+	#[primitives(skip)]
+	pub title_keywords: Vec<String>,
+	/// Skip uploads shorter than this, going by the watch page (`0s` disables the lookup). Unknown lengths are notified.
+	#[serde(default = "__default_min_duration", with = "human_duration")]
+	#[primitives(skip)]
+	pub min_duration: Duration,
+	/// How often to check for new uploads, e.g. `30s`, `5m`
+	#[serde(default = "__default_check_every", with = "human_duration")]
+	#[primitives(skip)]
+	pub check_every: Duration,
+	/// Discord webhook URL to post upload notifications to as well, alongside Telegram
+	#[serde(default)]
+	pub discord_webhook: Option<String>,
 }
 
 pub struct YoutubeMonitor {
@@ -42,17 +78,21 @@ impl Client for YoutubeMonitor {
 		SURFACE
 	}
 
-	async fn listen(&mut self) -> Result<Infallible, AdapterError> {
+	async fn listen(&mut self) -> Result<(), AdapterError> {
 		println!("YouTube: Listening...");
 		info!("Monitoring channels: {:?}", self.youtube_config.channels.keys());
 
 		loop {
-			match run_youtube_monitor(&self.youtube_config, &self.telegram_config).await {
+			match run_youtube_monitor(&self.youtube_config, &self.telegram_config, &SystemClock).await {
+				Ok(()) => return Ok(()),
 				Err(YoutubeError::Auth(detail)) => return Err(AdapterError::Auth { surface: SURFACE, detail }),
 				Err(YoutubeError::Recoverable(e)) => {
+					status::reconnecting(SURFACE);
 					error!("YouTube monitor error: {e:#}");
 					error!("Reconnecting in 5 minutes...");
-					time::sleep(Duration::from_secs(5 * 60)).await;
+					if shutdown::requested_during(Duration::from_secs(5 * 60)).await {
+						return Ok(());
+					}
 				}
 			}
 		}
@@ -70,97 +110,465 @@ impl<E: Into<color_eyre::eyre::Report>> From<E> for YoutubeError {
 	}
 }
 
-async fn ok_or_classify(response: reqwest::Response, op: &str) -> Result<reqwest::Response, YoutubeError> {
-	let status = response.status();
-	if status.is_success() {
-		return Ok(response);
+/// `youtube_last_uploaded.json`, where handled videos were kept before `seen_items`.
+#[derive(Deserialize)]
+struct LegacyState {
+	#[serde(default)]
+	seen_video_ids: Vec<String>,
+}
+
+/// Carry the ids of a leftover `youtube_last_uploaded.json` over to `seen_items`, then remove the file. Its
+/// title-keyed entries are dropped; those videos are past the freshness window by now.
+async fn import_legacy_state(db: &Database) -> Result<()> {
+	let path = xdg::BaseDirectories::with_prefix("social_networks").place_state_file("youtube_last_uploaded.json")?;
+	let content = match std::fs::read_to_string(&path) {
+		Ok(content) => content,
+		Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(()),
+		Err(e) => return Err(e).wrap_err_with(|| format!("Failed to read {}", path.display())),
+	};
+	let ids = legacy_seen_ids(&content).wrap_err_with(|| format!("Failed to parse {}", path.display()))?;
+	for id in &ids {
+		db.mark_seen(SEEN_NAMESPACE, id).await?;
 	}
-	let body = response.text().await.unwrap_or_default();
-	if matches!(status.as_u16(), 401 | 403) {
-		return Err(YoutubeError::Auth(format!("{op}: {status}: {body}")));
+	std::fs::remove_file(&path)?;
+	info!("Imported {} seen videos from {}", ids.len(), path.display());
+	Ok(())
+}
+
+fn legacy_seen_ids(content: &str) -> Result<Vec<String>> {
+	Ok(serde_json::from_str::<LegacyState>(content)?.seen_video_ids)
+}
+
+/// What fetching a channel's RSS feed turned up.
+enum Feed {
+	Latest(RssEntry),
+	/// A valid feed without videos
+	Empty,
+	/// A problem with this channel only (deleted or renamed channel, error page instead of a feed); other channels
+	/// are unaffected, so it's backed off rather than retried every cycle
+	Unavailable(String),
+}
+
+async fn fetch_feed(client: &reqwest::Client, channel_id: &str) -> Result<Feed, YoutubeError> {
+	let url = format!("https://www.youtube.com/feeds/videos.xml?channel_id={channel_id}");
+	let response = client.get(&url).send().await.context("Failed to fetch YouTube RSS feed")?;
+	let status = response.status().as_u16();
+	let body = response.text().await.context("Failed to read YouTube RSS feed")?;
+	classify_feed(status, &body)
+}
+
+/// Check the status before handing the body to the XML parser: 401/403 are auth errors, 5xx are YouTube's problem and
+/// retried next cycle, and any other error status is this channel's.
+fn classify_feed(status: u16, body: &str) -> Result<Feed, YoutubeError> {
+	match status {
+		200..=299 => Ok(match parse_youtube_rss(body) {
+			Ok(Some(entry)) => Feed::Latest(entry),
+			Ok(None) => Feed::Empty,
+			Err(e) => Feed::Unavailable(format!("{e:#}")),
+		}),
+		401 | 403 => Err(YoutubeError::Auth(format!("youtube_rss: HTTP {status}: {body}"))),
+		500..=599 => Err(YoutubeError::Recoverable(color_eyre::eyre::eyre!("youtube_rss: HTTP {status}"))),
+		_ => Ok(Feed::Unavailable(format!("HTTP {status}"))),
 	}
-	Err(YoutubeError::Recoverable(color_eyre::eyre::eyre!("{op}: {status}: {body}")))
 }
 
-#[derive(Debug, Default, Deserialize, Serialize)]
-struct LastUploadedTitles {
-	channels: HashMap<String, String>,
+/// Channels whose feed came back [`Feed::Unavailable`], with how many times in a row and when to try again.
+#[derive(Debug, Default)]
+struct FeedBackoff(HashMap<String, (u32, Timestamp)>);
+
+const FEED_COOLDOWN_BASE: SignedDuration = SignedDuration::from_mins(10);
+const FEED_COOLDOWN_MAX: SignedDuration = SignedDuration::from_hours(6);
+
+impl FeedBackoff {
+	fn is_cooling_down(&self, channel_name: &str, now: Timestamp) -> bool {
+		self.0.get(channel_name).is_some_and(|(_, retry_at)| *retry_at > now)
+	}
+
+	/// Returns how long to leave the channel alone and whether this starts a streak (the only time worth a warning).
+	fn failed(&mut self, channel_name: &str, now: Timestamp) -> (SignedDuration, bool) {
+		let (failures, retry_at) = self.0.entry(channel_name.to_string()).or_insert((0, now));
+		*failures += 1;
+		let cooldown = FEED_COOLDOWN_BASE
+			.checked_mul(1 << (*failures - 1).min(10))
+			.map_or(FEED_COOLDOWN_MAX, |c| c.min(FEED_COOLDOWN_MAX));
+		*retry_at = now + cooldown;
+		(cooldown, *failures == 1)
+	}
+
+	/// Returns whether the channel had been failing.
+	fn succeeded(&mut self, channel_name: &str) -> bool {
+		self.0.remove(channel_name).is_some()
+	}
 }
 
-#[instrument(skip(youtube_config, telegram_config))]
-async fn run_youtube_monitor(youtube_config: &YoutubeConfig, telegram_config: &TelegramConfig) -> Result<Infallible, YoutubeError> {
-	let client = reqwest::Client::new();
-	let telegram = TelegramNotifier::new(telegram_config.clone());
+/// How recently a regular upload must have been published to be notified. Videos are deduplicated by id in the
+/// database; this only keeps a channel's back catalogue quiet when it's first added (or its seen rows are lost).
+const FRESH_WINDOW: SignedDuration = SignedDuration::from_mins(15);
+/// Premieres and streams enter the feed when they're scheduled, possibly hours ahead, so they're still announced
+/// (once) if listed within this long.
+const BROADCAST_WINDOW: SignedDuration = SignedDuration::from_hours(24);
 
-	let state_file = xdg::BaseDirectories::with_prefix("social_networks")
-		.place_state_file("youtube_last_uploaded.json")
-		.map_err(color_eyre::eyre::Report::from)?;
-	let mut last_uploaded: LastUploadedTitles = if state_file.exists() {
-		let content = std::fs::read_to_string(&state_file)?;
-		serde_json::from_str(&content)?
-	} else {
-		LastUploadedTitles::default()
+#[instrument(skip(youtube_config, telegram_config, clock))]
+async fn run_youtube_monitor(youtube_config: &YoutubeConfig, telegram_config: &TelegramConfig, clock: &impl Clock) -> Result<(), YoutubeError> {
+	let client = http::client();
+	let telegram = TelegramNotifier::new(telegram_config.clone());
+	let discord = youtube_config.discord_webhook.clone().map(DiscordWebhook::new);
+	let notifiers = Notifiers {
+		telegram: &telegram,
+		discord: discord.as_ref(),
 	};
+	let db = Database::try_new().await.context("Failed to open database")?;
+	import_legacy_state(&db).await?;
 
 	info!("--YouTube-- monitor started");
+	status::connected(SURFACE);
+
+	// Resolved once per channel; one that fails to resolve is skipped and retried next cycle
+	let mut channel_ids: HashMap<&str, String> = HashMap::new();
+	let mut backoff = FeedBackoff::default();
 
-	//LOOP: daemon - runs until process termination
+	//LOOP: daemon - runs until shutdown
 	loop {
-		for (channel_name, channel_id) in &youtube_config.channels {
-			match check_channel(&client, channel_id, channel_name, &mut last_uploaded, &telegram).await {
-				Ok(_) => debug!("Checked channel: {channel_name}"),
+		let mut timer = CycleTimer::start("youtube");
+		for (channel_name, channel) in &youtube_config.channels {
+			let _item = timer.time(channel_name);
+			if !channel_ids.contains_key(channel_name.as_str()) {
+				match resolve_channel_id(&client, channel).await {
+					Ok(id) => {
+						debug!("Resolved YouTube channel {channel_name} ({channel}) to {id}");
+						channel_ids.insert(channel_name, id);
+					}
+					Err(YoutubeError::Auth(detail)) => return Err(YoutubeError::Auth(detail)),
+					Err(YoutubeError::Recoverable(e)) => {
+						status::error(SURFACE);
+						error!("Failed to resolve YouTube channel {channel_name} ({channel}): {e:#}");
+						continue;
+					}
+				}
+			}
+			if backoff.is_cooling_down(channel_name, clock.now()) {
+				continue;
+			}
+			let channel_id = &channel_ids[channel_name.as_str()];
+			let entry = match fetch_feed(&client, channel_id).await {
+				Ok(Feed::Unavailable(detail)) => {
+					let (cooldown, first) = backoff.failed(channel_name, clock.now());
+					if first {
+						warn!("YouTube feed of {channel_name} ({channel}) unavailable: {detail}. Backing off, starting at {cooldown:#}");
+					} else {
+						debug!("YouTube feed of {channel_name} still unavailable ({detail}), next try in {cooldown:#}");
+					}
+					continue;
+				}
+				Ok(feed) => {
+					if backoff.succeeded(channel_name) {
+						info!("YouTube feed of {channel_name} is back");
+					}
+					match feed {
+						Feed::Latest(entry) => entry,
+						_ => {
+							debug!("YouTube feed of {channel_name} has no videos");
+							continue;
+						}
+					}
+				}
+				Err(YoutubeError::Auth(detail)) => return Err(YoutubeError::Auth(detail)),
+				Err(YoutubeError::Recoverable(e)) => {
+					status::error(SURFACE);
+					error!("Error fetching feed of {channel_name}: {e:#}");
+					continue;
+				}
+			};
+			match check_channel(&client, youtube_config, entry, channel_name, &db, notifiers, clock).await {
+				Ok(_) => {
+					status::event(SURFACE);
+					debug!("Checked channel: {channel_name}");
+				}
 				Err(YoutubeError::Auth(detail)) => return Err(YoutubeError::Auth(detail)),
-				Err(YoutubeError::Recoverable(e)) => error!("Error checking channel {channel_name}: {e:#}"),
+				Err(YoutubeError::Recoverable(e)) => {
+					status::error(SURFACE);
+					error!("Error checking channel {channel_name}: {e:#}");
+				}
 			}
 		}
+		timer.finish();
 
-		let state_json = serde_json::to_string(&last_uploaded)?;
-		std::fs::write(&state_file, state_json)?;
-
-		time::sleep(Duration::from_secs(60)).await;
+		if shutdown::requested_during(youtube_config.check_every).await {
+			return Ok(());
+		}
 	}
 }
 
-#[instrument(skip(client, last_uploaded, telegram))]
-async fn check_channel(client: &reqwest::Client, channel_id: &str, channel_name: &str, last_uploaded: &mut LastUploadedTitles, telegram: &TelegramNotifier) -> Result<(), YoutubeError> {
-	let url = format!("https://www.youtube.com/feeds/videos.xml?channel_id={channel_id}");
-
-	let response = client.get(&url).send().await.context("Failed to fetch YouTube RSS feed")?;
-	let response = ok_or_classify(response, "youtube_rss").await?;
+/// Where new videos are announced: always Telegram, plus Discord when `youtube.discord_webhook` is set.
+#[derive(Clone, Copy)]
+struct Notifiers<'a> {
+	telegram: &'a TelegramNotifier,
+	discord: Option<&'a DiscordWebhook>,
+}
 
-	let xml_content = response.text().await.map_err(color_eyre::eyre::Report::from)?;
+/// Notify about the latest `entry` of a channel's feed, if it's new and passes the filters.
+/// Handled videos (notified or deliberately skipped) are marked seen, so a retitled video isn't announced again,
+/// nor a premiere once it goes live.
+#[instrument(skip(client, youtube_config, entry, db, notifiers, clock))]
+async fn check_channel(
+	client: &reqwest::Client,
+	youtube_config: &YoutubeConfig,
+	entry: RssEntry,
+	channel_name: &str,
+	db: &Database,
+	notifiers: Notifiers<'_>,
+	clock: &impl Clock,
+) -> Result<(), YoutubeError> {
+	let telegram = notifiers.telegram;
+	let RssEntry { video_id, title, published, .. } = &entry;
+
+	if db.is_seen(SEEN_NAMESPACE, video_id).await? {
+		return Ok(());
+	}
+	let mark_seen = async || db.mark_seen(SEEN_NAMESPACE, video_id).await;
+	// Negative for a premiere scheduled in the future
+	let age = clock.now().duration_since(*published);
+	if age >= BROADCAST_WINDOW {
+		return Ok(());
+	}
 
-	let (video_id, title, published) = parse_youtube_rss(&xml_content)?;
+	let page = watch_page(client, video_id).await;
+	let broadcast = entry.broadcast.or_else(|| page.as_deref().and_then(watch_page_broadcast)).unwrap_or_default();
+	if age >= FRESH_WINDOW && broadcast == Broadcast::Upload {
+		// Without the page we can't rule out a premiere, so look again next cycle
+		if page.is_some() || entry.broadcast.is_some() {
+			mark_seen().await?;
+		}
+		return Ok(());
+	}
 
-	let now = Timestamp::now();
-	let time_since_upload: SignedDuration = now.duration_since(published);
+	if broadcast == Broadcast::Upload {
+		if youtube_config.skip_shorts && (entry.is_short() || probe_is_short(client, video_id).await) {
+			info!("Skipping short from {channel_name}: {title:?}");
+			mark_seen().await?;
+			return Ok(());
+		}
 
-	if time_since_upload < SignedDuration::from_mins(15) {
-		if let Some(last_title) = last_uploaded.channels.get(channel_name)
-			&& last_title == &title
+		if !youtube_config.min_duration.is_zero()
+			&& let Some(duration) = page.as_deref().and_then(watch_page_duration)
+			&& duration < youtube_config.min_duration
 		{
+			info!("Skipping {}s video from {channel_name}: {title:?}", duration.as_secs());
+			mark_seen().await?;
 			return Ok(());
 		}
+	}
 
-		println!("YouTube: [{channel_name}] uploaded: {title}");
-		info!("New video from {channel_name}: {title:?}");
+	println!("YouTube: [{channel_name}] {}: {title}", broadcast.headline());
+	info!("New {broadcast:?} from {channel_name}: {title:?}");
 
-		let sentiment = analyze_sentiment(&title).await.unwrap_or_else(|e| {
-			error!("Failed to analyze sentiment: {e}");
-			"unclear".to_string()
-		});
+	if snooze::is_snoozed("youtube") {
+		info!("YouTube snoozed, not notifying about video from {channel_name}");
+		mark_seen().await?;
+		return Ok(());
+	}
 
-		if let Err(e) = telegram.send_youtube_notification(channel_name, &title, &sentiment, &video_id).await {
-			error!("Failed to send YouTube notification: {e}");
+	if let Some(keyword) = matching_keyword(title, &youtube_config.title_keywords) {
+		info!("Title of new video from {channel_name} matches keyword {keyword:?}");
+		if let Err(e) = telegram.send_youtube_keyword_alert(channel_name, title, keyword, video_id).await {
+			error!("Failed to send YouTube keyword alert: {e}");
 		}
+	}
+
+	let sentiment = analyze_sentiment(title, db).await.unwrap_or_else(|e| {
+		error!("Failed to analyze sentiment: {e}");
+		"unclear".to_string()
+	});
 
-		last_uploaded.channels.insert(channel_name.to_string(), title.to_string());
+	if let Err(e) = telegram.send_youtube_notification(channel_name, broadcast.headline(), title, &sentiment, video_id).await {
+		error!("Failed to send YouTube notification: {e}");
+	}
+	if let Some(discord) = notifiers.discord
+		&& let Err(e) = discord.send_youtube_notification(channel_name, broadcast.headline(), title, &sentiment, video_id).await
+	{
+		error!("Failed to post YouTube notification to the Discord webhook: {e:#}");
 	}
 
+	mark_seen().await?;
+
 	Ok(())
 }
 
-fn parse_youtube_rss(xml: &str) -> Result<(String, String, Timestamp)> {
+/// How a channel is written in `youtube.channels`.
+#[derive(Debug, PartialEq, Eq)]
+enum ChannelRef {
+	Id(String),
+	/// Channel page whose HTML names the id: `@handle`s, `/c/` and `/user/` URLs
+	Page(String),
+}
+
+fn is_channel_id(s: &str) -> bool {
+	s.len() == 24 && s.starts_with("UC") && s.bytes().all(|b| b.is_ascii_alphanumeric() || b == b'-' || b == b'_')
+}
+
+fn parse_channel_ref(value: &str) -> Result<ChannelRef> {
+	let value = value.trim().trim_end_matches('/');
+	if is_channel_id(value) {
+		return Ok(ChannelRef::Id(value.to_string()));
+	}
+	if let Some(handle) = value.strip_prefix('@') {
+		return Ok(ChannelRef::Page(format!("https://www.youtube.com/@{handle}")));
+	}
+	let path = value.strip_prefix("https://").or_else(|| value.strip_prefix("http://")).unwrap_or(value);
+	let path = path.strip_prefix("www.").or_else(|| path.strip_prefix("m.")).unwrap_or(path);
+	let Some(path) = path.strip_prefix("youtube.com/") else {
+		bail!("`{value}` is neither a channel id (UC...), an @handle, nor a youtube.com channel URL");
+	};
+	let mut segments = path.split(['/', '?']);
+	match (segments.next().unwrap_or_default(), segments.next()) {
+		("channel", Some(id)) if is_channel_id(id) => Ok(ChannelRef::Id(id.to_string())),
+		(handle, _) if handle.starts_with('@') => Ok(ChannelRef::Page(format!("https://www.youtube.com/{handle}"))),
+		(kind @ ("c" | "user"), Some(name)) if !name.is_empty() => Ok(ChannelRef::Page(format!("https://www.youtube.com/{kind}/{name}"))),
+		_ => bail!("`{value}` is not a YouTube channel URL"),
+	}
+}
+
+/// Without it, EU requests get the cookie consent interstitial instead of the page.
+const CONSENT_COOKIE: &str = "CONSENT=YES+1";
+
+/// Channel id for a `youtube.channels` value, fetching the channel page for anything but a raw id.
+async fn resolve_channel_id(client: &reqwest::Client, value: &str) -> Result<String, YoutubeError> {
+	let url = match parse_channel_ref(value)? {
+		ChannelRef::Id(id) => return Ok(id),
+		ChannelRef::Page(url) => url,
+	};
+	let response = client.get(&url).header("Cookie", CONSENT_COOKIE).send().await.context("Failed to fetch YouTube channel page")?;
+	let html = response
+		.error_for_status()
+		.context("Failed to fetch YouTube channel page")?
+		.text()
+		.await
+		.context("Failed to read YouTube channel page")?;
+	Ok(extract_channel_id(&html).with_context(|| format!("No channel id found on {url}"))?)
+}
+
+/// Channel id from a channel page: its canonical link, else its RSS link, else the `externalId` in the page data.
+fn extract_channel_id(html: &str) -> Option<String> {
+	[
+		"<link rel=\"canonical\" href=\"https://www.youtube.com/channel/",
+		"feeds/videos.xml?channel_id=",
+		"\"externalId\":\"",
+	]
+	.iter()
+	.find_map(|marker| {
+		let start = html.find(marker)? + marker.len();
+		let id: String = html[start..].chars().take_while(|c| c.is_ascii_alphanumeric() || *c == '-' || *c == '_').collect();
+		is_channel_id(&id).then_some(id)
+	})
+}
+
+/// Latest upload as listed in a channel's RSS feed.
+#[derive(Debug)]
+struct RssEntry {
+	video_id: String,
+	title: String,
+	published: Timestamp,
+	/// `<link rel="alternate">`; Shorts are listed under `/shorts/` instead of `/watch`.
+	link: Option<String>,
+	/// `<yt:liveBroadcastContent>`, when the feed has it; otherwise the watch page tells
+	broadcast: Option<Broadcast>,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+enum Broadcast {
+	#[default]
+	Upload,
+	Live,
+	/// Scheduled premiere or stream, not started yet
+	Premiere,
+}
+
+impl Broadcast {
+	fn from_feed(value: &str) -> Option<Self> {
+		match value {
+			"none" => Some(Self::Upload),
+			"live" => Some(Self::Live),
+			"upcoming" => Some(Self::Premiere),
+			_ => None,
+		}
+	}
+
+	fn headline(self) -> &'static str {
+		match self {
+			Self::Upload => "uploaded a new video",
+			Self::Live => "🔴 LIVE",
+			Self::Premiere => "⏰ PREMIERE",
+		}
+	}
+}
+
+impl RssEntry {
+	fn is_short(&self) -> bool {
+		self.link.as_deref().is_some_and(|l| l.contains("/shorts/"))
+	}
+}
+
+/// The feed doesn't always list Shorts under `/shorts/`, so ask YouTube: the `/shorts/` URL serves
+/// a Short directly but redirects regular videos to `/watch`. Any failure counts as "not a short".
+async fn probe_is_short(client: &reqwest::Client, video_id: &str) -> bool {
+	let url = format!("https://www.youtube.com/shorts/{video_id}");
+	// reqwest follows redirects by default, so compare the final URL rather than the status
+	match client.head(&url).send().await {
+		Ok(response) => response.status().is_success() && response.url().path().starts_with("/shorts/"),
+		Err(e) => {
+			debug!("Shorts probe failed for {video_id}: {e}");
+			false
+		}
+	}
+}
+
+/// HTML of a video's watch page, for what the feed doesn't say (length, live status). `None` on failure, in which
+/// case the video is treated as a regular upload of unknown length and notified anyway.
+async fn watch_page(client: &reqwest::Client, video_id: &str) -> Option<String> {
+	let url = format!("https://www.youtube.com/watch?v={video_id}");
+	let page = async { client.get(&url).header("Cookie", CONSENT_COOKIE).send().await?.error_for_status()?.text().await };
+	match page.await {
+		Ok(html) => Some(html),
+		Err(e) => {
+			warn!("Failed to fetch watch page of {video_id}: {e}");
+			None
+		}
+	}
+}
+
+/// Live status from the player data's `liveBroadcastDetails`; `None` if the page doesn't mention it.
+fn watch_page_broadcast(html: &str) -> Option<Broadcast> {
+	if html.contains("\"isLiveNow\":true") {
+		Some(Broadcast::Live)
+	} else if html.contains("\"isUpcoming\":true") {
+		Some(Broadcast::Premiere)
+	} else {
+		None
+	}
+}
+
+/// `lengthSeconds` from the player data, else the `itemprop="duration"` meta tag (ISO 8601, e.g. `PT1M3S`).
+/// Live streams and premieres report 0, which counts as unknown.
+fn watch_page_duration(html: &str) -> Option<Duration> {
+	let after = |marker: &str| html.find(marker).map(|i| &html[i + marker.len()..]);
+	let from_player = after("\"lengthSeconds\":\"")
+		.and_then(|rest| rest[..rest.find('"')?].parse::<u64>().ok())
+		.map(Duration::from_secs);
+	let from_meta = || {
+		let rest = after("<meta itemprop=\"duration\" content=\"")?;
+		let iso: SignedDuration = rest[..rest.find('"')?].parse().ok()?;
+		Duration::try_from(iso).ok()
+	};
+	from_player.or_else(from_meta).filter(|d| !d.is_zero())
+}
+
+fn matching_keyword<'a>(title: &str, keywords: &'a [String]) -> Option<&'a str> {
+	let title = title.to_lowercase();
+	keywords.iter().find(|k| title.contains(&k.to_lowercase())).map(String::as_str)
+}
+
+/// Latest entry of an Atom feed; `None` if the feed has no entries. Errors if `xml` isn't a feed at all.
+fn parse_youtube_rss(xml: &str) -> Result<Option<RssEntry>> {
 	let mut reader = Reader::from_str(xml);
 	reader.config_mut().trim_text(true);
 
@@ -169,24 +577,36 @@ fn parse_youtube_rss(xml: &str) -> Result<(String, String, Timestamp)> {
 	let mut video_id = None;
 	let mut title = None;
 	let mut published = None;
+	let mut link = None;
+	let mut broadcast = None;
 	let mut current_tag = String::new();
+	let mut saw_feed = false;
 
 	while let Ok(event) = reader.read_event_into(&mut buf) {
 		match event {
 			Event::Eof => break,
 			Event::Start(e) => {
 				let tag_name = String::from_utf8_lossy(e.name().as_ref()).to_string();
-				if tag_name == "entry" {
-					in_entry = true;
+				match tag_name.as_str() {
+					"feed" => saw_feed = true,
+					"entry" => in_entry = true,
+					_ => {}
 				}
 				current_tag = tag_name;
 			}
+			Event::Empty(e) if in_entry && e.name().as_ref() == b"link" => {
+				let is_alternate = e.try_get_attribute("rel").ok().flatten().is_some_and(|a| a.value.as_ref() == b"alternate");
+				if is_alternate && let Ok(Some(href)) = e.try_get_attribute("href") {
+					link = Some(String::from_utf8_lossy(&href.value).to_string());
+				}
+			}
 			Event::Text(e) if in_entry => {
 				let text = e.escape_ascii().to_string();
 				match current_tag.as_str() {
 					"yt:videoId" => video_id = Some(text),
 					"title" => title = Some(text),
 					"published" => published = Some(text),
+					"yt:liveBroadcastContent" => broadcast = Broadcast::from_feed(&text),
 					_ => {}
 				}
 			}
@@ -200,7 +620,13 @@ fn parse_youtube_rss(xml: &str) -> Result<(String, String, Timestamp)> {
 					let published_dt: Timestamp = dt_str.parse()?;
 
 					#[allow(clippy::unnecessary_unwrap)]
-					return Ok((video_id.unwrap(), title.unwrap(), published_dt));
+					return Ok(Some(RssEntry {
+						video_id: video_id.unwrap(),
+						title: title.unwrap(),
+						published: published_dt,
+						link,
+						broadcast,
+					}));
 				}
 			}
 			_ => {}
@@ -208,11 +634,64 @@ fn parse_youtube_rss(xml: &str) -> Result<(String, String, Timestamp)> {
 		buf.clear();
 	}
 
-	bail!("No video entry found in RSS feed")
+	if !saw_feed {
+		bail!("Not an Atom feed");
+	}
+	Ok(None)
 }
 
-async fn analyze_sentiment(title: &str) -> Result<String> {
-	let btc_price = btc_price(3).await.unwrap_or(0);
+/// Print how [`parse_youtube_rss`] reads each saved feed in `dir`, for collecting parser regression cases.
+/// Fails if any sample didn't parse.
+pub fn parse_samples(dir: &Path) -> Result<()> {
+	let results = parse_sample_dir(dir)?;
+	if results.is_empty() {
+		bail!("No .xml feeds in {}", dir.display());
+	}
+	let mut failed = 0;
+	for (path, result) in &results {
+		let name = path.file_name().unwrap_or_default().to_string_lossy();
+		match result {
+			Ok(None) => println!("ok    {name}: no entries"),
+			Ok(Some(entry)) => println!(
+				"ok    {name}: {} {:?} published {}{}",
+				entry.video_id,
+				entry.title,
+				entry.published,
+				if entry.is_short() { " (short)" } else { "" }
+			),
+			Err(e) => {
+				failed += 1;
+				println!("FAIL  {name}: {e:#}");
+			}
+		}
+	}
+	println!("\n{} parsed, {failed} failed", results.len() - failed);
+	if failed > 0 {
+		bail!("{failed} of {} samples failed to parse", results.len());
+	}
+	Ok(())
+}
+
+/// `parse_youtube_rss` over each `.xml` file directly in `dir`, by file name.
+fn parse_sample_dir(dir: &Path) -> Result<Vec<(PathBuf, Result<Option<RssEntry>>)>> {
+	let mut paths = std::fs::read_dir(dir)
+		.with_context(|| format!("Failed to read {}", dir.display()))?
+		.map(|entry| Ok(entry?.path()))
+		.collect::<Result<Vec<_>>>()?;
+	paths.retain(|p| p.extension().is_some_and(|ext| ext == "xml"));
+	paths.sort();
+	Ok(paths
+		.into_iter()
+		.map(|path| {
+			let parsed = std::fs::read_to_string(&path).map_err(Into::into).and_then(|xml| parse_youtube_rss(&xml));
+			(path, parsed)
+		})
+		.collect())
+}
+
+/// Its cost counts toward the daily LLM budget, under the `youtube` component.
+async fn analyze_sentiment(title: &str, db: &Database) -> Result<String> {
+	let btc_price = coin_price("BTC", 3).await.map(|p| p as u64).unwrap_or(0);
 
 	let prompt = format!(
 		"You receive a title of a youtube video from a crypto channel and current BTC price in case they reference it. \
@@ -222,8 +701,200 @@ async fn analyze_sentiment(title: &str) -> Result<String> {
 	);
 
 	let response = ask_llm::oneshot(&prompt).await?;
+	debug!("LLM sentiment for {title:?} (cost: {:.4} cents)", response.cost_cents);
+	if let Err(e) = db.record_llm_spend(SURFACE, response.cost_cents.into(), &db_timestamp(Timestamp::now())).await {
+		warn!("Failed to record LLM cost: {e:#}");
+	}
 
 	let sentiment = response.text.split_whitespace().next().unwrap_or("unclear").to_lowercase();
 
 	Ok(sentiment)
 }
+
+fn __default_min_duration() -> Duration {
+	Duration::from_secs(60)
+}
+
+fn __default_check_every() -> Duration {
+	Duration::from_secs(60)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn feed(link: &str) -> String {
+		format!(
+			r#"<?xml version="1.0" encoding="UTF-8"?>
+<feed xmlns:yt="http://www.youtube.com/xml/schemas/2015" xmlns:media="http://search.yahoo.com/mrss/" xmlns="http://www.w3.org/2005/Atom">
+ <title>Some Channel</title>
+ <entry>
+  <id>yt:video:abc123</id>
+  <yt:videoId>abc123</yt:videoId>
+  <title>BTC to the moon</title>
+  <link rel="alternate" href="{link}"/>
+  <published>2025-01-15T12:00:00+00:00</published>
+ </entry>
+</feed>"#
+		)
+	}
+
+	#[test]
+	fn test_parse_channel_ref() {
+		let id = "UCI3uVtN-W5StRN1RsLNKV6g";
+		let channel_id = |s: &str| parse_channel_ref(s).unwrap();
+		assert_eq!(channel_id(id), ChannelRef::Id(id.to_string()));
+		assert_eq!(channel_id(&format!("https://www.youtube.com/channel/{id}/videos")), ChannelRef::Id(id.to_string()));
+		assert_eq!(channel_id("@hamaha"), ChannelRef::Page("https://www.youtube.com/@hamaha".to_string()));
+		assert_eq!(
+			channel_id("https://www.youtube.com/@hamaha/videos"),
+			ChannelRef::Page("https://www.youtube.com/@hamaha".to_string())
+		);
+		assert_eq!(channel_id("youtube.com/@hamaha?si=x"), ChannelRef::Page("https://www.youtube.com/@hamaha".to_string()));
+		assert_eq!(
+			channel_id("https://m.youtube.com/c/SomeName/"),
+			ChannelRef::Page("https://www.youtube.com/c/SomeName".to_string())
+		);
+
+		assert!(parse_channel_ref("hamaha").is_err());
+		assert!(parse_channel_ref("https://example.com/@hamaha").is_err());
+		assert!(parse_channel_ref("https://www.youtube.com/watch?v=abc123").is_err());
+	}
+
+	#[test]
+	fn test_extract_channel_id() {
+		let id = "UCI3uVtN-W5StRN1RsLNKV6g";
+		let canonical = format!(r#"<html><head><link rel="canonical" href="https://www.youtube.com/channel/{id}"></head></html>"#);
+		assert_eq!(extract_channel_id(&canonical).as_deref(), Some(id));
+		let rss = format!(r#"<link rel="alternate" type="application/rss+xml" href="https://www.youtube.com/feeds/videos.xml?channel_id={id}">"#);
+		assert_eq!(extract_channel_id(&rss).as_deref(), Some(id));
+		let data = format!(r#"var ytInitialData = {{"metadata":{{"channelMetadataRenderer":{{"externalId":"{id}"}}}}}};"#);
+		assert_eq!(extract_channel_id(&data).as_deref(), Some(id));
+		assert_eq!(extract_channel_id(r#"<link rel="canonical" href="https://www.youtube.com/channel/short">"#), None);
+		assert_eq!(extract_channel_id("<html>404</html>"), None);
+	}
+
+	#[test]
+	fn test_legacy_seen_ids() {
+		let state = r#"{"channels":{"hamaha":"Big stream"},"seen_video_ids":["abc123","def456"]}"#;
+		assert_eq!(legacy_seen_ids(state).unwrap(), vec!["abc123".to_string(), "def456".to_string()]);
+		// Written before ids were kept
+		assert!(legacy_seen_ids(r#"{"channels":{"hamaha":"Big stream"}}"#).unwrap().is_empty());
+		assert!(legacy_seen_ids("not json").is_err());
+	}
+
+	#[test]
+	fn test_parse_sample_dir() {
+		let dir = std::env::temp_dir().join(format!("social_networks_rss_samples_{}", std::process::id()));
+		std::fs::create_dir_all(&dir).unwrap();
+		std::fs::write(dir.join("b_video.xml"), feed("https://www.youtube.com/watch?v=abc123")).unwrap();
+		std::fs::write(dir.join("a_empty.xml"), "<feed></feed>").unwrap();
+		std::fs::write(dir.join("c_error_page.xml"), "<html><body>404 Not Found</body></html>").unwrap();
+		std::fs::write(dir.join("notes.txt"), "not a feed").unwrap();
+
+		let results = parse_sample_dir(&dir).unwrap();
+		let summary: Vec<String> = results
+			.iter()
+			.map(|(path, r)| {
+				let parsed = match r {
+					Ok(entry) => entry.as_ref().map_or("no entries".to_string(), |e| e.video_id.clone()),
+					Err(e) => e.to_string(),
+				};
+				format!("{}: {parsed}", path.file_name().unwrap().to_string_lossy())
+			})
+			.collect();
+		assert_eq!(summary, ["a_empty.xml: no entries", "b_video.xml: abc123", "c_error_page.xml: Not an Atom feed"]);
+		assert!(parse_samples(&dir).is_err());
+
+		std::fs::remove_dir_all(&dir).unwrap();
+	}
+
+	#[test]
+	fn test_classify_feed() {
+		let video = feed("https://www.youtube.com/watch?v=abc123");
+		assert!(matches!(classify_feed(200, &video), Ok(Feed::Latest(e)) if e.video_id == "abc123"));
+		assert!(matches!(classify_feed(200, "<feed><title>No uploads</title></feed>"), Ok(Feed::Empty)));
+		assert!(matches!(classify_feed(200, "<html>oops</html>"), Ok(Feed::Unavailable(d)) if d == "Not an Atom feed"));
+		assert!(matches!(classify_feed(404, "<html>Not Found</html>"), Ok(Feed::Unavailable(d)) if d == "HTTP 404"));
+		assert!(matches!(classify_feed(403, ""), Err(YoutubeError::Auth(_))));
+		assert!(matches!(classify_feed(503, ""), Err(YoutubeError::Recoverable(_))));
+	}
+
+	#[test]
+	fn test_feed_backoff() {
+		let now: Timestamp = "2026-03-01T12:00:00Z".parse().unwrap();
+		let mut backoff = FeedBackoff::default();
+		assert!(!backoff.is_cooling_down("gone", now));
+
+		assert_eq!(backoff.failed("gone", now), (SignedDuration::from_mins(10), true));
+		assert!(backoff.is_cooling_down("gone", now + SignedDuration::from_mins(9)));
+		assert!(!backoff.is_cooling_down("gone", now + SignedDuration::from_mins(10)));
+		assert!(!backoff.is_cooling_down("other", now));
+
+		// Doubles on every failure in a row, warning only on the first, up to the cap
+		assert_eq!(backoff.failed("gone", now), (SignedDuration::from_mins(20), false));
+		let cooldowns: Vec<_> = (0..10).map(|_| backoff.failed("gone", now).0).collect();
+		assert_eq!(cooldowns[..4], [40, 80, 160, 320].map(SignedDuration::from_mins));
+		assert!(cooldowns[4..].iter().all(|c| *c == FEED_COOLDOWN_MAX));
+
+		assert!(backoff.succeeded("gone"));
+		assert!(!backoff.is_cooling_down("gone", now));
+		assert!(!backoff.succeeded("gone"));
+		assert_eq!(backoff.failed("gone", now), (SignedDuration::from_mins(10), true));
+	}
+
+	#[test]
+	fn test_broadcast_detection() {
+		let live = feed("https://www.youtube.com/watch?v=abc123").replace("</published>", "</published>\n  <yt:liveBroadcastContent>live</yt:liveBroadcastContent>");
+		assert_eq!(parse_youtube_rss(&live).unwrap().unwrap().broadcast, Some(Broadcast::Live));
+
+		assert_eq!(
+			watch_page_broadcast(r#""liveBroadcastDetails":{"isLiveNow":true,"startTimestamp":"2026-03-01T12:00:00+00:00"}"#),
+			Some(Broadcast::Live)
+		);
+		assert_eq!(watch_page_broadcast(r#""liveBroadcastDetails":{"isLiveNow":false},"isUpcoming":true"#), Some(Broadcast::Premiere));
+		assert_eq!(watch_page_broadcast(r#""videoDetails":{"lengthSeconds":"600"}"#), None);
+	}
+
+	#[test]
+	fn test_watch_page_duration() {
+		assert_eq!(
+			watch_page_duration(r#"{"videoDetails":{"videoId":"abc123","lengthSeconds":"43","keywords":[]}}"#),
+			Some(Duration::from_secs(43))
+		);
+		assert_eq!(watch_page_duration(r#"<meta itemprop="duration" content="PT12M5S">"#), Some(Duration::from_secs(12 * 60 + 5)));
+		// Live streams report 0
+		assert_eq!(watch_page_duration(r#""lengthSeconds":"0""#), None);
+		assert_eq!(watch_page_duration(r#""lengthSeconds":"soon""#), None);
+		assert_eq!(watch_page_duration("<html>consent</html>"), None);
+	}
+
+	#[test]
+	fn test_matching_keyword() {
+		let keywords = vec!["liquidation".to_string(), "SOL".to_string()];
+		assert_eq!(matching_keyword("Massive LIQUIDATION incoming", &keywords), Some("liquidation"));
+		assert_eq!(matching_keyword("why sol will flip eth", &keywords), Some("SOL"));
+		assert_eq!(matching_keyword("BTC weekly update", &keywords), None);
+		assert_eq!(matching_keyword("anything", &[]), None);
+	}
+
+	#[test]
+	fn test_shorts_detection_from_entry() {
+		let short = parse_youtube_rss(&feed("https://www.youtube.com/shorts/abc123")).unwrap().unwrap();
+		assert!(short.is_short());
+
+		let video = parse_youtube_rss(&feed("https://www.youtube.com/watch?v=abc123")).unwrap().unwrap();
+		assert!(!video.is_short());
+		insta::assert_debug_snapshot!(video, @r#"
+		RssEntry {
+		    video_id: "abc123",
+		    title: "BTC to the moon",
+		    published: 2025-01-15T12:00:00Z,
+		    link: Some(
+		        "https://www.youtube.com/watch?v=abc123",
+		    ),
+		    broadcast: None,
+		}
+		"#);
+	}
+}
@@ -0,0 +1,105 @@
+//! Source of the current time. Time-dependent checks (cooldowns, freshness windows) take a [`Clock`] instead of
+//! calling `Timestamp::now()`, so tests can drive them with a [`MockClock`].
+use std::{
+	collections::HashMap,
+	hash::Hash,
+	sync::{Arc, Mutex},
+};
+
+use jiff::{SignedDuration, Timestamp};
+
+pub trait Clock {
+	fn now(&self) -> Timestamp;
+}
+
+/// The wall clock.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+	fn now(&self) -> Timestamp {
+		Timestamp::now()
+	}
+}
+
+/// A clock that only moves when told to. Clones share the same time, so a test can keep one and hand the other out.
+#[derive(Clone, Debug)]
+pub struct MockClock(Arc<Mutex<Timestamp>>);
+
+impl MockClock {
+	pub fn new(start: Timestamp) -> Self {
+		Self(Arc::new(Mutex::new(start)))
+	}
+
+	pub fn set(&self, now: Timestamp) {
+		*self.0.lock().unwrap() = now;
+	}
+
+	pub fn advance(&self, by: SignedDuration) {
+		*self.0.lock().unwrap() += by;
+	}
+}
+
+impl Clock for MockClock {
+	fn now(&self) -> Timestamp {
+		*self.0.lock().unwrap()
+	}
+}
+
+/// Per-key quiet period. Every hit restarts its key's period, so a steady stream of hits stays quiet until it pauses
+/// for `period`.
+#[derive(Debug)]
+pub struct Cooldown<K, C = SystemClock> {
+	period: SignedDuration,
+	clock: C,
+	last_hit: HashMap<K, Timestamp>,
+}
+
+impl<K: Eq + Hash, C: Clock> Cooldown<K, C> {
+	pub fn new(period: SignedDuration, clock: C) -> Self {
+		Self {
+			period,
+			clock,
+			last_hit: HashMap::new(),
+		}
+	}
+
+	/// Record a hit on `key`; whether it came `period` or more after the previous one (always true for the first).
+	pub fn hit(&mut self, key: K) -> bool {
+		let now = self.clock.now();
+		self.last_hit.insert(key, now).is_none_or(|prev| now.duration_since(prev) >= self.period)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_cooldown_boundary() {
+		let clock = MockClock::new("2026-03-01T12:00:00Z".parse().unwrap());
+		let mut cooldown = Cooldown::new(SignedDuration::from_mins(15), clock.clone());
+
+		assert!(cooldown.hit("alice"));
+		clock.advance(SignedDuration::from_mins(15) - SignedDuration::from_secs(1));
+		assert!(!cooldown.hit("alice"));
+		// Keys cool down independently
+		assert!(cooldown.hit("bob"));
+
+		// The suppressed hit restarted the period: 15m after the first hit is not enough
+		clock.advance(SignedDuration::from_secs(1));
+		assert!(!cooldown.hit("alice"));
+		clock.advance(SignedDuration::from_mins(15));
+		assert!(cooldown.hit("alice"));
+	}
+
+	#[test]
+	fn test_zero_cooldown_always_fires() {
+		let clock = MockClock::new("2026-03-01T12:00:00Z".parse().unwrap());
+		let mut cooldown = Cooldown::new(SignedDuration::ZERO, clock.clone());
+		assert!(cooldown.hit(1));
+		assert!(cooldown.hit(1));
+		clock.set("2026-03-01T12:00:01Z".parse().unwrap());
+		assert!(cooldown.hit(1));
+	}
+}
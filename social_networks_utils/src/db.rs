@@ -1,6 +1,77 @@
-use color_eyre::eyre::{Result, WrapErr};
+use color_eyre::eyre::{ContextCompat, Result, WrapErr};
 use libsql::Connection;
-use tracing::info;
+use serde::Serialize;
+use tracing::{debug, info};
+
+/// Schema changes on top of the `CREATE TABLE IF NOT EXISTS` baseline in [`create_baseline`], applied in order.
+/// `PRAGMA user_version` records how many have run. Append only; never edit or reorder an entry.
+/// Up-to-date databases skip the baseline on open, so new tables must be added here rather than there.
+const MIGRATIONS: &[&str] = &[
+	// 1: LLM rationale (or which rule decided) for each classification
+	"ALTER TABLE processed_emails ADD COLUMN reason TEXT",
+	// 2: which monitored list a tracked poll came from, to route its closing notification
+	"ALTER TABLE tracked_polls ADD COLUMN list_id TEXT",
+	// 3: polls we posted ourselves (`twitter-schedule`), to correlate with their outcomes
+	"CREATE TABLE IF NOT EXISTS posted_polls (
+        tweet_id  TEXT PRIMARY KEY,
+        text      TEXT NOT NULL,
+        options   TEXT NOT NULL,
+        posted_at TEXT NOT NULL
+    )",
+	// 4: LLM spend per UTC day (`YYYY-MM-DD`), for `email.llm_daily_budget_cents`
+	"CREATE TABLE IF NOT EXISTS llm_costs (
+        day        TEXT PRIMARY KEY,
+        cost_cents REAL NOT NULL
+    )",
+	// 5: YouTube videos already handled, replacing the title-keyed `youtube_last_uploaded.json`
+	"CREATE TABLE IF NOT EXISTS seen_youtube_videos (
+        video_id   TEXT PRIMARY KEY,
+        channel_id TEXT NOT NULL,
+        seen_at    TEXT NOT NULL
+    )",
+	// 6: items any monitor already handled, by monitor-chosen namespace; see `Database::is_seen`
+	"CREATE TABLE IF NOT EXISTS seen_items (
+        namespace TEXT NOT NULL,
+        key       TEXT NOT NULL,
+        seen_at   TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%SZ', 'now')),
+        PRIMARY KEY (namespace, key)
+    )",
+	// 7: YouTube moves onto `seen_items`
+	"INSERT OR IGNORE INTO seen_items (namespace, key, seen_at) SELECT 'youtube', video_id, seen_at FROM seen_youtube_videos;
+    DROP TABLE seen_youtube_videos",
+	// 8: `Database::purge_old_emails` deletes by age
+	"CREATE INDEX IF NOT EXISTS processed_emails_processed_at ON processed_emails (processed_at)",
+	// 9: one row per LLM call, by the component that made it, replacing the per-day `llm_costs` totals; read by day
+	"CREATE TABLE IF NOT EXISTS llm_spend (
+        component  TEXT NOT NULL,
+        cost_cents REAL NOT NULL,
+        at         TEXT NOT NULL
+    );
+    CREATE INDEX IF NOT EXISTS llm_spend_at ON llm_spend (at)",
+	// 10: `llm_costs` (all spent on emails) moves onto `llm_spend`, one row per day
+	"INSERT INTO llm_spend (component, cost_cents, at)
+        SELECT 'email', cost_cents, day || 'T00:00:00Z' FROM llm_costs
+        WHERE NOT EXISTS (SELECT 1 FROM llm_spend WHERE component = 'email' AND at = llm_costs.day || 'T00:00:00Z');
+    DROP TABLE llm_costs",
+	// 11: one correction per email; redelivered and repeated reactions used to add a row each. Keeps the first
+	"DELETE FROM email_corrections WHERE rowid NOT IN (SELECT MIN(rowid) FROM email_corrections GROUP BY message_id);
+    CREATE UNIQUE INDEX IF NOT EXISTS email_corrections_message_id ON email_corrections (message_id)",
+	// 12: `getUpdates` offsets, by consumer, so a restart doesn't re-read updates already handled
+	"CREATE TABLE IF NOT EXISTS update_offsets (
+        consumer    TEXT PRIMARY KEY,
+        next_offset INTEGER NOT NULL
+    )",
+];
+
+/// Sum of a [`Database::llm_spend`] breakdown. Folds from `0.0`: an empty float `sum()` is `-0.0`, which prints as such.
+pub fn total_llm_spend(spend: &[(String, f64)]) -> f64 {
+	spend.iter().fold(0.0, |total, (_, cents)| total + cents)
+}
+
+/// Format for timestamp columns compared in SQL; fixed-width UTC so it compares as text.
+pub fn db_timestamp(ts: jiff::Timestamp) -> String {
+	ts.strftime("%Y-%m-%dT%H:%M:%SZ").to_string()
+}
 
 #[derive(Clone)]
 pub struct Database {
@@ -13,21 +84,20 @@ impl Database {
 		let db_path = xdg_dirs.place_state_file("db.sqlite3")?;
 		info!("Opening SQLite database at {}", db_path.display());
 
-		let db = libsql::Builder::new_local(&db_path).build().await.wrap_err("failed to open SQLite database")?;
+		Self::open(&db_path).await
+	}
+
+	async fn open(db_path: &std::path::Path) -> Result<Self> {
+		let db = libsql::Builder::new_local(db_path).build().await.wrap_err("failed to open SQLite database")?;
 		let conn = db.connect().wrap_err("failed to get connection")?;
 
-		conn.execute(
-			"CREATE TABLE IF NOT EXISTS processed_emails (
-                message_id   TEXT PRIMARY KEY,
-                processed_at TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%SZ', 'now')),
-                from_email   TEXT NOT NULL,
-                subject      TEXT NOT NULL,
-                is_human     INTEGER NOT NULL
-            )",
-			(),
-		)
-		.await
-		.wrap_err("failed to create processed_emails table")?;
+		let applied = schema_version(&conn).await?;
+		if !needs_migration(applied) {
+			debug!("Database schema is up to date (version {applied}), skipping migrations");
+			return Ok(Self { conn });
+		}
+		create_baseline(&conn).await?;
+		migrate(&conn, MIGRATIONS, applied).await?;
 
 		Ok(Self { conn })
 	}
@@ -41,16 +111,486 @@ impl Database {
 		Ok(rows.next().await.wrap_err("failed to read row")?.is_some())
 	}
 
-	pub async fn mark_email_processed(&self, message_id: &str, from_email: &str, subject: &str, is_human: bool) -> Result<()> {
+	/// `reason` is why the email was (or wasn't) considered human: the LLM's rationale or the rule that decided.
+	pub async fn mark_email_processed(&self, message_id: &str, from_email: &str, subject: &str, is_human: bool, reason: Option<&str>) -> Result<()> {
 		self.conn
 			.execute(
-				"INSERT OR IGNORE INTO processed_emails (message_id, from_email, subject, is_human) VALUES (?1, ?2, ?3, ?4)",
-				libsql::params![message_id, from_email, subject, is_human as i64],
+				"INSERT OR IGNORE INTO processed_emails (message_id, from_email, subject, is_human, reason) VALUES (?1, ?2, ?3, ?4, ?5)",
+				libsql::params![message_id, from_email, subject, is_human as i64, reason],
 			)
 			.await
 			.wrap_err("failed to execute mark_email_processed")?;
 		Ok(())
 	}
+
+	/// How many processed emails came from `address` (lowercase, no display name). `from_email` holds the whole `From`
+	/// header, so both a bare `address` and `Name <address>` count, whatever the display name was.
+	pub async fn sender_count(&self, address: &str) -> Result<u64> {
+		let mut rows = self
+			.conn
+			.query(
+				// A suffix compare rather than LIKE, where `_` in addresses would be a wildcard
+				"SELECT COUNT(*) FROM processed_emails
+                 WHERE lower(from_email) = ?1 OR substr(lower(from_email), -(length(?1) + 2)) = '<' || ?1 || '>'",
+				[address],
+			)
+			.await
+			.wrap_err("failed to query sender_count")?;
+		let row = rows.next().await.wrap_err("failed to read row")?.context("sender_count returned no row")?;
+		Ok(row.get::<i64>(0)? as u64)
+	}
+
+	/// Cached human/automated verdict for a sender address, if classified within the last `ttl_days`.
+	pub async fn get_sender_classification(&self, from_email: &str, ttl_days: u32) -> Result<Option<bool>> {
+		let mut rows = self
+			.conn
+			.query(
+				"SELECT is_human FROM sender_classifications WHERE from_email = ?1 AND classified_at >= strftime('%Y-%m-%dT%H:%M:%SZ', 'now', ?2)",
+				libsql::params![from_email, format!("-{ttl_days} days")],
+			)
+			.await
+			.wrap_err("failed to query get_sender_classification")?;
+		match rows.next().await.wrap_err("failed to read row")? {
+			Some(row) => Ok(Some(row.get::<i64>(0)? != 0)),
+			None => Ok(None),
+		}
+	}
+
+	pub async fn set_sender_classification(&self, from_email: &str, is_human: bool) -> Result<()> {
+		self.conn
+			.execute(
+				"INSERT OR REPLACE INTO sender_classifications (from_email, is_human) VALUES (?1, ?2)",
+				libsql::params![from_email, is_human as i64],
+			)
+			.await
+			.wrap_err("failed to execute set_sender_classification")?;
+		Ok(())
+	}
+
+	/// Latest tweet id already handled for `user_id` as a member of `list_id`.
+	pub async fn get_last_seen_tweet(&self, user_id: &str, list_id: &str) -> Result<Option<String>> {
+		let mut rows = self
+			.conn
+			.query("SELECT last_tweet_id FROM seen_tweets WHERE user_id = ?1 AND list_id = ?2", [user_id, list_id])
+			.await
+			.wrap_err("failed to query get_last_seen_tweet")?;
+		match rows.next().await.wrap_err("failed to read row")? {
+			Some(row) => Ok(Some(row.get(0)?)),
+			None => Ok(None),
+		}
+	}
+
+	pub async fn set_last_seen_tweet(&self, user_id: &str, list_id: &str, tweet_id: &str) -> Result<()> {
+		self.conn
+			.execute(
+				"INSERT OR REPLACE INTO seen_tweets (user_id, list_id, last_tweet_id) VALUES (?1, ?2, ?3)",
+				[user_id, list_id, tweet_id],
+			)
+			.await
+			.wrap_err("failed to execute set_last_seen_tweet")?;
+		Ok(())
+	}
+
+	/// Whether `key` was already handled. Each monitor dedupes in its own `namespace` (e.g. `youtube`, keyed by video
+	/// id), so keys only need to be unique within it.
+	pub async fn is_seen(&self, namespace: &str, key: &str) -> Result<bool> {
+		let mut rows = self
+			.conn
+			.query("SELECT 1 FROM seen_items WHERE namespace = ?1 AND key = ?2", [namespace, key])
+			.await
+			.wrap_err("failed to query is_seen")?;
+		Ok(rows.next().await.wrap_err("failed to read row")?.is_some())
+	}
+
+	/// Keeps the first `seen_at` if the item was already marked. Returns whether this call marked it; atomic, so of
+	/// concurrent callers only one gets `true`.
+	pub async fn mark_seen(&self, namespace: &str, key: &str) -> Result<bool> {
+		let inserted = self
+			.conn
+			.execute("INSERT OR IGNORE INTO seen_items (namespace, key) VALUES (?1, ?2)", [namespace, key])
+			.await
+			.wrap_err("failed to execute mark_seen")?;
+		Ok(inserted > 0)
+	}
+
+	/// Remember a tweet with a poll so its results can be reported once it closes. `poll_ends_at` is
+	/// `%Y-%m-%dT%H:%M:%SZ` UTC, so it compares correctly as text. Re-tracking a known tweet is a no-op.
+	pub async fn track_poll(&self, tweet_id: &str, list_id: &str, author: &str, text: &str, poll_ends_at: &str) -> Result<()> {
+		self.conn
+			.execute(
+				"INSERT OR IGNORE INTO tracked_polls (tweet_id, list_id, author, text, poll_ends_at) VALUES (?1, ?2, ?3, ?4, ?5)",
+				[tweet_id, list_id, author, text, poll_ends_at],
+			)
+			.await
+			.wrap_err("failed to execute track_poll")?;
+		Ok(())
+	}
+
+	/// Polls not yet reported as closed that ended in `(stale_before, now]`, oldest first.
+	/// Polls that ended before `stale_before` are left alone for good (e.g. the tweet was deleted).
+	pub async fn due_polls(&self, now: &str, stale_before: &str) -> Result<Vec<TrackedPoll>> {
+		let mut rows = self
+			.conn
+			.query(
+				"SELECT tweet_id, list_id, author, text, poll_ends_at FROM tracked_polls
+                WHERE notified_closed = 0 AND poll_ends_at <= ?1 AND poll_ends_at > ?2 ORDER BY poll_ends_at",
+				[now, stale_before],
+			)
+			.await
+			.wrap_err("failed to query due_polls")?;
+
+		let mut polls = Vec::new();
+		while let Some(row) = rows.next().await.wrap_err("failed to read row")? {
+			polls.push(TrackedPoll {
+				tweet_id: row.get(0)?,
+				list_id: row.get(1)?,
+				author: row.get(2)?,
+				text: row.get(3)?,
+				poll_ends_at: row.get(4)?,
+			});
+		}
+		Ok(polls)
+	}
+
+	pub async fn mark_poll_closed(&self, tweet_id: &str) -> Result<()> {
+		self.conn
+			.execute("UPDATE tracked_polls SET notified_closed = 1 WHERE tweet_id = ?1", [tweet_id])
+			.await
+			.wrap_err("failed to execute mark_poll_closed")?;
+		Ok(())
+	}
+
+	/// Record a poll posted from our account. `options` are stored as a JSON array.
+	pub async fn record_posted_poll(&self, tweet_id: &str, text: &str, options: &[String], posted_at: &str) -> Result<()> {
+		self.conn
+			.execute(
+				"INSERT OR IGNORE INTO posted_polls (tweet_id, text, options, posted_at) VALUES (?1, ?2, ?3, ?4)",
+				libsql::params![tweet_id, text, serde_json::to_string(options)?, posted_at],
+			)
+			.await
+			.wrap_err("failed to execute record_posted_poll")?;
+		Ok(())
+	}
+
+	/// A poll we posted, if `tweet_id` is one.
+	pub async fn posted_poll(&self, tweet_id: &str) -> Result<Option<PostedPoll>> {
+		let mut rows = self
+			.conn
+			.query("SELECT tweet_id, text, options, posted_at FROM posted_polls WHERE tweet_id = ?1", [tweet_id])
+			.await
+			.wrap_err("failed to query posted_poll")?;
+		match rows.next().await.wrap_err("failed to read row")? {
+			Some(row) => Ok(Some(PostedPoll {
+				tweet_id: row.get(0)?,
+				text: row.get(1)?,
+				options: serde_json::from_str(&row.get::<String>(2)?).wrap_err("malformed posted_polls.options")?,
+				posted_at: row.get(3)?,
+			})),
+			None => Ok(None),
+		}
+	}
+
+	/// Record one LLM call's cost, spent by `component` (`email`, `youtube`) at `at` ([`db_timestamp`]).
+	pub async fn record_llm_spend(&self, component: &str, cost_cents: f64, at: &str) -> Result<()> {
+		self.conn
+			.execute(
+				"INSERT INTO llm_spend (component, cost_cents, at) VALUES (?1, ?2, ?3)",
+				libsql::params![component, cost_cents, at],
+			)
+			.await
+			.wrap_err("failed to execute record_llm_spend")?;
+		Ok(())
+	}
+
+	/// LLM spend on `day` (`YYYY-MM-DD`, UTC) by component, in component order; empty if nothing was recorded.
+	pub async fn llm_spend(&self, day: &str) -> Result<Vec<(String, f64)>> {
+		let next_day = day.parse::<jiff::civil::Date>().wrap_err("llm_spend: `day` is not a date")?.tomorrow()?.to_string();
+		let mut rows = self
+			.conn
+			.query(
+				"SELECT component, SUM(cost_cents) FROM llm_spend WHERE at >= ?1 AND at < ?2 GROUP BY component ORDER BY component",
+				[day, next_day.as_str()],
+			)
+			.await
+			.wrap_err("failed to query llm_spend")?;
+		let mut spend = Vec::new();
+		while let Some(row) = rows.next().await.wrap_err("failed to read row")? {
+			spend.push((row.get(0)?, row.get(1)?));
+		}
+		Ok(spend)
+	}
+
+	/// Record `item_hash` as notified at `now`, unless it already was since `expired_before`. Returns whether this call
+	/// claimed it. Atomic, so concurrent monitors can't both claim the same item. Entries older than `expired_before`
+	/// are pruned on the way.
+	pub async fn claim_notification(&self, item_hash: &str, now: &str, expired_before: &str) -> Result<bool> {
+		self.conn
+			.execute("DELETE FROM recent_notifications WHERE notified_at < ?1", [expired_before])
+			.await
+			.wrap_err("failed to prune recent_notifications")?;
+		let inserted = self
+			.conn
+			.execute(
+				"INSERT OR IGNORE INTO recent_notifications (item_hash, notified_at) VALUES (?1, ?2)",
+				libsql::params![item_hash, now],
+			)
+			.await
+			.wrap_err("failed to execute claim_notification")?;
+		Ok(inserted == 1)
+	}
+
+	pub async fn forget_notification(&self, item_hash: &str) -> Result<()> {
+		self.conn
+			.execute("DELETE FROM recent_notifications WHERE item_hash = ?1", [item_hash])
+			.await
+			.wrap_err("failed to execute forget_notification")?;
+		Ok(())
+	}
+
+	/// Delete processed emails from before `older_than` (`%Y-%m-%dT%H:%M:%SZ` UTC), with the records of which Telegram
+	/// message they were forwarded as. Corrections are kept, as they stand alone. Returns how many emails went.
+	pub async fn purge_old_emails(&self, older_than: &str) -> Result<u64> {
+		let purged = self
+			.conn
+			.execute("DELETE FROM processed_emails WHERE processed_at < ?1", [older_than])
+			.await
+			.wrap_err("failed to execute purge_old_emails")?;
+		self.conn
+			.execute("DELETE FROM forwarded_emails WHERE message_id NOT IN (SELECT message_id FROM processed_emails)", ())
+			.await
+			.wrap_err("failed to prune forwarded_emails")?;
+		Ok(purged)
+	}
+
+	/// Keyset-paginated read of `processed_emails` in insertion order. Pass the `rowid` of the
+	/// last row of the previous page as `after`; an empty page means the end was reached.
+	pub async fn processed_emails_page(&self, after: i64, limit: u32) -> Result<Vec<ProcessedEmail>> {
+		let mut rows = self
+			.conn
+			.query(
+				"SELECT rowid, message_id, from_email, subject, is_human, processed_at, reason FROM processed_emails WHERE rowid > ?1 ORDER BY rowid LIMIT ?2",
+				libsql::params![after, limit],
+			)
+			.await
+			.wrap_err("failed to query processed_emails_page")?;
+
+		let mut page = Vec::new();
+		while let Some(row) = rows.next().await.wrap_err("failed to read row")? {
+			page.push(ProcessedEmail {
+				rowid: row.get(0)?,
+				message_id: row.get(1)?,
+				from_email: row.get(2)?,
+				subject: row.get(3)?,
+				is_human: row.get::<i64>(4)? != 0,
+				processed_at: row.get(5)?,
+				reason: row.get(6)?,
+			});
+		}
+		Ok(page)
+	}
+
+	/// Remember which Telegram message an email was forwarded as, so reactions on it can be traced back.
+	pub async fn record_forwarded_email(&self, chat_id: i64, tg_message_id: i64, message_id: &str) -> Result<()> {
+		self.conn
+			.execute(
+				"INSERT OR REPLACE INTO forwarded_emails (chat_id, tg_message_id, message_id) VALUES (?1, ?2, ?3)",
+				libsql::params![chat_id, tg_message_id, message_id],
+			)
+			.await
+			.wrap_err("failed to execute record_forwarded_email")?;
+		Ok(())
+	}
+
+	/// Record that the classifier got `message_id` wrong; `is_human` is the corrected verdict.
+	/// Returns `false` if the Telegram message does not correspond to a forwarded email, or its correction was already recorded.
+	pub async fn record_email_correction(&self, chat_id: i64, tg_message_id: i64, is_human: bool) -> Result<bool> {
+		let inserted = self
+			.conn
+			.execute(
+				"INSERT OR IGNORE INTO email_corrections (message_id, from_email, subject, is_human)
+                SELECT p.message_id, p.from_email, p.subject, ?3
+                FROM forwarded_emails f JOIN processed_emails p ON p.message_id = f.message_id
+                WHERE f.chat_id = ?1 AND f.tg_message_id = ?2",
+				libsql::params![chat_id, tg_message_id, is_human as i64],
+			)
+			.await
+			.wrap_err("failed to execute record_email_correction")?;
+		Ok(inserted > 0)
+	}
+
+	/// The `getUpdates` offset `consumer` last stored, if any.
+	pub async fn update_offset(&self, consumer: &str) -> Result<Option<i64>> {
+		let mut rows = self
+			.conn
+			.query("SELECT next_offset FROM update_offsets WHERE consumer = ?1", [consumer])
+			.await
+			.wrap_err("failed to query update_offset")?;
+		match rows.next().await.wrap_err("failed to read row")? {
+			Some(row) => Ok(Some(row.get(0)?)),
+			None => Ok(None),
+		}
+	}
+
+	pub async fn set_update_offset(&self, consumer: &str, next_offset: i64) -> Result<()> {
+		self.conn
+			.execute(
+				"INSERT OR REPLACE INTO update_offsets (consumer, next_offset) VALUES (?1, ?2)",
+				libsql::params![consumer, next_offset],
+			)
+			.await
+			.wrap_err("failed to execute set_update_offset")?;
+		Ok(())
+	}
+}
+
+/// A row of `processed_emails`, as handed out by [`Database::processed_emails_page`].
+#[derive(Clone, Debug, Serialize)]
+pub struct ProcessedEmail {
+	#[serde(skip)]
+	pub rowid: i64,
+	pub message_id: String,
+	pub from_email: String,
+	pub subject: String,
+	pub is_human: bool,
+	pub processed_at: String,
+	/// `None` for rows processed before the column existed.
+	pub reason: Option<String>,
+}
+
+/// A row of `posted_polls`, as handed out by [`Database::posted_poll`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PostedPoll {
+	pub tweet_id: String,
+	pub text: String,
+	pub options: Vec<String>,
+	pub posted_at: String,
+}
+
+/// A row of `tracked_polls`, as handed out by [`Database::due_polls`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TrackedPoll {
+	pub tweet_id: String,
+	/// `None` for polls tracked before the column existed.
+	pub list_id: Option<String>,
+	pub author: String,
+	pub text: String,
+	pub poll_ends_at: String,
+}
+
+async fn create_baseline(conn: &Connection) -> Result<()> {
+	conn.execute(
+		"CREATE TABLE IF NOT EXISTS processed_emails (
+                message_id   TEXT PRIMARY KEY,
+                processed_at TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%SZ', 'now')),
+                from_email   TEXT NOT NULL,
+                subject      TEXT NOT NULL,
+                is_human     INTEGER NOT NULL
+            )",
+		(),
+	)
+	.await
+	.wrap_err("failed to create processed_emails table")?;
+
+	conn.execute(
+		"CREATE TABLE IF NOT EXISTS forwarded_emails (
+                chat_id      INTEGER NOT NULL,
+                tg_message_id INTEGER NOT NULL,
+                message_id   TEXT NOT NULL,
+                PRIMARY KEY (chat_id, tg_message_id)
+            )",
+		(),
+	)
+	.await
+	.wrap_err("failed to create forwarded_emails table")?;
+
+	conn.execute(
+		"CREATE TABLE IF NOT EXISTS email_corrections (
+                message_id   TEXT NOT NULL,
+                recorded_at  TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%SZ', 'now')),
+                from_email   TEXT NOT NULL,
+                subject      TEXT NOT NULL,
+                is_human     INTEGER NOT NULL
+            )",
+		(),
+	)
+	.await
+	.wrap_err("failed to create email_corrections table")?;
+
+	conn.execute(
+		"CREATE TABLE IF NOT EXISTS sender_classifications (
+                from_email    TEXT PRIMARY KEY,
+                is_human      INTEGER NOT NULL,
+                classified_at TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%SZ', 'now'))
+            )",
+		(),
+	)
+	.await
+	.wrap_err("failed to create sender_classifications table")?;
+
+	conn.execute(
+		"CREATE TABLE IF NOT EXISTS seen_tweets (
+                user_id       TEXT NOT NULL,
+                list_id       TEXT NOT NULL,
+                last_tweet_id TEXT NOT NULL,
+                seen_at       TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%SZ', 'now')),
+                PRIMARY KEY (user_id, list_id)
+            )",
+		(),
+	)
+	.await
+	.wrap_err("failed to create seen_tweets table")?;
+
+	conn.execute(
+		"CREATE TABLE IF NOT EXISTS tracked_polls (
+                tweet_id        TEXT PRIMARY KEY,
+                author          TEXT NOT NULL,
+                text            TEXT NOT NULL,
+                poll_ends_at    TEXT NOT NULL,
+                notified_closed INTEGER NOT NULL DEFAULT 0
+            )",
+		(),
+	)
+	.await
+	.wrap_err("failed to create tracked_polls table")?;
+
+	conn.execute(
+		"CREATE TABLE IF NOT EXISTS recent_notifications (
+                item_hash   TEXT PRIMARY KEY,
+                notified_at TEXT NOT NULL
+            )",
+		(),
+	)
+	.await
+	.wrap_err("failed to create recent_notifications table")?;
+	Ok(())
+}
+
+/// How many [`MIGRATIONS`] have been applied, per `PRAGMA user_version`.
+async fn schema_version(conn: &Connection) -> Result<usize> {
+	let mut rows = conn.query("PRAGMA user_version", ()).await.wrap_err("failed to read user_version")?;
+	Ok(match rows.next().await.wrap_err("failed to read row")? {
+		Some(row) => row.get::<i64>(0)? as usize,
+		None => 0,
+	})
+}
+
+/// A database at the latest version (or newer, from a later build) skips the baseline and the migration loop.
+fn needs_migration(applied: usize) -> bool {
+	applied < MIGRATIONS.len()
+}
+
+/// Apply `migrations` past the first `applied`, each in one transaction with its `user_version` bump, so a crash
+/// part-way leaves it either fully applied or not at all. An entry may hold several statements.
+async fn migrate(conn: &Connection, migrations: &[&str], applied: usize) -> Result<()> {
+	for (i, sql) in migrations.iter().enumerate().skip(applied) {
+		let version = i + 1;
+		info!("Applying database migration {version}");
+		let tx = conn.transaction().await.wrap_err("failed to start a migration transaction")?;
+		tx.execute_batch(sql).await.wrap_err_with(|| format!("failed to apply migration {version}"))?;
+		tx.execute(&format!("PRAGMA user_version = {version}"), ()).await.wrap_err("failed to bump user_version")?;
+		tx.commit().await.wrap_err_with(|| format!("failed to commit migration {version}"))?;
+	}
+	Ok(())
 }
 
 impl std::fmt::Debug for Database {
@@ -58,3 +598,372 @@ impl std::fmt::Debug for Database {
 		f.debug_struct("Database").finish()
 	}
 }
+
+#[cfg(test)]
+mod tests {
+	use std::sync::atomic::{AtomicUsize, Ordering};
+
+	use super::*;
+
+	/// A database file in a temp dir of its own (tests run in parallel), removed with the dir on drop.
+	struct TestDb {
+		dir: std::path::PathBuf,
+		path: std::path::PathBuf,
+	}
+
+	impl TestDb {
+		async fn open(&self) -> Database {
+			Database::open(&self.path).await.unwrap()
+		}
+	}
+
+	impl Drop for TestDb {
+		fn drop(&mut self) {
+			let _ = std::fs::remove_dir_all(&self.dir);
+		}
+	}
+
+	fn test_db() -> TestDb {
+		static COUNT: AtomicUsize = AtomicUsize::new(0);
+		let dir = std::env::temp_dir().join(format!("social_networks_db_test_{}_{}", std::process::id(), COUNT.fetch_add(1, Ordering::Relaxed)));
+		let _ = std::fs::remove_dir_all(&dir);
+		std::fs::create_dir_all(&dir).unwrap();
+		TestDb { path: dir.join("db.sqlite3"), dir }
+	}
+
+	#[tokio::test]
+	async fn test_migration_keeps_old_rows_readable() {
+		let db_file = test_db();
+		let path = &db_file.path;
+
+		// A database from before `reason` existed
+		{
+			let db = libsql::Builder::new_local(path).build().await.unwrap();
+			let conn = db.connect().unwrap();
+			conn.execute(
+				"CREATE TABLE processed_emails (message_id TEXT PRIMARY KEY, processed_at TEXT NOT NULL DEFAULT 'then', from_email TEXT NOT NULL, subject TEXT NOT NULL, is_human INTEGER NOT NULL)",
+				(),
+			)
+			.await
+			.unwrap();
+			conn.execute("INSERT INTO processed_emails (message_id, from_email, subject, is_human) VALUES ('old', 'a@b.c', 'hi', 1)", ())
+				.await
+				.unwrap();
+		}
+
+		let db = db_file.open().await;
+		db.mark_email_processed("new", "x@y.z", "yo", false, Some("newsletter footer")).await.unwrap();
+		// Reopening must not re-apply migrations
+		let db2 = db_file.open().await;
+
+		let page = db2.processed_emails_page(0, 10).await.unwrap();
+		let reasons: Vec<_> = page.iter().map(|r| (r.message_id.as_str(), r.reason.as_deref())).collect();
+		assert_eq!(reasons, vec![("old", None), ("new", Some("newsletter footer"))]);
+	}
+
+	#[tokio::test]
+	async fn test_failed_migration_rolls_back() {
+		let db_file = test_db();
+		let path = &db_file.path;
+		let db = libsql::Builder::new_local(path).build().await.unwrap();
+		let conn = db.connect().unwrap();
+
+		let migrations = ["CREATE TABLE a (x INTEGER)", "INSERT INTO a VALUES (1); INSERT INTO missing VALUES (1)"];
+		assert!(migrate(&conn, &migrations, 0).await.is_err());
+		// The first migration stuck; none of the second did, nor its version bump
+		assert_eq!(schema_version(&conn).await.unwrap(), 1);
+		let mut rows = conn.query("SELECT COUNT(*) FROM a", ()).await.unwrap();
+		assert_eq!(rows.next().await.unwrap().unwrap().get::<i64>(0).unwrap(), 0);
+	}
+
+	#[tokio::test]
+	async fn test_up_to_date_schema_skips_migrations() {
+		assert!(needs_migration(0));
+		assert!(needs_migration(MIGRATIONS.len() - 1));
+		assert!(!needs_migration(MIGRATIONS.len()));
+		assert!(!needs_migration(MIGRATIONS.len() + 1));
+
+		let db_file = test_db();
+		let db = db_file.open().await;
+		assert_eq!(schema_version(&db.conn).await.unwrap(), MIGRATIONS.len());
+		db.conn.execute("DROP TABLE seen_tweets", ()).await.unwrap();
+		drop(db);
+
+		// The baseline isn't re-run on an up-to-date database, so the dropped table stays gone
+		let db = db_file.open().await;
+		let mut rows = db.conn.query("SELECT 1 FROM sqlite_master WHERE name = 'seen_tweets'", ()).await.unwrap();
+		assert!(rows.next().await.unwrap().is_none());
+	}
+
+	#[tokio::test]
+	async fn test_seen_tweets_keyed_by_user_and_list() {
+		let db_file = test_db();
+		let db = db_file.open().await;
+
+		assert_eq!(db.get_last_seen_tweet("42", "list_a").await.unwrap(), None);
+		db.set_last_seen_tweet("42", "list_a", "100").await.unwrap();
+		db.set_last_seen_tweet("42", "list_b", "90").await.unwrap();
+		db.set_last_seen_tweet("42", "list_a", "101").await.unwrap();
+		assert_eq!(db.get_last_seen_tweet("42", "list_a").await.unwrap().as_deref(), Some("101"));
+		assert_eq!(db.get_last_seen_tweet("42", "list_b").await.unwrap().as_deref(), Some("90"));
+		assert_eq!(db.get_last_seen_tweet("7", "list_a").await.unwrap(), None);
+	}
+
+	#[tokio::test]
+	async fn test_seen_items() {
+		let db_file = test_db();
+		let db = db_file.open().await;
+
+		assert!(!db.is_seen("youtube", "abc123").await.unwrap());
+		assert!(db.mark_seen("youtube", "abc123").await.unwrap());
+		assert!(db.is_seen("youtube", "abc123").await.unwrap());
+		assert!(!db.is_seen("youtube", "def456").await.unwrap());
+		// Namespaces don't see each other's keys
+		assert!(!db.is_seen("twitter", "abc123").await.unwrap());
+
+		// Marking again (e.g. a premiere going live) keeps the first sighting
+		db.conn.execute("UPDATE seen_items SET seen_at = '2026-03-01T12:00:00Z'", ()).await.unwrap();
+		assert!(!db.mark_seen("youtube", "abc123").await.unwrap());
+		let mut rows = db.conn.query("SELECT seen_at FROM seen_items WHERE key = 'abc123'", ()).await.unwrap();
+		assert_eq!(rows.next().await.unwrap().unwrap().get::<String>(0).unwrap(), "2026-03-01T12:00:00Z");
+		assert!(db.is_seen("youtube", "abc123").await.unwrap());
+
+		// Survives reopening, i.e. a restart
+		drop(db);
+		let db = db_file.open().await;
+		assert!(db.is_seen("youtube", "abc123").await.unwrap());
+	}
+
+	#[tokio::test]
+	async fn test_seen_youtube_videos_carry_over() {
+		let db_file = test_db();
+		let path = &db_file.path;
+
+		// A database at migration 5, with a video seen under the old table
+		{
+			let db = libsql::Builder::new_local(path).build().await.unwrap();
+			let conn = db.connect().unwrap();
+			create_baseline(&conn).await.unwrap();
+			migrate(&conn, &MIGRATIONS[..5], 0).await.unwrap();
+			conn.execute("INSERT INTO seen_youtube_videos VALUES ('abc123', 'UC_channel', '2026-03-01T12:00:00Z')", ())
+				.await
+				.unwrap();
+		}
+
+		let db = db_file.open().await;
+		assert!(db.is_seen("youtube", "abc123").await.unwrap());
+		assert!(!db.is_seen("youtube", "def456").await.unwrap());
+		let mut rows = db.conn.query("SELECT 1 FROM sqlite_master WHERE name = 'seen_youtube_videos'", ()).await.unwrap();
+		assert!(rows.next().await.unwrap().is_none());
+	}
+
+	#[tokio::test]
+	async fn test_llm_costs_carry_over() {
+		let db_file = test_db();
+		let path = &db_file.path;
+
+		// A database at migration 8, with spend recorded as per-day totals
+		{
+			let db = libsql::Builder::new_local(path).build().await.unwrap();
+			let conn = db.connect().unwrap();
+			create_baseline(&conn).await.unwrap();
+			migrate(&conn, &MIGRATIONS[..8], 0).await.unwrap();
+			conn.execute("INSERT INTO llm_costs VALUES ('2026-03-01', 1.5)", ()).await.unwrap();
+		}
+
+		let db = db_file.open().await;
+		assert_eq!(db.llm_spend("2026-03-01").await.unwrap(), vec![("email".to_string(), 1.5)]);
+		let mut rows = db.conn.query("SELECT 1 FROM sqlite_master WHERE name = 'llm_costs'", ()).await.unwrap();
+		assert!(rows.next().await.unwrap().is_none());
+
+		// Re-running the copy (as a crash before the version bump used to) doesn't count the spend twice
+		db.conn.execute("CREATE TABLE llm_costs (day TEXT PRIMARY KEY, cost_cents REAL NOT NULL)", ()).await.unwrap();
+		db.conn.execute("INSERT INTO llm_costs VALUES ('2026-03-01', 1.5)", ()).await.unwrap();
+		migrate(&db.conn, &MIGRATIONS[..10], 9).await.unwrap();
+		assert_eq!(db.llm_spend("2026-03-01").await.unwrap(), vec![("email".to_string(), 1.5)]);
+	}
+
+	#[tokio::test]
+	async fn test_due_polls() {
+		let db_file = test_db();
+		let db = db_file.open().await;
+
+		db.track_poll("1", "list", "alice", "up or down?", "2026-01-01T12:00:00Z").await.unwrap();
+		db.track_poll("2", "list", "bob", "long poll", "2026-01-03T12:00:00Z").await.unwrap();
+		db.track_poll("3", "list", "carol", "ancient", "2025-06-01T12:00:00Z").await.unwrap();
+		// Seeing the tweet again doesn't reset it
+		db.track_poll("1", "other", "alice", "edited", "2026-01-09T12:00:00Z").await.unwrap();
+
+		let due = |now| db.due_polls(now, "2025-12-31T12:00:00Z");
+		assert!(due("2026-01-01T11:59:59Z").await.unwrap().is_empty());
+		let polls = due("2026-01-02T00:00:00Z").await.unwrap();
+		assert_eq!(
+			polls,
+			vec![TrackedPoll {
+				tweet_id: "1".to_string(),
+				list_id: Some("list".to_string()),
+				author: "alice".to_string(),
+				text: "up or down?".to_string(),
+				poll_ends_at: "2026-01-01T12:00:00Z".to_string(),
+			}]
+		);
+
+		db.mark_poll_closed("1").await.unwrap();
+		let ids: Vec<_> = due("2026-01-04T00:00:00Z").await.unwrap().into_iter().map(|p| p.tweet_id).collect();
+		assert_eq!(ids, vec!["2"]);
+	}
+
+	#[tokio::test]
+	async fn test_recent_notifications_ttl() {
+		let db_file = test_db();
+		let db = db_file.open().await;
+
+		// Window of 24h: claims made before `expired_before` no longer count
+		assert!(db.claim_notification("a", "2026-01-01T12:00:00Z", "2025-12-31T12:00:00Z").await.unwrap());
+		assert!(!db.claim_notification("a", "2026-01-01T18:00:00Z", "2025-12-31T18:00:00Z").await.unwrap());
+		assert!(db.claim_notification("b", "2026-01-01T18:00:00Z", "2025-12-31T18:00:00Z").await.unwrap());
+		assert!(!db.claim_notification("a", "2026-01-02T11:59:59Z", "2026-01-01T11:59:59Z").await.unwrap());
+		assert!(db.claim_notification("a", "2026-01-02T12:00:01Z", "2026-01-01T12:00:01Z").await.unwrap());
+
+		// Survives reopening, i.e. a restart
+		drop(db);
+		let db = db_file.open().await;
+		assert!(!db.claim_notification("b", "2026-01-02T12:00:01Z", "2026-01-01T12:00:01Z").await.unwrap());
+
+		db.forget_notification("b").await.unwrap();
+		assert!(db.claim_notification("b", "2026-01-02T12:00:02Z", "2026-01-01T12:00:02Z").await.unwrap());
+	}
+
+	#[tokio::test]
+	async fn test_purge_old_emails() {
+		let db_file = test_db();
+		let db = db_file.open().await;
+
+		for (id, processed_at) in [("old", "2025-09-01T00:00:00Z"), ("edge", "2025-10-01T00:00:00Z"), ("new", "2026-01-01T00:00:00Z")] {
+			db.conn
+				.execute(
+					"INSERT INTO processed_emails (message_id, processed_at, from_email, subject, is_human) VALUES (?1, ?2, 'a@b.c', 'hi', 1)",
+					[id, processed_at],
+				)
+				.await
+				.unwrap();
+		}
+		db.record_forwarded_email(-100, 1, "old").await.unwrap();
+		db.record_forwarded_email(-100, 2, "new").await.unwrap();
+
+		assert_eq!(db.purge_old_emails("2025-10-01T00:00:00Z").await.unwrap(), 1);
+		let ids: Vec<_> = db.processed_emails_page(0, 10).await.unwrap().into_iter().map(|e| e.message_id).collect();
+		assert_eq!(ids, vec!["edge", "new"]);
+		// A reaction on the purged email's forward no longer finds it, the newer one still does
+		assert!(!db.record_email_correction(-100, 1, false).await.unwrap());
+		assert!(db.record_email_correction(-100, 2, false).await.unwrap());
+		assert_eq!(db.purge_old_emails("2025-10-01T00:00:00Z").await.unwrap(), 0);
+	}
+
+	#[tokio::test]
+	async fn test_email_corrections_once_per_email() {
+		let db_file = test_db();
+		let path = &db_file.path;
+
+		// A database at migration 10, where a redelivered reaction recorded the same correction twice
+		{
+			let db = libsql::Builder::new_local(path).build().await.unwrap();
+			let conn = db.connect().unwrap();
+			create_baseline(&conn).await.unwrap();
+			migrate(&conn, &MIGRATIONS[..10], 0).await.unwrap();
+			for _ in 0..2 {
+				conn.execute("INSERT INTO email_corrections (message_id, from_email, subject, is_human) VALUES ('a', 'a@b.c', 'hi', 0)", ())
+					.await
+					.unwrap();
+			}
+		}
+
+		let db = db_file.open().await;
+		let corrections = || async {
+			let mut rows = db.conn.query("SELECT message_id FROM email_corrections ORDER BY message_id", ()).await.unwrap();
+			let mut ids = Vec::new();
+			while let Some(row) = rows.next().await.unwrap() {
+				ids.push(row.get::<String>(0).unwrap());
+			}
+			ids
+		};
+		assert_eq!(corrections().await, ["a"]);
+
+		db.mark_email_processed("b", "a@b.c", "hey", true, None).await.unwrap();
+		db.record_forwarded_email(-100, 2, "b").await.unwrap();
+		assert!(db.record_email_correction(-100, 2, false).await.unwrap());
+		// Reacting again, or Telegram redelivering the reaction, doesn't count twice
+		assert!(!db.record_email_correction(-100, 2, false).await.unwrap());
+		assert_eq!(corrections().await, ["a", "b"]);
+	}
+
+	#[tokio::test]
+	async fn test_update_offset() {
+		let db_file = test_db();
+		let db = db_file.open().await;
+
+		assert_eq!(db.update_offset("email_feedback").await.unwrap(), None);
+		db.set_update_offset("email_feedback", 41).await.unwrap();
+		db.set_update_offset("email_feedback", 42).await.unwrap();
+		assert_eq!(db.update_offset("email_feedback").await.unwrap(), Some(42));
+		assert_eq!(db.update_offset("other").await.unwrap(), None);
+	}
+
+	#[tokio::test]
+	async fn test_sender_count() {
+		let db_file = test_db();
+		let db = db_file.open().await;
+
+		assert_eq!(db.sender_count("alice@example.com").await.unwrap(), 0);
+		db.mark_email_processed("1", "Alice <alice@example.com>", "hi", true, None).await.unwrap();
+		db.mark_email_processed("2", "alice@example.com", "again", true, None).await.unwrap();
+		db.mark_email_processed("3", "Alice Smith <Alice@Example.com>", "re: hi", true, None).await.unwrap();
+		db.mark_email_processed("4", "Bob <bob@example.com>", "hey", true, None).await.unwrap();
+		db.mark_email_processed("6", "Bob <bobXexample.com>", "hey", true, None).await.unwrap();
+		// Only a suffix of this address, must not count
+		db.mark_email_processed("5", "Mallory <malice@example.com>", "hey", false, None).await.unwrap();
+
+		assert_eq!(db.sender_count("alice@example.com").await.unwrap(), 3);
+		assert_eq!(db.sender_count("bob@example.com").await.unwrap(), 1);
+		assert_eq!(db.sender_count("bob_example.com").await.unwrap(), 0);
+	}
+
+	#[tokio::test]
+	async fn test_llm_spend_accumulates_per_day() {
+		let db_file = test_db();
+		let db = db_file.open().await;
+
+		assert_eq!(db.llm_spend("2026-01-01").await.unwrap(), vec![]);
+		assert_eq!(format!("{:.2}", total_llm_spend(&[])), "0.00");
+		db.record_llm_spend("email", 0.25, "2026-01-01T00:00:00Z").await.unwrap();
+		db.record_llm_spend("youtube", 0.5, "2026-01-01T12:00:00Z").await.unwrap();
+		db.record_llm_spend("email", 0.125, "2026-01-01T23:59:59Z").await.unwrap();
+		db.record_llm_spend("email", 0.125, "2026-01-02T00:00:00Z").await.unwrap();
+		db.record_llm_spend("email", 0.125, "2025-12-31T23:59:59Z").await.unwrap();
+		assert_eq!(db.llm_spend("2026-01-01").await.unwrap(), vec![("email".to_string(), 0.375), ("youtube".to_string(), 0.5)]);
+		assert_eq!(total_llm_spend(&db.llm_spend("2026-01-01").await.unwrap()), 0.875);
+		assert_eq!(db.llm_spend("2026-01-02").await.unwrap(), vec![("email".to_string(), 0.125)]);
+	}
+
+	#[tokio::test]
+	async fn test_posted_polls() {
+		let db_file = test_db();
+		let db = db_file.open().await;
+
+		let options = vec!["up".to_string(), "down".to_string()];
+		db.record_posted_poll("1", "btc?", &options, "2026-01-01T12:00:00Z").await.unwrap();
+		// A resumed slot can confirm the same tweet again; the first record stands
+		db.record_posted_poll("1", "btc? again", &options, "2026-01-01T12:05:00Z").await.unwrap();
+
+		assert_eq!(
+			db.posted_poll("1").await.unwrap(),
+			Some(PostedPoll {
+				tweet_id: "1".to_string(),
+				text: "btc?".to_string(),
+				options,
+				posted_at: "2026-01-01T12:00:00Z".to_string(),
+			})
+		);
+		assert_eq!(db.posted_poll("2").await.unwrap(), None);
+	}
+}
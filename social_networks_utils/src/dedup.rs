@@ -0,0 +1,67 @@
+//! Persisted set of recently notified items, shared by every notification path so a restart (or two monitors seeing
+//! the same item) doesn't notify about it again within [`DEDUP_WINDOW`].
+use std::sync::Arc;
+
+use jiff::{SignedDuration, Timestamp};
+use sha1::{Digest, Sha1};
+use tokio::sync::OnceCell;
+use tracing::warn;
+
+use crate::db::{Database, db_timestamp};
+
+/// How long a notified item stays suppressed.
+pub const DEDUP_WINDOW: SignedDuration = SignedDuration::from_hours(24);
+
+/// Handle on the shared set. Cheap to construct and clone; the database is opened on first use.
+#[derive(Clone, Default)]
+pub struct RecentlyNotified {
+	db: Arc<OnceCell<Database>>,
+}
+
+impl std::fmt::Debug for RecentlyNotified {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		f.debug_struct("RecentlyNotified").field("opened", &self.db.initialized()).finish()
+	}
+}
+
+impl RecentlyNotified {
+	/// Claim `item` (anything identifying the logical item and where it's going) for notifying now. `false` if it was
+	/// already claimed within [`DEDUP_WINDOW`]. Fails open: if the database is unavailable, the notification goes out.
+	pub async fn claim(&self, item: &str) -> bool {
+		let now = Timestamp::now();
+		let claimed = async {
+			let db = self.db.get_or_try_init(Database::try_new).await?;
+			db.claim_notification(&item_hash(item), &db_timestamp(now), &db_timestamp(now - DEDUP_WINDOW)).await
+		}
+		.await;
+		claimed.unwrap_or_else(|e| {
+			warn!("Notification dedup unavailable, notifying anyway: {e:#}");
+			true
+		})
+	}
+
+	/// Undo a [`claim`](Self::claim) whose notification failed to send, so the retry isn't suppressed.
+	pub async fn release(&self, item: &str) {
+		if let Some(db) = self.db.get()
+			&& let Err(e) = db.forget_notification(&item_hash(item)).await
+		{
+			warn!("Failed to release notification dedup claim: {e:#}");
+		}
+	}
+}
+
+fn item_hash(item: &str) -> String {
+	Sha1::digest(item.as_bytes()).iter().map(|b| format!("{b:02x}")).collect()
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_item_hash() {
+		assert_eq!(item_hash("youtube:dQw4w9WgXcQ"), item_hash("youtube:dQw4w9WgXcQ"));
+		assert_ne!(item_hash("youtube:dQw4w9WgXcQ"), item_hash("youtube:dQw4w9WgXcR"));
+		assert_eq!(item_hash("").len(), 40);
+	}
+}
@@ -0,0 +1,114 @@
+//! Last `Heartbeat` line a monitor logged. The log files (`<state>/social_networks/<name>.log`) hold
+//! tracing's JSON events, pretty-printed or one per line (`log_format = "json"`); a process can be alive yet hung, and the heartbeat age is what tells.
+use std::{
+	io::{Read, Seek, SeekFrom},
+	path::{Path, PathBuf},
+};
+
+use color_eyre::eyre::{ContextCompat, Result};
+use jiff::Timestamp;
+use serde::Deserialize;
+
+/// Only this much of the end of a log is read; heartbeats are frequent, logs can be gigabytes.
+const TAIL_BYTES: u64 = 1024 * 1024;
+
+#[derive(Deserialize)]
+struct LogEvent {
+	timestamp: Timestamp,
+	#[serde(default)]
+	target: String,
+	#[serde(default)]
+	fields: LogFields,
+}
+
+#[derive(Default, Deserialize)]
+struct LogFields {
+	#[serde(default)]
+	message: String,
+}
+
+/// Log file of the monitor run as `name` (as passed to `clientside!`, e.g. `twitter_schedule`).
+pub fn log_path(name: &str) -> Result<PathBuf> {
+	let dirs = xdg::BaseDirectories::with_prefix("social_networks");
+	Ok(dirs.get_state_home().context("No state directory")?.join(format!("{name}.log")))
+}
+
+/// When the log at `path` last recorded a heartbeat, only counting those logged from `module` (and its submodules) if
+/// given, to tell monitors sharing `run_all.log` apart; `None` if it has none in its tail, or doesn't exist.
+pub fn last_heartbeat(path: &Path, module: Option<&str>) -> Result<Option<Timestamp>> {
+	if !path.exists() {
+		return Ok(None);
+	}
+	let mut file = std::fs::File::open(path)?;
+	let len = file.metadata()?.len();
+	file.seek(SeekFrom::Start(len.saturating_sub(TAIL_BYTES)))?;
+	let mut tail = Vec::new();
+	file.read_to_end(&mut tail)?;
+	Ok(parse_last_heartbeat(&String::from_utf8_lossy(&tail), module))
+}
+
+/// Events start on a line of their own `{`; anything before the first one is a cut-off event. Parsing stops at the
+/// first malformed event (usually one still being written).
+pub(crate) fn parse_last_heartbeat(log: &str, module: Option<&str>) -> Option<Timestamp> {
+	let start = if log.starts_with('{') { 0 } else { log.find("\n{")? + 1 };
+	serde_json::Deserializer::from_str(&log[start..])
+		.into_iter::<LogEvent>()
+		.map_while(Result::ok)
+		.filter(|event| event.fields.message.starts_with("Heartbeat"))
+		.filter(|event| module.is_none_or(|module| event.target.strip_prefix(module).is_some_and(|rest| rest.is_empty() || rest.starts_with("::"))))
+		.map(|event| event.timestamp)
+		.last()
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn event(timestamp: &str, message: &str) -> String {
+		event_from("social_networks_adapters::twitter", timestamp, message)
+	}
+
+	fn event_from(target: &str, timestamp: &str, message: &str) -> String {
+		let event = serde_json::json!({
+			"timestamp": timestamp,
+			"level": "INFO",
+			"fields": { "message": message },
+			"target": target,
+		});
+		format!("{}\n", serde_json::to_string_pretty(&event).unwrap())
+	}
+
+	#[test]
+	fn test_last_heartbeat_in_log() {
+		let log = [
+			event("2026-03-01T11:00:00.5Z", "Heartbeat. Time: 03/01/26-11:00"),
+			event("2026-03-01T11:30:00Z", "Heartbeat received. Time: 03/01/26-11. Since last heartbeat processed: 3 messages"),
+			event("2026-03-01T11:45:00Z", "posted tweet_id=1"),
+		]
+		.concat();
+		let last = Some("2026-03-01T11:30:00Z".parse().unwrap());
+		assert_eq!(parse_last_heartbeat(&log, None), last);
+
+		// Cut mid-event at either end, as a tail read or a concurrent write leaves it
+		let cut = format!("{}{}", &log[30..], &event("2026-03-01T12:00:00Z", "Heartbeat. Time: 03/01/26-12:00")[..40]);
+		assert_eq!(parse_last_heartbeat(&cut, None), last);
+		assert_eq!(parse_last_heartbeat(&event("2026-03-01T11:45:00Z", "posted tweet_id=1"), None), None);
+		assert_eq!(parse_last_heartbeat("", None), None);
+	}
+
+	#[test]
+	fn test_last_heartbeat_of_module() {
+		// `run_all.log` holds every monitor's heartbeats
+		let log = [
+			event_from("social_networks_adapters::twitter", "2026-03-01T11:00:00Z", "Heartbeat. Time: 03/01/26-11:00"),
+			event_from("social_networks_adapters::discord", "2026-03-01T11:30:00Z", "Heartbeat received. Time: 03/01/26-11."),
+			event_from("social_networks_adapters::twitter_schedule", "2026-03-01T11:45:00Z", "Heartbeat. Time: 03/01/26-11:45"),
+		]
+		.concat();
+		let at = |timestamp: &str| Some(timestamp.parse().unwrap());
+		assert_eq!(parse_last_heartbeat(&log, Some("social_networks_adapters::twitter")), at("2026-03-01T11:00:00Z"));
+		assert_eq!(parse_last_heartbeat(&log, Some("social_networks_adapters::discord")), at("2026-03-01T11:30:00Z"));
+		assert_eq!(parse_last_heartbeat(&log, Some("social_networks_adapters::youtube")), None);
+		assert_eq!(parse_last_heartbeat(&log, None), at("2026-03-01T11:45:00Z"));
+	}
+}
@@ -0,0 +1,90 @@
+//! Shared HTTP client setup. YouTube (and some APIs) throttle or block reqwest's default user-agent, so every
+//! outbound `reqwest` client is built here with the configured one. Responses are requested gzip/brotli-compressed
+//! (feeds are large) and decoded transparently, so callers parse bodies as usual.
+use std::sync::OnceLock;
+
+use reqwest::Client;
+
+/// Used unless the config's `user_agent` overrides it.
+pub const DEFAULT_USER_AGENT: &str = "Mozilla/5.0 (X11; Linux x86_64; rv:140.0) Gecko/20100101 Firefox/140.0";
+
+static USER_AGENT: OnceLock<String> = OnceLock::new();
+
+/// Set the user-agent for every [`client`] built afterwards. Called once at startup with the config value; later
+/// calls are ignored.
+pub fn set_user_agent(user_agent: impl Into<String>) {
+	let _ = USER_AGENT.set(user_agent.into());
+}
+
+pub fn user_agent() -> &'static str {
+	USER_AGENT.get().map_or(DEFAULT_USER_AGENT, String::as_str)
+}
+
+/// A client sending [`user_agent`]. Panics where [`Client::new`] would (TLS backend failing to initialize).
+pub fn client() -> Client {
+	client_with_user_agent(user_agent())
+}
+
+pub fn client_with_user_agent(user_agent: &str) -> Client {
+	Client::builder()
+		.user_agent(user_agent)
+		.gzip(true)
+		.brotli(true)
+		.build()
+		.expect("Failed to initialize HTTP client")
+}
+
+#[cfg(test)]
+mod tests {
+	use std::{io::Write, net::SocketAddr};
+
+	use tokio::{
+		io::{AsyncReadExt, AsyncWriteExt},
+		net::TcpListener,
+		task::JoinHandle,
+	};
+
+	use super::*;
+
+	/// Answer one request with `headers` and `body`; the task resolves to the request head, lowercased.
+	async fn serve_once(headers: &'static str, body: Vec<u8>) -> (SocketAddr, JoinHandle<String>) {
+		let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+		let addr = listener.local_addr().unwrap();
+		let server = tokio::spawn(async move {
+			let (mut socket, _) = listener.accept().await.unwrap();
+			let mut request = vec![0; 4096];
+			let n = socket.read(&mut request).await.unwrap();
+			let head = format!("HTTP/1.1 200 OK\r\n{headers}content-length: {}\r\nconnection: close\r\n\r\n", body.len());
+			socket.write_all(head.as_bytes()).await.unwrap();
+			socket.write_all(&body).await.unwrap();
+			String::from_utf8_lossy(&request[..n]).to_lowercase()
+		});
+		(addr, server)
+	}
+
+	#[tokio::test]
+	async fn test_client_sends_configured_user_agent() {
+		let (addr, server) = serve_once("", Vec::new()).await;
+		client_with_user_agent("test-agent/1.0").get(format!("http://{addr}/")).send().await.unwrap();
+		let request = server.await.unwrap();
+		assert!(request.contains("user-agent: test-agent/1.0\r\n"), "{request}");
+	}
+
+	#[tokio::test]
+	async fn test_gzip_response_is_decoded() {
+		let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+		encoder.write_all(br#"{"feed":{"entry":[{"title":"Hello"}]}}"#).unwrap();
+		let (addr, server) = serve_once("content-type: application/json\r\ncontent-encoding: gzip\r\n", encoder.finish().unwrap()).await;
+
+		let body: serde_json::Value = client().get(format!("http://{addr}/")).send().await.unwrap().json().await.unwrap();
+		assert_eq!(body["feed"]["entry"][0]["title"], "Hello");
+		let request = server.await.unwrap();
+		let accept_encoding = request.lines().find_map(|l| l.strip_prefix("accept-encoding: ")).unwrap_or_default();
+		assert!(accept_encoding.contains("gzip") && accept_encoding.contains("br"), "{request}");
+	}
+
+	#[test]
+	fn test_default_user_agent() {
+		assert_eq!(user_agent(), DEFAULT_USER_AGENT);
+	}
+}
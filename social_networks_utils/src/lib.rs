@@ -1,3 +1,13 @@
+pub mod clock;
 pub mod db;
+pub mod dedup;
+pub mod heartbeat;
+pub mod http;
+pub mod logging;
+pub mod profile;
+pub mod shutdown;
+pub mod snooze;
+pub mod status;
 pub mod telegram_utils;
+pub mod template;
 pub mod utils;
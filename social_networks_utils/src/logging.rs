@@ -0,0 +1,105 @@
+//! JSON-lines logging (`log_format = "json"`), for shipping the monitors' logs to Loki/ELK. The default stays
+//! `clientside!`'s pretty-printed JSON, which spreads each event over several lines; this writes every event as one
+//! line of the same shape, with the fields of the spans it happened in, to the same `<name>.log`.
+use std::sync::Mutex;
+
+use color_eyre::eyre::{ContextCompat, Result};
+use serde::{Deserialize, Serialize};
+use tracing::Subscriber;
+use tracing_subscriber::{EnvFilter, Layer, filter::LevelFilter, fmt, fmt::MakeWriter, layer::SubscriberExt as _, registry::LookupSpan, util::SubscriberInitExt as _};
+
+use crate::heartbeat;
+
+const FORMAT_ENV: &str = "SOCIAL_NETWORKS_LOG_FORMAT";
+
+#[derive(Clone, Copy, Debug, Default, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum LogFormat {
+	/// `clientside!`'s multi-line JSON
+	#[default]
+	Pretty,
+	/// One JSON object per line
+	Json,
+}
+
+/// `SOCIAL_NETWORKS_LOG_FORMAT` (`pretty` or `json`) if set, else the configured format.
+pub fn format(configured: LogFormat) -> LogFormat {
+	match std::env::var(FORMAT_ENV).as_deref() {
+		Ok("json") => LogFormat::Json,
+		Ok("pretty") => LogFormat::Pretty,
+		Ok(other) => {
+			eprintln!("Ignoring {FORMAT_ENV}={other}, expected `pretty` or `json`");
+			configured
+		}
+		Err(_) => configured,
+	}
+}
+
+/// Log the monitor run as `name` to its log file in JSON lines, and warnings and errors to stderr, like `clientside!`
+/// does. `RUST_LOG` overrides `directives`, which default to `info`.
+pub fn init_json_lines(name: &str, directives: Option<&str>) -> Result<()> {
+	let path = heartbeat::log_path(name)?;
+	std::fs::create_dir_all(path.parent().context("Log path has no parent directory")?)?;
+	let file = std::fs::File::create(&path)?;
+	let filter = match EnvFilter::try_from_default_env() {
+		Ok(filter) => filter,
+		Err(_) => EnvFilter::try_new(directives.unwrap_or("info"))?,
+	};
+	let stderr = fmt::layer().with_writer(std::io::stderr).with_filter(LevelFilter::WARN);
+	tracing_subscriber::registry().with(filter).with(json_layer(Mutex::new(file))).with(stderr).try_init()?;
+	Ok(())
+}
+
+fn json_layer<S, W>(writer: W) -> impl Layer<S>
+where
+	S: Subscriber + for<'a> LookupSpan<'a>,
+	W: for<'w> MakeWriter<'w> + Send + Sync + 'static, {
+	fmt::layer()
+		.json()
+		.with_current_span(true)
+		.with_span_list(true)
+		.with_file(true)
+		.with_line_number(true)
+		.with_ansi(false)
+		.with_writer(writer)
+}
+
+#[cfg(test)]
+mod tests {
+	use std::{io::Write, sync::Arc};
+
+	use super::*;
+
+	#[derive(Clone, Default)]
+	struct Sink(Arc<Mutex<Vec<u8>>>);
+	impl Write for Sink {
+		fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+			self.0.lock().unwrap().write(buf)
+		}
+
+		fn flush(&mut self) -> std::io::Result<()> {
+			Ok(())
+		}
+	}
+
+	#[test]
+	fn test_json_lines_carry_span_fields() {
+		let sink = Sink::default();
+		let writer = sink.clone();
+		let subscriber = tracing_subscriber::registry().with(json_layer(move || writer.clone()));
+		tracing::subscriber::with_default(subscriber, || {
+			let _span = tracing::info_span!("check_channel", channel_name = "bankless").entered();
+			tracing::info!("Heartbeat. Time: 03/01/26-11:00");
+			tracing::info!(video_id = "abc123", "New upload");
+		});
+
+		let log = String::from_utf8(sink.0.lock().unwrap().clone()).unwrap();
+		let events: Vec<serde_json::Value> = log.lines().map(|line| serde_json::from_str(line).unwrap()).collect();
+		assert_eq!(events.len(), 2);
+		assert_eq!(events[1]["fields"]["video_id"], "abc123");
+		assert_eq!(events[1]["span"]["channel_name"], "bankless");
+		assert_eq!(events[1]["spans"][0]["name"], "check_channel");
+		// `health` finds heartbeats in either format
+		assert!(heartbeat::parse_last_heartbeat(&log, None).is_some());
+	}
+}
@@ -0,0 +1,110 @@
+//! Timing of polling monitor cycles. Always logged at debug; `--profile` logs it at info, so it shows without
+//! raising the log level.
+use std::{
+	sync::atomic::{AtomicBool, Ordering},
+	time::{Duration, Instant},
+};
+
+use tracing::{debug, info};
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+
+pub fn set_enabled(enabled: bool) {
+	ENABLED.store(enabled, Ordering::Relaxed);
+}
+
+pub fn enabled() -> bool {
+	ENABLED.load(Ordering::Relaxed)
+}
+
+/// Times one cycle of a monitor and each item (channel, list member) it works through.
+#[derive(Debug)]
+pub struct CycleTimer {
+	monitor: &'static str,
+	started: Instant,
+	items: usize,
+	slowest: Option<(String, Duration)>,
+}
+
+impl CycleTimer {
+	pub fn start(monitor: &'static str) -> Self {
+		Self {
+			monitor,
+			started: Instant::now(),
+			items: 0,
+			slowest: None,
+		}
+	}
+
+	/// Record that `label` took `elapsed`.
+	pub fn item(&mut self, label: &str, elapsed: Duration) {
+		emit(&format!("{} item {label} took {}", self.monitor, millis(elapsed)));
+		self.items += 1;
+		if self.slowest.as_ref().is_none_or(|(_, slowest)| elapsed > *slowest) {
+			self.slowest = Some((label.to_string(), elapsed));
+		}
+	}
+
+	/// Time `label` until the returned guard is dropped, so every early `continue` still records it.
+	pub fn time(&mut self, label: &str) -> ItemTimer<'_> {
+		ItemTimer {
+			timer: self,
+			label: label.to_string(),
+			started: Instant::now(),
+		}
+	}
+
+	/// Log the whole cycle's duration along with its slowest item.
+	pub fn finish(self) {
+		emit(&self.summary(self.started.elapsed()));
+	}
+
+	fn summary(&self, elapsed: Duration) -> String {
+		let mut summary = format!("{} cycle took {}", self.monitor, millis(elapsed));
+		if let Some((label, slowest)) = &self.slowest {
+			summary.push_str(&format!(" over {} items, slowest {label} ({})", self.items, millis(*slowest)));
+		}
+		summary
+	}
+}
+
+pub struct ItemTimer<'a> {
+	timer: &'a mut CycleTimer,
+	label: String,
+	started: Instant,
+}
+
+impl Drop for ItemTimer<'_> {
+	fn drop(&mut self) {
+		self.timer.item(&self.label, self.started.elapsed());
+	}
+}
+
+fn emit(message: &str) {
+	if enabled() {
+		info!("{message}");
+	} else {
+		debug!("{message}");
+	}
+}
+
+fn millis(d: Duration) -> String {
+	format!("{}ms", d.as_millis())
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_cycle_timer_keeps_slowest() {
+		let mut timer = CycleTimer::start("youtube");
+		assert_eq!(timer.summary(Duration::from_millis(5)), "youtube cycle took 5ms");
+
+		timer.item("fast", Duration::from_millis(120));
+		timer.item("slow", Duration::from_millis(2300));
+		timer.item("medium", Duration::from_millis(800));
+		drop(timer.time("instant"));
+		assert_eq!(timer.summary(Duration::from_millis(3300)), "youtube cycle took 3300ms over 4 items, slowest slow (2300ms)");
+	}
+}
@@ -0,0 +1,170 @@
+//! Clean stops. Ctrl-C, SIGTERM, or the `--max-runtime` deadline passing requests a shutdown; monitors check for it
+//! where stopping loses nothing (between cycles, while waiting for the next event) and return, so their state is
+//! persisted before the process exits. A second Ctrl-C exits right away.
+use std::{
+	sync::{LazyLock, OnceLock},
+	time::Duration,
+};
+
+use tokio::{sync::watch, time};
+use tracing::{info, warn};
+
+static SHUTDOWN: LazyLock<Shutdown> = LazyLock::new(Shutdown::new);
+static MAX_RUNTIME: OnceLock<Duration> = OnceLock::new();
+
+/// A shutdown request, set once by whichever stop comes first, with the reason for it. Monitors use the process-wide
+/// one through the free functions below; tests make their own, so they neither share it nor install signal handlers.
+#[derive(Clone, Debug)]
+pub struct Shutdown(watch::Sender<Option<&'static str>>);
+
+impl Default for Shutdown {
+	fn default() -> Self {
+		Self::new()
+	}
+}
+
+impl Shutdown {
+	pub fn new() -> Self {
+		Self(watch::Sender::new(None))
+	}
+
+	/// Request a shutdown once `max_runtime` has passed. Must be called from within a tokio runtime.
+	pub fn request_after(&self, max_runtime: Duration) {
+		let shutdown = self.clone();
+		tokio::spawn(async move {
+			time::sleep(max_runtime).await;
+			shutdown.request("max runtime reached");
+		});
+	}
+
+	/// Ask every monitor watching this to stop. Only the first reason is kept.
+	pub fn request(&self, reason: &'static str) {
+		let first = self.0.send_if_modified(|requested| {
+			if requested.is_some() {
+				return false;
+			}
+			*requested = Some(reason);
+			true
+		});
+		if first {
+			info!("Shutting down: {reason}");
+		}
+	}
+
+	pub fn is_requested(&self) -> bool {
+		self.0.borrow().is_some()
+	}
+
+	/// Why the shutdown was requested, once it was.
+	pub fn reason(&self) -> Option<&'static str> {
+		*self.0.borrow()
+	}
+
+	/// Resolves once a shutdown is requested (right away if it already was).
+	pub async fn requested(&self) {
+		let _ = self.0.subscribe().wait_for(Option::is_some).await;
+	}
+
+	/// Sleep for `duration`, waking early if a shutdown is requested; whether one was.
+	pub async fn requested_during(&self, duration: Duration) -> bool {
+		time::timeout(duration, self.requested()).await.is_ok()
+	}
+}
+
+/// Request a shutdown once the process ran this long, counted from [`install`]. Called once at startup with the
+/// `--max-runtime` value; later calls are ignored.
+pub fn set_max_runtime(max_runtime: Duration) {
+	let _ = MAX_RUNTIME.set(max_runtime);
+}
+
+/// Start turning signals and the max runtime into a shutdown request. Must be called from within a tokio runtime.
+pub fn install() {
+	if let Some(&max_runtime) = MAX_RUNTIME.get() {
+		SHUTDOWN.request_after(max_runtime);
+	}
+	tokio::spawn(watch_for_shutdown());
+}
+
+async fn watch_for_shutdown() {
+	tokio::select! {
+		() = SHUTDOWN.requested() => {}
+		() = sigterm() => SHUTDOWN.request("received SIGTERM"),
+		Ok(()) = tokio::signal::ctrl_c() => SHUTDOWN.request("received Ctrl-C"),
+	}
+	eprintln!("Stopping ({}), Ctrl-C to exit right away", SHUTDOWN.reason().unwrap_or("requested"));
+	// Polling monitors only stop between cycles; an impatient second Ctrl-C shouldn't have to wait for that
+	if tokio::signal::ctrl_c().await.is_ok() {
+		warn!("Second Ctrl-C, exiting without waiting for monitors to stop");
+		std::process::exit(130);
+	}
+}
+
+async fn sigterm() {
+	match tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate()) {
+		Ok(mut signal) => {
+			signal.recv().await;
+		}
+		Err(e) => {
+			warn!("Not handling SIGTERM: {e}");
+			std::future::pending().await
+		}
+	}
+}
+
+/// Ask every monitor to stop.
+pub fn request(reason: &'static str) {
+	SHUTDOWN.request(reason);
+}
+
+pub fn is_requested() -> bool {
+	SHUTDOWN.is_requested()
+}
+
+/// Resolves once a shutdown is requested (right away if it already was).
+pub async fn requested() {
+	SHUTDOWN.requested().await;
+}
+
+/// Sleep for `duration`, waking early if a shutdown is requested; whether one was.
+pub async fn requested_during(duration: Duration) -> bool {
+	SHUTDOWN.requested_during(duration).await
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[tokio::test(start_paused = true)]
+	async fn test_loop_stops_at_max_runtime() {
+		let shutdown = Shutdown::new();
+		shutdown.request_after(Duration::from_secs(60));
+		let started = time::Instant::now();
+
+		// A monitor loop: a cycle, then the wait for the next one
+		let mut cycles = 0;
+		loop {
+			cycles += 1;
+			if shutdown.requested_during(Duration::from_secs(25)).await {
+				break;
+			}
+		}
+		assert_eq!(cycles, 3);
+		assert_eq!(started.elapsed(), Duration::from_secs(60));
+		assert!(shutdown.is_requested());
+		assert_eq!(shutdown.reason(), Some("max runtime reached"));
+		// Later waits don't block
+		assert!(shutdown.requested_during(Duration::from_secs(25)).await);
+		assert_eq!(started.elapsed(), Duration::from_secs(60));
+	}
+
+	#[tokio::test]
+	async fn test_first_reason_wins() {
+		let shutdown = Shutdown::new();
+		assert!(!shutdown.is_requested());
+		shutdown.request("received SIGTERM");
+		shutdown.request("received Ctrl-C");
+		assert_eq!(shutdown.reason(), Some("received SIGTERM"));
+		// The process-wide one is untouched
+		assert!(!is_requested());
+	}
+}
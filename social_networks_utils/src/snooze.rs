@@ -0,0 +1,161 @@
+//! Temporary per-source mutes, set at runtime with `social_networks snooze` and shared by every
+//! running monitor through a state file. Monitors check [`is_snoozed`] right before notifying.
+use std::{collections::BTreeMap, path::PathBuf};
+
+use clap::Args;
+use color_eyre::eyre::{Result, bail};
+use jiff::Timestamp;
+use serde::{Deserialize, Serialize};
+use tracing::warn;
+
+use crate::utils::{format_duration, parse_duration, write_atomic};
+
+/// Sources that can be snoozed; each monitor checks its own name.
+pub const SOURCES: [&str; 5] = ["dms", "email", "telegram_channel_watch", "twitter", "youtube"];
+
+#[derive(Args)]
+pub struct SnoozeArgs {
+	/// Source to mute (one of: dms, email, telegram_channel_watch, twitter, youtube). Lists active snoozes if omitted.
+	pub source: Option<String>,
+	/// How long to mute for, e.g. `1h`, `30m`, `2h 15m`
+	#[arg(required_unless_present = "clear", value_parser = parse_duration)]
+	pub duration: Option<std::time::Duration>,
+	/// Lift the snooze on `source` instead
+	#[arg(long, requires = "source")]
+	pub clear: bool,
+}
+
+/// Muted-until timestamp per source.
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Serialize)]
+pub struct Snoozes(BTreeMap<String, Timestamp>);
+
+impl Snoozes {
+	pub fn load() -> Result<Self> {
+		let path = state_file()?;
+		if !path.exists() {
+			return Ok(Self::default());
+		}
+		Ok(serde_json::from_str(&std::fs::read_to_string(path)?)?)
+	}
+
+	pub fn save(&self) -> Result<()> {
+		write_atomic(&state_file()?, serde_json::to_string_pretty(self)?)?;
+		Ok(())
+	}
+
+	pub fn snooze(&mut self, source: &str, until: Timestamp) {
+		self.0.insert(source.to_string(), until);
+	}
+
+	/// Returns whether `source` was snoozed.
+	pub fn clear(&mut self, source: &str) -> bool {
+		self.0.remove(source).is_some()
+	}
+
+	/// `Some(until)` while `source` is muted at `now`.
+	pub fn muted_until(&self, source: &str, now: Timestamp) -> Option<Timestamp> {
+		self.0.get(source).copied().filter(|until| *until > now)
+	}
+
+	/// Drop snoozes that ran out by `now`; returns whether any were.
+	pub fn clear_expired(&mut self, now: Timestamp) -> bool {
+		let before = self.0.len();
+		self.0.retain(|_, until| *until > now);
+		self.0.len() != before
+	}
+
+	pub fn active(&self, now: Timestamp) -> impl Iterator<Item = (&str, Timestamp)> {
+		self.0.iter().filter(move |(_, until)| **until > now).map(|(source, until)| (source.as_str(), *until))
+	}
+}
+
+fn state_file() -> Result<PathBuf> {
+	Ok(xdg::BaseDirectories::with_prefix("social_networks").place_state_file("snoozes.json")?)
+}
+
+/// Whether notifications from `source` are muted right now. Re-reads the state file so `snooze` takes effect
+/// without restarting monitors. Only reads it: expired entries are left for `snooze` to clean up, so monitors never
+/// race the CLI to write it. A broken state file never mutes.
+pub fn is_snoozed(source: &str) -> bool {
+	match Snoozes::load() {
+		Ok(snoozes) => snoozes.muted_until(source, Timestamp::now()).is_some(),
+		Err(e) => {
+			warn!("Failed to read snoozes, ignoring them: {e:#}");
+			false
+		}
+	}
+}
+
+pub fn run(args: SnoozeArgs) -> Result<()> {
+	let now = Timestamp::now();
+	let mut snoozes = Snoozes::load()?;
+	snoozes.clear_expired(now);
+
+	match (args.source, args.duration) {
+		(None, _) => {
+			let mut any = false;
+			for (source, until) in snoozes.active(now) {
+				println!(
+					"{source}: muted until {until} ({:#} left)",
+					until.duration_since(now).round(jiff::Unit::Second).unwrap_or_default()
+				);
+				any = true;
+			}
+			if !any {
+				println!("No active snoozes");
+			}
+		}
+		(Some(source), _) if args.clear =>
+			if snoozes.clear(&source) {
+				println!("Unmuted {source}");
+			} else {
+				println!("{source} was not snoozed");
+			},
+		(Some(source), Some(duration)) => {
+			if !SOURCES.contains(&source.as_str()) {
+				bail!("Unknown source `{source}`, expected one of: {}", SOURCES.join(", "));
+			}
+			if duration.is_zero() {
+				bail!("Snooze duration must be positive, got {}", format_duration(duration));
+			}
+			let until = now.checked_add(duration)?;
+			snoozes.snooze(&source, until);
+			println!("Muted {source} until {until}");
+		}
+		(Some(_), None) => unreachable!("clap requires a duration unless --clear"),
+	}
+	snoozes.save()
+}
+
+#[cfg(test)]
+mod tests {
+	use jiff::SignedDuration;
+
+	use super::*;
+
+	#[test]
+	fn test_snooze_suppresses_until_expiry() {
+		let now: Timestamp = "2026-03-01T12:00:00Z".parse().unwrap();
+		let mut snoozes = Snoozes::default();
+		snoozes.snooze("twitter", now + SignedDuration::from_hours(1));
+
+		assert!(snoozes.muted_until("twitter", now).is_some());
+		assert!(snoozes.muted_until("twitter", now + SignedDuration::from_mins(59)).is_some());
+		assert!(snoozes.muted_until("youtube", now).is_none());
+		// Auto-clears once the timestamp passes
+		let later = now + SignedDuration::from_hours(1);
+		assert!(snoozes.muted_until("twitter", later).is_none());
+		assert!(!snoozes.clear_expired(now));
+		assert!(snoozes.clear_expired(later));
+		assert_eq!(snoozes, Snoozes::default());
+	}
+
+	#[test]
+	fn test_snoozes_roundtrip() {
+		let mut snoozes = Snoozes::default();
+		snoozes.snooze("email", "2026-03-01T13:00:00Z".parse().unwrap());
+		let json = serde_json::to_string(&snoozes).unwrap();
+		assert_eq!(json, r#"{"email":"2026-03-01T13:00:00Z"}"#);
+		assert_eq!(serde_json::from_str::<Snoozes>(&json).unwrap(), snoozes);
+	}
+}
@@ -0,0 +1,273 @@
+//! Live status of each running monitor. Monitors report connection changes and handled events to a process-wide
+//! registry, which mirrors every monitor into its own `monitors/<name>.json` state file so `social_networks health`
+//! (a separate process) can read them.
+use std::{
+	collections::HashMap,
+	path::{Path, PathBuf},
+	sync::{LazyLock, Mutex},
+	time::{Duration, Instant},
+};
+
+use color_eyre::eyre::{ContextCompat, Result};
+use jiff::Timestamp;
+use serde::{Deserialize, Serialize};
+use tracing::warn;
+
+use crate::utils::write_atomic;
+
+/// Event-only updates hit the disk at most this often; state changes are written right away.
+const FLUSH_EVERY: Duration = Duration::from_secs(5);
+
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MonitorState {
+	Connected,
+	Reconnecting,
+	Error,
+	/// Shut down cleanly (Ctrl-C, SIGTERM, `--max-runtime`). A monitor last in any other state whose process is gone
+	/// died without getting to say so.
+	Stopped,
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct MonitorStatus {
+	pub name: String,
+	pub state: MonitorState,
+	pub last_event_at: Option<Timestamp>,
+	/// Events handled since the monitor started.
+	pub events: u64,
+	/// Recoverable failures (reconnects) plus the terminal one, if any.
+	pub errors: u64,
+	pub updated_at: Timestamp,
+	/// Process that reported it, so readers can tell a stale file from a live monitor.
+	pub pid: u32,
+}
+
+impl MonitorStatus {
+	fn new(name: &str) -> Self {
+		Self {
+			name: name.to_string(),
+			state: MonitorState::Connected,
+			last_event_at: None,
+			events: 0,
+			errors: 0,
+			updated_at: Timestamp::now(),
+			pid: std::process::id(),
+		}
+	}
+}
+
+struct Entry {
+	status: MonitorStatus,
+	flushed_at: Option<Instant>,
+}
+
+pub struct StatusRegistry {
+	/// `None` when no state directory is available; statuses then only live in memory.
+	dir: Option<PathBuf>,
+	monitors: Mutex<HashMap<String, Entry>>,
+}
+
+static REGISTRY: LazyLock<StatusRegistry> = LazyLock::new(|| StatusRegistry::new(state_dir().ok()));
+
+impl StatusRegistry {
+	pub fn new(dir: Option<PathBuf>) -> Self {
+		Self {
+			dir,
+			monitors: Mutex::new(HashMap::new()),
+		}
+	}
+
+	pub fn connected(&self, name: &str) {
+		self.update(name, true, |s| s.state = MonitorState::Connected);
+	}
+
+	pub fn event(&self, name: &str) {
+		self.update(name, false, |s| {
+			s.events += 1;
+			s.last_event_at = Some(Timestamp::now());
+		});
+	}
+
+	pub fn error(&self, name: &str) {
+		self.update(name, true, |s| s.errors += 1);
+	}
+
+	pub fn reconnecting(&self, name: &str) {
+		self.update(name, true, |s| {
+			s.state = MonitorState::Reconnecting;
+			s.errors += 1;
+		});
+	}
+
+	pub fn failed(&self, name: &str) {
+		self.update(name, true, |s| {
+			s.state = MonitorState::Error;
+			s.errors += 1;
+		});
+	}
+
+	pub fn get(&self, name: &str) -> Option<MonitorStatus> {
+		self.monitors.lock().unwrap().get(name).map(|e| e.status.clone())
+	}
+
+	/// Mark every monitor that didn't fail as stopped, then [`Self::flush`].
+	pub fn stopped(&self) {
+		for entry in self.monitors.lock().unwrap().values_mut() {
+			if entry.status.state != MonitorState::Error {
+				entry.status.state = MonitorState::Stopped;
+				entry.status.updated_at = Timestamp::now();
+			}
+		}
+		self.flush();
+	}
+
+	/// Write out every status, including events still held back by [`FLUSH_EVERY`].
+	pub fn flush(&self) {
+		let Some(dir) = &self.dir else { return };
+		for (name, entry) in self.monitors.lock().unwrap().iter_mut() {
+			entry.flushed_at = Some(Instant::now());
+			if let Err(e) = write_status(dir, &entry.status) {
+				warn!("Failed to write status of {name}: {e:#}");
+			}
+		}
+	}
+
+	fn update(&self, name: &str, force_flush: bool, f: impl FnOnce(&mut MonitorStatus)) {
+		let mut monitors = self.monitors.lock().unwrap();
+		let entry = monitors.entry(name.to_string()).or_insert_with(|| Entry {
+			status: MonitorStatus::new(name),
+			flushed_at: None,
+		});
+		f(&mut entry.status);
+		entry.status.updated_at = Timestamp::now();
+
+		let due = force_flush || entry.flushed_at.is_none_or(|at| at.elapsed() >= FLUSH_EVERY);
+		if let (true, Some(dir)) = (due, &self.dir) {
+			entry.flushed_at = Some(Instant::now());
+			if let Err(e) = write_status(dir, &entry.status) {
+				warn!("Failed to write status of {name}: {e:#}");
+			}
+		}
+	}
+}
+
+fn write_status(dir: &Path, status: &MonitorStatus) -> Result<()> {
+	std::fs::create_dir_all(dir)?;
+	// Replaced whole, so `health` never reads a half-written file
+	write_atomic(&dir.join(format!("{}.json", status.name)), serde_json::to_string_pretty(status)?)?;
+	Ok(())
+}
+
+fn state_dir() -> Result<PathBuf> {
+	let dirs = xdg::BaseDirectories::with_prefix("social_networks");
+	Ok(dirs.get_state_home().context("No state directory")?.join("monitors"))
+}
+
+pub fn connected(name: &str) {
+	REGISTRY.connected(name);
+}
+
+/// Count one handled event (a message, a checked feed, a poll cycle).
+pub fn event(name: &str) {
+	REGISTRY.event(name);
+}
+
+/// Count a recoverable error the monitor carried on past without reconnecting (one channel or list failing).
+pub fn error(name: &str) {
+	REGISTRY.error(name);
+}
+
+/// The monitor hit a recoverable error and is about to retry.
+pub fn reconnecting(name: &str) {
+	REGISTRY.reconnecting(name);
+}
+
+/// The monitor hit a terminal error and is exiting.
+pub fn failed(name: &str) {
+	REGISTRY.failed(name);
+}
+
+/// Record that every monitor of this process that didn't fail stopped cleanly, and persist every status, before a
+/// clean exit.
+pub fn stopped() {
+	REGISTRY.stopped();
+}
+
+/// Status as seen by this process.
+pub fn get(name: &str) -> Option<MonitorStatus> {
+	REGISTRY.get(name)
+}
+
+/// Last reported status of every monitor that ever ran on this machine, by name.
+pub fn read_all() -> Result<Vec<MonitorStatus>> {
+	read_dir(&state_dir()?)
+}
+
+fn read_status(path: &Path) -> Result<MonitorStatus> {
+	Ok(serde_json::from_str(&std::fs::read_to_string(path)?)?)
+}
+
+/// A status file that can't be read or parsed is skipped with a warning, so one broken file doesn't hide the others.
+pub fn read_dir(dir: &Path) -> Result<Vec<MonitorStatus>> {
+	if !dir.exists() {
+		return Ok(Vec::new());
+	}
+	let mut statuses = Vec::new();
+	for entry in std::fs::read_dir(dir)? {
+		let path = entry?.path();
+		if path.extension().is_none_or(|ext| ext != "json") {
+			continue;
+		}
+		match read_status(&path) {
+			Ok(status) => statuses.push(status),
+			Err(e) => warn!("Skipping status file {}: {e:#}", path.display()),
+		}
+	}
+	statuses.sort_by(|a, b| a.name.cmp(&b.name));
+	Ok(statuses)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_registry_flushes_to_state_files() {
+		let dir = std::env::temp_dir().join(format!("social_networks_status_test_{}", std::process::id()));
+		let _ = std::fs::remove_dir_all(&dir);
+		let registry = StatusRegistry::new(Some(dir.clone()));
+
+		registry.connected("youtube");
+		let on_disk = read_dir(&dir).unwrap();
+		assert_eq!(on_disk.len(), 1);
+		assert_eq!(on_disk[0].state, MonitorState::Connected);
+
+		// Events right after a flush stay in memory
+		registry.event("youtube");
+		assert_eq!(read_dir(&dir).unwrap()[0].events, 0);
+		assert_eq!(registry.get("youtube").unwrap().events, 1);
+
+		// A state change writes everything accumulated so far
+		registry.reconnecting("youtube");
+		let status = &read_dir(&dir).unwrap()[0];
+		assert_eq!((status.state, status.events, status.errors), (MonitorState::Reconnecting, 1, 1));
+		assert!(status.last_event_at.is_some());
+		assert_eq!(status.pid, std::process::id());
+
+		// A clean stop writes out held-back events too, and leaves a failed monitor failed
+		registry.event("youtube");
+		assert_eq!(read_dir(&dir).unwrap()[0].events, 1);
+		registry.failed("email");
+		registry.stopped();
+		let youtube = read_dir(&dir).unwrap().into_iter().find(|s| s.name == "youtube").unwrap();
+		assert_eq!((youtube.state, youtube.events), (MonitorState::Stopped, 2));
+
+		std::fs::write(dir.join("notes.txt"), "not a status").unwrap();
+		std::fs::write(dir.join("twitter.json"), "{\"name\":").unwrap();
+		let names: Vec<_> = read_dir(&dir).unwrap().into_iter().map(|s| (s.name, s.state)).collect();
+		assert_eq!(names, vec![("email".to_string(), MonitorState::Error), ("youtube".to_string(), MonitorState::Stopped)]);
+
+		std::fs::remove_dir_all(&dir).unwrap();
+	}
+}
@@ -7,9 +7,18 @@ use std::{future::Future, path::Path, pin::Pin, sync::Arc};
 use color_eyre::eyre::{Result, bail};
 use grammers_client::{Client, SignInError, client::UpdatesConfiguration};
 use grammers_mtsender::SenderPool;
-use grammers_session::storages::SqliteSession;
+use grammers_session::{
+	Session,
+	storages::SqliteSession,
+	types::{UpdateState, UpdatesState},
+};
+use jiff::{SignedDuration, Timestamp};
 use tracing::{debug, error, info};
 
+/// Oldest saved update position still worth catching up from; Telegram replays everything since, and a backlog
+/// older than this isn't news anymore.
+pub const MAX_CATCH_UP_AGE: SignedDuration = SignedDuration::from_hours(6);
+
 /// A pinned future representing the MTProto runner.
 /// Store this in your state and poll it alongside other futures using `select`.
 pub type RunnerFuture = Pin<Box<dyn Future<Output = ()> + Send>>;
@@ -29,6 +38,9 @@ pub struct ConnectionConfig<'a> {
 	pub api_hash: &'a str,
 	/// Session file suffix (e.g., "_dm" for DM monitor, "" for main)
 	pub session_suffix: &'a str,
+	/// Replay updates missed since the position last saved with `UpdateStream::sync_update_state`, if
+	/// [`should_catch_up`] agrees. Otherwise only updates from now on are streamed.
+	pub catch_up: bool,
 }
 
 /// Establishes a Telegram connection with proper session handling.
@@ -61,6 +73,21 @@ pub async fn connect(config: ConnectionConfig<'_>) -> Result<TelegramConnection>
 		}
 	};
 
+	// Read before the dialog pre-fetch below, which overwrites each channel's saved position with its current one
+	let saved_state = if config.catch_up {
+		let saved = session.updates_state().await?;
+		let last_written = std::fs::metadata(&session_file).and_then(|m| m.modified()).ok().and_then(|t| Timestamp::try_from(t).ok());
+		if should_catch_up(&saved, last_written, Timestamp::now()) {
+			info!("Catching up on updates since the saved position (written {last_written:?})");
+			Some(saved)
+		} else {
+			info!("No recent saved update position, starting from now");
+			None
+		}
+	} else {
+		None
+	};
+
 	info!("Connecting to Telegram with api_id: {}", config.api_id);
 	let pool = SenderPool::new(Arc::clone(&session), config.api_id);
 	let SenderPool { runner, handle, updates } = pool;
@@ -83,20 +110,38 @@ pub async fn connect(config: ConnectionConfig<'_>) -> Result<TelegramConnection>
 	}
 	info!("Cached {dialog_count} dialogs");
 
+	let catch_up = saved_state.is_some();
+	if let Some(saved) = saved_state {
+		let current = session.updates_state().await?;
+		session.set_update_state(UpdateState::All(restore_channel_positions(saved, current))).await?;
+	}
+
 	let updates = client
-		.stream_updates(
-			updates,
-			UpdatesConfiguration {
-				catch_up: false,
-				..Default::default()
-			},
-		)
+		.stream_updates(updates, UpdatesConfiguration { catch_up, ..Default::default() })
 		.await
 		.map_err(|e| color_eyre::eyre::eyre!(e))?;
 
 	Ok(TelegramConnection { client, updates, runner })
 }
 
+/// Whether to resume from `saved` rather than start from now: there must be a saved position at all (fresh sessions
+/// have none), and the session must have been written to (which running monitors do as they save the position)
+/// within [`MAX_CATCH_UP_AGE`].
+pub fn should_catch_up(saved: &UpdatesState, last_written: Option<Timestamp>, now: Timestamp) -> bool {
+	saved.pts != 0 && last_written.is_some_and(|written| now.duration_since(written) <= MAX_CATCH_UP_AGE)
+}
+
+/// The `saved` position, keeping `current` positions only for channels `saved` doesn't know (joined since).
+fn restore_channel_positions(saved: UpdatesState, current: UpdatesState) -> UpdatesState {
+	let mut channels = saved.channels;
+	for channel in current.channels {
+		if !channels.iter().any(|c| c.id == channel.id) {
+			channels.push(channel);
+		}
+	}
+	UpdatesState { channels, ..saved }
+}
+
 /// Check stack usage and return true if we should force a reconnect.
 /// Logs a critical warning if stack usage is above threshold.
 pub fn should_reconnect_for_stack() -> bool {
@@ -154,3 +199,41 @@ async fn authenticate(client: &Client, phone: &str, api_hash: &str, session_file
 	info!("Session saved successfully to {}", session_file.display());
 	Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+	use grammers_session::types::ChannelState;
+
+	use super::*;
+
+	fn state(pts: i32, channels: &[(i64, i32)]) -> UpdatesState {
+		UpdatesState {
+			pts,
+			qts: 0,
+			date: 1_772_366_400,
+			seq: 0,
+			channels: channels.iter().map(|&(id, pts)| ChannelState { id, pts }).collect(),
+		}
+	}
+
+	#[test]
+	fn test_should_catch_up() {
+		let now: Timestamp = "2026-03-01T12:00:00Z".parse().unwrap();
+		let saved = state(100, &[(1, 50)]);
+		assert!(should_catch_up(&saved, Some(now - SignedDuration::from_mins(2)), now));
+		assert!(should_catch_up(&saved, Some(now - MAX_CATCH_UP_AGE), now));
+		// Too stale: starting from now beats forwarding hours of backlog
+		assert!(!should_catch_up(&saved, Some(now - MAX_CATCH_UP_AGE - SignedDuration::from_secs(1)), now));
+		assert!(!should_catch_up(&saved, None, now));
+		// Never saved a position
+		assert!(!should_catch_up(&UpdatesState::default(), Some(now), now));
+	}
+
+	#[test]
+	fn test_restore_channel_positions() {
+		let restored = restore_channel_positions(state(100, &[(1, 50), (2, 70)]), state(120, &[(1, 60), (3, 10)]));
+		assert_eq!(restored.pts, 100);
+		let channels: Vec<_> = restored.channels.iter().map(|c| (c.id, c.pts)).collect();
+		assert_eq!(channels, [(1, 50), (2, 70), (3, 10)]);
+	}
+}
@@ -0,0 +1,150 @@
+//! `${var}` text templates, shared by tweet templates and notification formats. `$${` is an escaped, literal `${`,
+//! and a lone `$` is just text. `${var?...}` is an optional section: its body, which may hold placeholders of its own
+//! and span lines, is rendered only when `var` has a non-empty value. The body runs to the first `}` not closing a
+//! placeholder inside it.
+use std::collections::HashMap;
+
+use color_eyre::eyre::{Result, bail};
+
+/// A piece of template text: literal, a `${name}` placeholder, or a `${name?...}` section.
+#[derive(Debug, PartialEq, Eq)]
+pub enum Segment<'a> {
+	Text(&'a str),
+	Variable(&'a str),
+	Optional { name: &'a str, body: Vec<Segment<'a>> },
+}
+
+/// Split a template into segments. Errors on a `${` not closed on its own line before the next `$`, on a section
+/// never closed, and on an empty `${}`, which would otherwise go out as literal text.
+pub fn segments(text: &str) -> Result<Vec<Segment<'_>>> {
+	Ok(parse(text, false)?.0)
+}
+
+/// Segments up to the end of `text`, or in a section up to its closing `}`; also returns what follows. `None` when a
+/// section runs out of text before it is closed.
+fn parse(text: &str, in_section: bool) -> Result<(Vec<Segment<'_>>, Option<&str>)> {
+	let mut segments = Vec::new();
+	let mut rest = text;
+
+	loop {
+		let stop = if in_section { rest.find(['$', '}']) } else { rest.find('$') };
+		let Some(stop) = stop else {
+			if !rest.is_empty() {
+				segments.push(Segment::Text(rest));
+			}
+			return Ok((segments, (!in_section).then_some("")));
+		};
+		let (before, from_stop) = rest.split_at(stop);
+		if !before.is_empty() {
+			segments.push(Segment::Text(before));
+		}
+		if let Some(after) = from_stop.strip_prefix('}') {
+			return Ok((segments, Some(after)));
+		}
+
+		if let Some(after) = from_stop.strip_prefix("$${") {
+			segments.push(Segment::Text("${"));
+			rest = after;
+		} else if let Some(after) = from_stop.strip_prefix("${") {
+			let end = after.find(['}', '?', '\n', '$']).filter(|&end| after[end..].starts_with(['}', '?']));
+			let Some(end) = end else {
+				let unclosed: String = from_stop.chars().take_while(|&c| c != '\n').take(20).collect();
+				bail!("Unclosed placeholder `{}`, expected `${{name}}` (or `$${{` for a literal `${{`)", unclosed.trim_end());
+			};
+			if end == 0 {
+				bail!("Empty placeholder `${{{}`", &after[..1]);
+			}
+			let name = &after[..end];
+			if after[end..].starts_with('}') {
+				segments.push(Segment::Variable(name));
+				rest = &after[end + 1..];
+			} else {
+				let (body, after_section) = parse(&after[end + 1..], true)?;
+				let Some(after_section) = after_section else {
+					bail!("Unclosed placeholder `${{{name}?`, an optional section needs a closing `}}`");
+				};
+				segments.push(Segment::Optional { name, body });
+				rest = after_section;
+			}
+		} else {
+			segments.push(Segment::Text("$"));
+			rest = &from_stop[1..];
+		}
+	}
+}
+
+/// Every variable `text` uses, in order of appearance, sections' own names included.
+pub fn variable_names(text: &str) -> Result<Vec<String>> {
+	fn collect(segments: &[Segment<'_>], names: &mut Vec<String>) {
+		for segment in segments {
+			match segment {
+				Segment::Text(_) => {}
+				Segment::Variable(name) => names.push(name.to_string()),
+				Segment::Optional { name, body } => {
+					names.push(name.to_string());
+					collect(body, names);
+				}
+			}
+		}
+	}
+	let mut names = Vec::new();
+	collect(&segments(text)?, &mut names);
+	Ok(names)
+}
+
+/// Placeholders without a value are left as they were; sections without a non-empty value are left out. Substituted
+/// values are not parsed again.
+pub fn render(text: &str, variables: &HashMap<String, String>) -> Result<String> {
+	fn render_into(segments: &[Segment<'_>], variables: &HashMap<String, String>, rendered: &mut String) {
+		for segment in segments {
+			match segment {
+				Segment::Text(text) => rendered.push_str(text),
+				Segment::Variable(name) => match variables.get(*name) {
+					Some(value) => rendered.push_str(value),
+					None => rendered.push_str(&format!("${{{name}}}")),
+				},
+				Segment::Optional { name, body } =>
+					if variables.get(*name).is_some_and(|value| !value.is_empty()) {
+						render_into(body, variables, rendered);
+					},
+			}
+		}
+	}
+	let mut rendered = String::with_capacity(text.len());
+	render_into(&segments(text)?, variables, &mut rendered);
+	Ok(rendered)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_optional_section() {
+		let template = "From: ${from}\n${reply_to?Reply-To: ${reply_to}\n}Subject: ${subject}";
+		assert_eq!(variable_names(template).unwrap(), vec!["from", "reply_to", "reply_to", "subject"]);
+
+		let mut variables = HashMap::from([("from".to_string(), "a@x.com".to_string()), ("subject".to_string(), "hi".to_string())]);
+		assert_eq!(render(template, &variables).unwrap(), "From: a@x.com\nSubject: hi");
+		variables.insert("reply_to".to_string(), String::new());
+		assert_eq!(render(template, &variables).unwrap(), "From: a@x.com\nSubject: hi");
+		variables.insert("reply_to".to_string(), "b@x.com".to_string());
+		assert_eq!(render(template, &variables).unwrap(), "From: a@x.com\nReply-To: b@x.com\nSubject: hi");
+	}
+
+	#[test]
+	fn test_section_edge_cases() {
+		let variables = HashMap::from([("a".to_string(), "1".to_string()), ("b".to_string(), "2".to_string())]);
+		// Nested sections, escapes and lone `$` inside a body
+		assert_eq!(render("${a?[${b?b=${b}, }$${ $5]}", &variables).unwrap(), "[b=2, ${ $5]");
+		assert_eq!(render("${a?}${missing?gone}", &variables).unwrap(), "");
+		// A `}` outside any section is plain text
+		assert_eq!(render("{${a}}", &variables).unwrap(), "{1}");
+
+		let err = |text| variable_names(text).unwrap_err().to_string();
+		assert_eq!(err("${a?never closed"), "Unclosed placeholder `${a?`, an optional section needs a closing `}`");
+		assert_eq!(err("${a?${b}"), "Unclosed placeholder `${a?`, an optional section needs a closing `}`");
+		assert_eq!(err("${?x}"), "Empty placeholder `${?`");
+		assert_eq!(err("${a?${}}"), "Empty placeholder `${}`");
+	}
+}
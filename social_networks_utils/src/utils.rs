@@ -1,4 +1,6 @@
-use color_eyre::eyre::Result;
+use color_eyre::eyre::{Result, WrapErr, eyre};
+use jiff::{SignedDuration, Span, SpanRelativeTo, Timestamp, civil::Time, tz::TimeZone};
+use serde::{Deserialize, Serialize};
 use v_exchanges::{ExchangeName, RetryConfig};
 
 /// Returns (stack_used, stack_remaining) in bytes
@@ -54,15 +56,112 @@ pub fn log_stack_critical(context: &str, stack_used: usize) {
 	);
 }
 
-pub async fn btc_price(n_retries: u8) -> Result<u64> {
+/// Exponential reconnect backoff (`e^attempt` seconds), capped at 10 minutes.
+pub fn reconnect_delay(attempt: u32) -> std::time::Duration {
+	let delay_secs = std::f64::consts::E.powi(attempt as i32).min(600.0);
+	std::time::Duration::from_secs_f64(delay_secs)
+}
+
+/// Scale a delay by a random factor in `[0.5, 1.0)` so retries from several clients don't line up.
+pub fn with_jitter(delay: std::time::Duration) -> std::time::Duration {
+	use rand::RngExt as _;
+	delay.mul_f64(rand::rng().random_range(0.5..1.0))
+}
+
+/// Parse a duration in jiff's friendly format, e.g. `30s`, `5m`, `2h`, `1h30m`, `1d 12h`, `2w` (or ISO 8601, `PT5M`).
+/// Days count as 24 hours; months, years and negative durations are rejected. Each error is a single message, so it
+/// reads the same as a clap `value_parser` (the CLI's durations) as in the config.
+pub fn parse_duration(s: &str) -> Result<std::time::Duration> {
+	let span: Span = s.trim().parse().map_err(|e| eyre!("Malformed duration `{s}`: {e}"))?;
+	let duration = span
+		.to_duration(SpanRelativeTo::days_are_24_hours())
+		.map_err(|_| eyre!("Duration `{s}` must be in weeks or smaller units"))?;
+	std::time::Duration::try_from(duration).map_err(|_| eyre!("Duration `{s}` is negative"))
+}
+
+/// Format `d` in the friendly format [`parse_duration`] reads back, e.g. `90s` as `1m 30s` and `7200s` as `2h`.
+pub fn format_duration(d: std::time::Duration) -> String {
+	match SignedDuration::try_from(d) {
+		Ok(d) => format!("{d:#}"),
+		Err(_) => format!("{}s", d.as_secs()),
+	}
+}
+
+/// `#[serde(with = "human_duration")]` for config durations written as [`parse_duration`] strings.
+pub mod human_duration {
+	use std::time::Duration;
+
+	use serde::{Deserialize, Deserializer, Serializer};
+
+	pub fn serialize<S: Serializer>(d: &Duration, serializer: S) -> Result<S::Ok, S::Error> {
+		serializer.serialize_str(&super::format_duration(*d))
+	}
+
+	pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Duration, D::Error> {
+		let s = String::deserialize(deserializer)?;
+		super::parse_duration(&s).map_err(|e| serde::de::Error::custom(format!("{e:#}")))
+	}
+}
+
+/// Replace `path` with `contents` through a temp file in the same directory and a rename, so a reader running
+/// concurrently (another monitor, the CLI) sees the old file or the new one, never a half-written one.
+pub fn write_atomic(path: &std::path::Path, contents: impl AsRef<[u8]>) -> std::io::Result<()> {
+	replace_file(path, contents.as_ref(), None)
+}
+
+/// [`write_atomic`] for secrets: the file is readable by its owner only (0600) from the moment it's created.
+pub fn write_private(path: &std::path::Path, contents: impl AsRef<[u8]>) -> std::io::Result<()> {
+	replace_file(path, contents.as_ref(), Some(0o600))
+}
+
+fn replace_file(path: &std::path::Path, contents: &[u8], mode: Option<u32>) -> std::io::Result<()> {
+	use std::{io::Write as _, os::unix::fs::OpenOptionsExt as _};
+
+	let tmp = temp_sibling(path);
+	let write = || {
+		let mut options = std::fs::OpenOptions::new();
+		options.write(true).create(true).truncate(true);
+		if let Some(mode) = mode {
+			options.mode(mode);
+		}
+		options.open(&tmp)?.write_all(contents)?;
+		std::fs::rename(&tmp, path)
+	};
+	write().inspect_err(|_| {
+		let _ = std::fs::remove_file(&tmp);
+	})
+}
+
+/// `<path>.<pid>.tmp`, unique per process so two writers don't share a temp file.
+fn temp_sibling(path: &std::path::Path) -> std::path::PathBuf {
+	let mut name = path.file_name().unwrap_or_default().to_os_string();
+	name.push(format!(".{}.tmp", std::process::id()));
+	path.with_file_name(name)
+}
+
+/// Last price of `symbol`'s USDT perpetual on Binance, e.g. `coin_price("ETH", 3)`.
+pub async fn coin_price(symbol: &str, n_retries: u8) -> Result<f64> {
 	let mut binance_exchange = ExchangeName::Binance.init_client();
 	binance_exchange.set_retry_config(RetryConfig {
 		max_retries: n_retries as u32,
 		..Default::default()
 	});
 
-	let price = binance_exchange.price("BTC-USDT.P".into()).await?;
-	Ok(price as u64)
+	let pair = format!("{}-USDT.P", symbol.to_uppercase());
+	let price = binance_exchange.price(pair.as_str().into()).await?;
+	Ok(price)
+}
+
+/// Price rounded to 3 significant digits, but never coarser than to 100, with thousands separators:
+/// BTC 64_321.5 -> "64,300", ETH 3_456.7 -> "3,460", SOL 152.3 -> "152". Below 100, two decimals are kept.
+pub fn format_price(price: f64) -> String {
+	if price < 100.0 {
+		return format!("{price:.2}");
+	}
+	let digits = price.log10().floor() as i32 + 1;
+	let step = 10u64.pow((digits - 3).clamp(0, 2) as u32);
+	let rounded = ((price / step as f64).round() as u64) * step;
+	format_num_with_thousands(rounded, ",")
 }
 
 pub fn format_num_with_thousands(num: u64, sep: &'static str) -> String {
@@ -75,3 +174,219 @@ pub fn format_num_with_thousands(num: u64, sep: &'static str) -> String {
 		.unwrap()
 		.join(sep)
 }
+
+/// Daily window of local time during which a monitor is allowed to notify.
+/// `end` before `start` means the window crosses midnight; `start == end` means always active.
+/// `start` is inclusive, `end` exclusive.
+///
+/// Accepted in config either as a table `{ start = "09:00", end = "18:00", tz = "Europe/Paris" }`
+/// or as a compact string `"09:00-18:00 Europe/Paris"` (zone optional, defaults to UTC).
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(try_from = "ActiveHoursRepr")]
+pub struct ActiveHours {
+	pub start: Time,
+	pub end: Time,
+	#[serde(with = "jiff::fmt::serde::tz::required")]
+	pub tz: TimeZone,
+}
+
+impl ActiveHours {
+	pub fn is_active(&self, now: Timestamp) -> bool {
+		let t = now.to_zoned(self.tz.clone()).time();
+		match self.start.cmp(&self.end) {
+			std::cmp::Ordering::Less => self.start <= t && t < self.end,
+			std::cmp::Ordering::Greater => t >= self.start || t < self.end,
+			std::cmp::Ordering::Equal => true,
+		}
+	}
+}
+
+impl std::str::FromStr for ActiveHours {
+	type Err = color_eyre::eyre::Report;
+
+	fn from_str(s: &str) -> Result<Self> {
+		let (range, tz) = match s.trim().split_once(char::is_whitespace) {
+			Some((range, tz)) => (range, TimeZone::get(tz.trim()).wrap_err_with(|| format!("unknown time zone in active hours: {tz}"))?),
+			None => (s.trim(), TimeZone::UTC),
+		};
+		let (start, end) = range.split_once('-').ok_or_else(|| eyre!("active hours must look like `HH:MM-HH:MM [tz]`, got: {s}"))?;
+		Ok(Self {
+			start: start.parse().wrap_err_with(|| format!("invalid active hours start: {start}"))?,
+			end: end.parse().wrap_err_with(|| format!("invalid active hours end: {end}"))?,
+			tz,
+		})
+	}
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum ActiveHoursRepr {
+	Compact(String),
+	Table {
+		start: Time,
+		end: Time,
+		#[serde(with = "jiff::fmt::serde::tz::required")]
+		tz: TimeZone,
+	},
+}
+
+impl TryFrom<ActiveHoursRepr> for ActiveHours {
+	type Error = color_eyre::eyre::Report;
+
+	fn try_from(repr: ActiveHoursRepr) -> Result<Self> {
+		match repr {
+			ActiveHoursRepr::Compact(s) => s.parse(),
+			ActiveHoursRepr::Table { start, end, tz } => Ok(Self { start, end, tz }),
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn hours(s: &str) -> ActiveHours {
+		s.parse().unwrap()
+	}
+
+	fn at(s: &str) -> Timestamp {
+		s.parse().unwrap()
+	}
+
+	#[test]
+	fn test_format_price() {
+		assert_eq!(format_price(64_321.5), "64,300");
+		assert_eq!(format_price(64_350.0), "64,400");
+		assert_eq!(format_price(123_456.0), "123,500");
+		assert_eq!(format_price(3_456.7), "3,460");
+		assert_eq!(format_price(152.3), "152");
+		assert_eq!(format_price(0.5234), "0.52");
+	}
+
+	#[test]
+	fn test_write_atomic_replaces_file() {
+		let dir = std::env::temp_dir().join(format!("social_networks_write_atomic_test_{}", std::process::id()));
+		std::fs::create_dir_all(&dir).unwrap();
+		let path = dir.join("state.json");
+		write_atomic(&path, "old").unwrap();
+		write_atomic(&path, "new").unwrap();
+		assert_eq!(std::fs::read_to_string(&path).unwrap(), "new");
+		// No temp file left behind
+		assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 1);
+		std::fs::remove_dir_all(&dir).unwrap();
+	}
+
+	#[test]
+	fn test_write_private_is_owner_only() {
+		use std::os::unix::fs::PermissionsExt as _;
+
+		let dir = std::env::temp_dir().join(format!("social_networks_write_private_test_{}", std::process::id()));
+		std::fs::create_dir_all(&dir).unwrap();
+		let path = dir.join("token.json");
+		// Replacing a world-readable file doesn't keep its mode
+		std::fs::write(&path, "old").unwrap();
+		std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o644)).unwrap();
+		write_private(&path, "secret").unwrap();
+		assert_eq!(std::fs::read_to_string(&path).unwrap(), "secret");
+		assert_eq!(std::fs::metadata(&path).unwrap().permissions().mode() & 0o777, 0o600);
+		std::fs::remove_dir_all(&dir).unwrap();
+	}
+
+	#[test]
+	fn test_parse_duration() {
+		use std::time::Duration;
+		assert_eq!(parse_duration("30s").unwrap(), Duration::from_secs(30));
+		assert_eq!(parse_duration("5m").unwrap(), Duration::from_secs(5 * 60));
+		assert_eq!(parse_duration("2h").unwrap(), Duration::from_secs(2 * 3600));
+		assert_eq!(parse_duration("1d").unwrap(), Duration::from_secs(86_400));
+		assert_eq!(parse_duration("2w").unwrap(), Duration::from_secs(14 * 86_400));
+		assert_eq!(parse_duration("250ms").unwrap(), Duration::from_millis(250));
+		assert_eq!(parse_duration(" 1h30m ").unwrap(), Duration::from_secs(90 * 60));
+		assert_eq!(parse_duration("1d 12h").unwrap(), Duration::from_secs(36 * 3600));
+		assert_eq!(parse_duration("0s").unwrap(), Duration::ZERO);
+		assert_eq!(parse_duration("5 min").unwrap(), Duration::from_secs(5 * 60));
+		assert_eq!(parse_duration("1.5h").unwrap(), Duration::from_secs(90 * 60));
+
+		let err = |s: &str| parse_duration(s).unwrap_err().to_string();
+		assert!(err("").starts_with("Malformed duration ``: "), "{}", err(""));
+		assert!(err("30").starts_with("Malformed duration `30`: "), "{}", err("30"));
+		assert!(err("h").starts_with("Malformed duration `h`: "), "{}", err("h"));
+		assert_eq!(err("-5m"), "Duration `-5m` is negative");
+		assert_eq!(err("1 month"), "Duration `1 month` must be in weeks or smaller units");
+		assert!(parse_duration("99999999999999999999w").is_err());
+	}
+
+	#[test]
+	fn test_format_duration() {
+		use std::time::Duration;
+		assert_eq!(format_duration(Duration::from_secs(90)), "1m 30s");
+		assert_eq!(format_duration(Duration::from_secs(7200)), "2h");
+		assert_eq!(format_duration(Duration::from_millis(1500)), "1s 500ms");
+		assert_eq!(format_duration(Duration::ZERO), "0s");
+		for s in ["30s", "5m", "2h", "1d", "2w", "250ms", "1h30m"] {
+			let d = parse_duration(s).unwrap();
+			assert_eq!(parse_duration(&format_duration(d)).unwrap(), d);
+		}
+	}
+
+	#[test]
+	fn test_active_hours_same_day() {
+		let h = hours("09:00-18:00");
+		assert!(!h.is_active(at("2025-01-15T08:59:59Z")));
+		assert!(h.is_active(at("2025-01-15T09:00:00Z")));
+		assert!(h.is_active(at("2025-01-15T12:00:00Z")));
+		assert!(h.is_active(at("2025-01-15T17:59:59Z")));
+		assert!(!h.is_active(at("2025-01-15T18:00:00Z")));
+		assert!(!h.is_active(at("2025-01-15T23:00:00Z")));
+	}
+
+	#[test]
+	fn test_active_hours_crossing_midnight() {
+		let h = hours("22:00-07:00");
+		assert!(!h.is_active(at("2025-01-15T21:59:59Z")));
+		assert!(h.is_active(at("2025-01-15T22:00:00Z")));
+		assert!(h.is_active(at("2025-01-15T23:59:59Z")));
+		assert!(h.is_active(at("2025-01-16T00:00:00Z")));
+		assert!(h.is_active(at("2025-01-16T06:59:59Z")));
+		assert!(!h.is_active(at("2025-01-16T07:00:00Z")));
+		assert!(!h.is_active(at("2025-01-16T12:00:00Z")));
+	}
+
+	#[test]
+	fn test_active_hours_crossing_midnight_in_tz() {
+		let h = hours("22:00-02:00 Europe/Paris");
+		// Paris is UTC+1 in January
+		assert!(h.is_active(at("2025-01-15T21:30:00Z")));
+		assert!(h.is_active(at("2025-01-15T23:30:00Z")));
+		assert!(!h.is_active(at("2025-01-16T01:30:00Z")));
+		assert!(!h.is_active(at("2025-01-15T20:59:00Z")));
+		// and UTC+2 in July
+		assert!(h.is_active(at("2025-07-15T20:00:00Z")));
+		assert!(!h.is_active(at("2025-07-16T00:00:00Z")));
+	}
+
+	#[test]
+	fn test_active_hours_equal_bounds_always_active() {
+		let h = hours("00:00-00:00");
+		assert!(h.is_active(at("2025-01-15T00:00:00Z")));
+		assert!(h.is_active(at("2025-01-15T13:37:00Z")));
+	}
+
+	#[test]
+	fn test_active_hours_parse_errors() {
+		assert!("09:00".parse::<ActiveHours>().is_err());
+		assert!("9am-5pm".parse::<ActiveHours>().is_err());
+		assert!("09:00-18:00 Mars/Olympus".parse::<ActiveHours>().is_err());
+	}
+
+	#[test]
+	fn test_active_hours_deserialize_both_forms() {
+		let compact: ActiveHours = serde_json::from_str(r#""09:00-18:00 Europe/Paris""#).unwrap();
+		let table: ActiveHours = serde_json::from_str(r#"{"start": "09:00", "end": "18:00", "tz": "Europe/Paris"}"#).unwrap();
+		for h in [compact, table] {
+			assert_eq!(h.start, Time::constant(9, 0, 0, 0));
+			assert_eq!(h.end, Time::constant(18, 0, 0, 0));
+			assert_eq!(h.tz.iana_name(), Some("Europe/Paris"));
+		}
+	}
+}